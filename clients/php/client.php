@@ -20,12 +20,91 @@ enum EchoRSCommands: string
     case HLLCount = "\x0f\x00";
     case HLLReset = "\x10\x00";
     case FLUSH = "\x11\x00";
-    case Unknown = "\x12\x00";
+    case JsonSet = "\x12\x00";
+    case JsonGet = "\x13\x00";
+    case JsonDel = "\x14\x00";
+    case JsonNumIncrBy = "\x15\x00";
+    case JsonArrAppend = "\x16\x00";
+    case JsonArrLen = "\x17\x00";
+    case JsonType = "\x18\x00";
+    case Append = "\x19\x00";
+    case StrLen = "\x1a\x00";
+    case GetRange = "\x1b\x00";
+    case SetRange = "\x1c\x00";
+    case GetSet = "\x1d\x00";
+    case GetDel = "\x1e\x00";
+    case GetEx = "\x1f\x00";
+    case MGet = "\x20\x00";
+    case MSet = "\x21\x00";
+    case MSetNX = "\x22\x00";
+    case Exists = "\x23\x00";
+    case Type = "\x24\x00";
+    case Ttl = "\x25\x00";
+    case PTtl = "\x26\x00";
+    case Persist = "\x27\x00";
+    case Expire = "\x28\x00";
+    case PExpire = "\x29\x00";
+    case ExpireAt = "\x2a\x00";
+    case PExpireAt = "\x2b\x00";
+    case ListPushEx = "\x2c\x00";
+    case HLLAddEx = "\x2d\x00";
+    case Scan = "\x2e\x00";
+    case Keys = "\x2f\x00";
+    case DbSize = "\x30\x00";
+    case RandomKey = "\x31\x00";
+    case Rename = "\x32\x00";
+    case RenameNX = "\x33\x00";
+    case Copy = "\x34\x00";
+    case Select = "\x35\x00";
+    case FlushDb = "\x36\x00";
+    case SwapDb = "\x37\x00";
+    case Move = "\x38\x00";
+    case MemoryUsage = "\x39\x00";
+    case MemoryStats = "\x3a\x00";
+    case ObjectEncoding = "\x3b\x00";
+    case ObjectIdleTime = "\x3c\x00";
+    case ObjectFreq = "\x3d\x00";
+    case DebugObject = "\x3e\x00";
+    case Save = "\x3f\x00";
+    case BgSave = "\x40\x00";
+    case LastSave = "\x41\x00";
+    case HLLLoad = "\x42\x00";
+    case BgRewriteAof = "\x43\x00";
+    case Dump = "\x44\x00";
+    case Restore = "\x45\x00";
+    case ReplicaOf = "\x46\x00";
+    case Psync = "\x47\x00";
+    case ClusterSlots = "\x48\x00";
+    case ClusterNodes = "\x49\x00";
+    case ClusterKeySlot = "\x4a\x00";
+    case ClusterSetSlot = "\x4b\x00";
+    case ClusterGetKeysInSlot = "\x4c\x00";
+    case ClusterCountKeysInSlot = "\x4d\x00";
+    case Asking = "\x4e\x00";
+    case Migrate = "\x4f\x00";
+    case Multi = "\x50\x00";
+    case Exec = "\x51\x00";
+    case Discard = "\x52\x00";
+    case Watch = "\x53\x00";
+    case Unwatch = "\x54\x00";
+    case Eval = "\x55\x00";
+    case EvalSha = "\x56\x00";
+    case ScriptLoad = "\x57\x00";
+    case ScriptKill = "\x58\x00";
+    case Publish = "\x59\x00";
+    case Subscribe = "\x5a\x00";
+    case Unsubscribe = "\x5b\x00";
+    case PSubscribe = "\x5c\x00";
+    case PUnsubscribe = "\x5d\x00";
+    case PubSubChannels = "\x5e\x00";
+    case PubSubNumSub = "\x5f\x00";
+    case Unknown = "\x60\x00";
 }
 enum EchoRSCommandResult: string
 {
     case OK = "\x01";
     case ERROR = "\x02";
+    case PUSH = "\x03";
 }
 enum ResultType: int
 {
@@ -35,14 +114,29 @@ enum ResultType: int
     case LIST = 4;
     case MAP = 5;
     case LONG = 6;
+    case NIL = 7;
+    case ARRAY = 8;
 }
-enum ExpireParamType
+enum ExpireParamType: int
 {
-    case EXPIRE_AT;
-    case EXPIRE_IN;
-    case KEEP_TTL;
-    case None;
+    case None = 0;
+    case EXPIRE_AT_SECONDS = 1;
+    case EXPIRE_AT = 2;
+    case EXPIRE_IN_SECONDS = 3;
+    case EXPIRE_IN = 4;
+    case KEEP_TTL = 5;
 }
+enum ExpireCondition: int
+{
+    case Always = 0;
+    case NX = 1;
+    case XX = 2;
+    case GT = 3;
+    case LT = 4;
+}
+/**
+ * EXPIRE_AT and EXPIRE_IN take milliseconds, the _SECONDS variants take seconds.
+ */
 class ExpireParam
 {
     public function __construct(private ExpireParamType $type, private ?int $value = null)
@@ -51,22 +145,26 @@ class ExpireParam
     public function serialize()
     {
         switch ($this->type) {
-            case ExpireParamType::EXPIRE_AT:
-                if ($this->value == null) return "";
-                return pack('V', 8) . pack('P', $this->value);
-            case ExpireParamType::EXPIRE_IN:
-                if ($this->value == null) return "";
-                return pack('V', 4) . pack('V', $this->value);
             case ExpireParamType::KEEP_TTL:
-                return pack('V', 1) . pack('C', 0);
+                return pack('V', 1) . pack('C', $this->type->value);
             case ExpireParamType::None:
                 return "";
-                break;
+            default:
+                if ($this->value === null) return "";
+                return pack('V', 9) . pack('C', $this->type->value) . pack('P', $this->value);
         }
     }
 }
 class EchoRSClient
 {
+    /**
+     * Server protocol this client understands, since version 2 missing keys are returned as null
+     * results instead of "Key not found" errors, since version 3 expire arguments are tagged.
+     */
+    const PROTOCOL_VERSION = 3;
+    const SET_NX = 1;
+    const SET_XX = 2;
+    const SET_GET = 4;
 
     /**
      * 
@@ -78,11 +176,22 @@ class EchoRSClient
         $this->fp = fsockopen("tcp://$ip", $port, $errno, $errstr);
         if (!$this->fp)
             throw new Exception("Could not connect to server");
+        $this->checkProtocol();
+    }
+    // refuses servers whose protocol_version in INFO differs from PROTOCOL_VERSION
+    private function checkProtocol()
+    {
+        $info = $this->processCommand(EchoRSCommands::Info->value)["result"];
+        if (!is_string($info) || !preg_match('/^protocol_version: (\d+)$/m', $info, $match))
+            throw new Exception("Server did not report its protocol version");
+        if ((int)$match[1] !== self::PROTOCOL_VERSION)
+            throw new Exception("Server protocol version $match[1] is not supported, expected " . self::PROTOCOL_VERSION);
     }
     private function getExpire(ExpireParamType $expType, ?int $value = null)
     {
     }
-    private function processCommand(string $cmd)
+    // with $nestedReplies an array reply holds a full reply per element, as sent by EXEC
+    private function processCommand(string $cmd, bool $nestedReplies = false)
     {
         $len = strlen($cmd);
         $lens = pack('V', $len);
@@ -91,20 +200,43 @@ class EchoRSClient
         $response = fread($this->fp, 8096);
         $result = EchoRSCommandResult::from($response[0]);
         $response = substr($response, 1);
+        $nested = $nestedReplies && ord($response[0]) == ResultType::ARRAY->value;
         return [
             "status" => $result->name,
-            "result" => $this->interpretValue($response)
+            "result" => $nested ? $this->interpretReplies($response) : $this->interpretValue($response)
         ];
     }
-    public function setString(string $key, string $value, ?ExpireParam $expire  = null)
+    public function setString(string $key, string $value, ?ExpireParam $expire  = null, int $flags = 0)
     {
         $keylen = pack('V', strlen($key));
         $cmd = EchoRSCommands::SetString->value . $keylen . $key . pack('V', strlen($value)) . $value;
+        $expireArg = $expire ? $expire->serialize() : "";
+        if ($expireArg === "" && $flags)
+            $expireArg = pack('V', 0);
+        $cmd .= $expireArg;
+        if ($flags)
+            $cmd .= pack('V', 1) . pack('C', $flags);
+        return $this->processCommand($cmd);
+    }
+    public function getSet(string $key, string $value)
+    {
+        $cmd = EchoRSCommands::GetSet->value . pack('V', strlen($key)) . $key;
+        $cmd .= pack('V', strlen($value)) . $value;
+        return $this->processCommand($cmd);
+    }
+    public function getDel(string $key)
+    {
+        $cmd = EchoRSCommands::GetDel->value . pack('V', strlen($key)) . $key;
+        return $this->processCommand($cmd);
+    }
+    public function getEx(string $key, ?ExpireParam $expire = null)
+    {
+        $cmd = EchoRSCommands::GetEx->value . pack('V', strlen($key)) . $key;
         if ($expire)
             $cmd .= $expire->serialize();
         return $this->processCommand($cmd);
     }
-    public function incrementFloat(string $key, ?float $by = null)
+    public function incrementFloat(string $key, ?float $by = null, ?ExpireParam $expire = null, bool $expireOnCreateOnly = false)
     {
         $keylen = pack('V', strlen($key));
         $cmd = EchoRSCommands::IncrementFloat->value . $keylen . $key;
@@ -112,10 +244,12 @@ class EchoRSClient
             $byB = pack('f', $by);
             $byBL = pack('V', strlen($byB));
             $cmd .= $byBL . $byB;
-        }
+        } else if ($expire)
+            $cmd .= pack('V', 0);
+        $cmd .= $this->serializeIncrementExpire($expire, $expireOnCreateOnly);
         return $this->processCommand($cmd);
     }
-    public function incrementInt(string $key, ?int $by = null)
+    public function incrementInt(string $key, ?int $by = null, ?ExpireParam $expire = null, bool $expireOnCreateOnly = false)
     {
         $keylen = pack('V', strlen($key));
         $cmd = EchoRSCommands::IncrementInt->value . $keylen . $key;
@@ -123,19 +257,182 @@ class EchoRSClient
             $byB = pack('V', $by);
             $byBL = pack('V', strlen($byB));
             $cmd .= $byBL . $byB;
-        }
+        } else if ($expire)
+            $cmd .= pack('V', 0);
+        $cmd .= $this->serializeIncrementExpire($expire, $expireOnCreateOnly);
         return $this->processCommand($cmd);
     }
+    private function serializeIncrementExpire(?ExpireParam $expire, bool $expireOnCreateOnly)
+    {
+        if (!$expire)
+            return "";
+        $expireArg = $expire->serialize();
+        if ($expireArg === "")
+            $expireArg = pack('V', 0);
+        return $expireArg . pack('V', 1) . pack('C', $expireOnCreateOnly ? 1 : 0);
+    }
     public function get(string $key)
     {
         $keylen = pack('V', strlen($key));
         $cmd = EchoRSCommands::Get->value . $keylen . $key;
         return $this->processCommand($cmd);
     }
-    public function listPush(string $key, array $values)
+    public function append(string $key, string $value)
+    {
+        $cmd = EchoRSCommands::Append->value . pack('V', strlen($key)) . $key;
+        $cmd .= pack('V', strlen($value)) . $value;
+        return $this->processCommand($cmd);
+    }
+    public function strLen(string $key)
+    {
+        $cmd = EchoRSCommands::StrLen->value . pack('V', strlen($key)) . $key;
+        return $this->processCommand($cmd);
+    }
+    public function getRange(string $key, int $start, int $end)
+    {
+        $cmd = EchoRSCommands::GetRange->value . pack('V', strlen($key)) . $key;
+        $cmd .= pack('V', 4) . pack('l', $start) . pack('V', 4) . pack('l', $end);
+        return $this->processCommand($cmd);
+    }
+    public function setRange(string $key, int $offset, string $value)
+    {
+        $cmd = EchoRSCommands::SetRange->value . pack('V', strlen($key)) . $key;
+        $cmd .= pack('V', 4) . pack('V', $offset) . pack('V', strlen($value)) . $value;
+        return $this->processCommand($cmd);
+    }
+    public function mget(array $keys)
+    {
+        $cmd = EchoRSCommands::MGet->value;
+        foreach ($keys as $key) {
+            $cmd .= pack('V', strlen($key)) . $key;
+        }
+        return $this->processCommand($cmd);
+    }
+    /**
+     * @param array<string, int|float|string> $values
+     */
+    public function mset(array $values)
+    {
+        return $this->processCommand(EchoRSCommands::MSet->value . $this->serializePairs($values));
+    }
+    /**
+     * @param array<string, int|float|string> $values
+     */
+    public function msetNX(array $values)
+    {
+        return $this->processCommand(EchoRSCommands::MSetNX->value . $this->serializePairs($values));
+    }
+    private function serializePairs(array $values)
+    {
+        $cmd = "";
+        foreach ($values as $key => $val) {
+            $key = strval($key);
+            $typed = match (true) {
+                is_int($val) => pack('C', ResultType::INT->value) . pack('l', $val),
+                is_float($val) => pack('C', ResultType::FLOAT->value) . pack('f', $val),
+                default => pack('C', ResultType::STRING->value) . pack('V', strlen(strval($val))) . strval($val)
+            };
+            $cmd .= pack('V', strlen($key)) . $key . pack('V', strlen($typed)) . $typed;
+        }
+        return $cmd;
+    }
+    public function exists(array $keys)
+    {
+        $cmd = EchoRSCommands::Exists->value;
+        foreach ($keys as $key) {
+            $cmd .= pack('V', strlen($key)) . $key;
+        }
+        return $this->processCommand($cmd);
+    }
+    public function type(string $key)
+    {
+        return $this->processCommand(EchoRSCommands::Type->value . pack('V', strlen($key)) . $key);
+    }
+    public function ttl(string $key)
+    {
+        return $this->processCommand(EchoRSCommands::Ttl->value . pack('V', strlen($key)) . $key);
+    }
+    public function pttl(string $key)
+    {
+        return $this->processCommand(EchoRSCommands::PTtl->value . pack('V', strlen($key)) . $key);
+    }
+    public function persist(string $key)
+    {
+        return $this->processCommand(EchoRSCommands::Persist->value . pack('V', strlen($key)) . $key);
+    }
+    /**
+     * @param EchoRSCommands $command one of Expire, PExpire, ExpireAt or PExpireAt
+     */
+    public function expire(
+        string $key,
+        int $time,
+        EchoRSCommands $command = EchoRSCommands::Expire,
+        ExpireCondition $condition = ExpireCondition::Always
+    ) {
+        $cmd = $command->value . pack('V', strlen($key)) . $key . pack('V', 8) . pack('P', $time);
+        if ($condition != ExpireCondition::Always)
+            $cmd .= pack('V', 1) . pack('C', $condition->value);
+        return $this->processCommand($cmd);
+    }
+    /**
+     * Returns [next cursor, keys], iteration is complete when the cursor is 0 again
+     */
+    public function scan(int $cursor = 0, string $pattern = "", int $count = 0, string $type = "")
+    {
+        $cmd = EchoRSCommands::Scan->value . pack('V', 8) . pack('P', $cursor);
+        $cmd .= pack('V', strlen($pattern)) . $pattern;
+        $countB = $count > 0 ? pack('V', $count) : "";
+        $cmd .= pack('V', strlen($countB)) . $countB;
+        $cmd .= pack('V', strlen($type)) . $type;
+        return $this->processCommand($cmd);
+    }
+    public function keys(string $pattern)
+    {
+        return $this->processCommand(EchoRSCommands::Keys->value . pack('V', strlen($pattern)) . $pattern);
+    }
+    public function dbSize()
+    {
+        return $this->processCommand(EchoRSCommands::DbSize->value);
+    }
+    public function randomKey()
+    {
+        return $this->processCommand(EchoRSCommands::RandomKey->value);
+    }
+    public function rename(string $from, string $to, bool $nx = false)
+    {
+        $command = $nx ? EchoRSCommands::RenameNX : EchoRSCommands::Rename;
+        $cmd = $command->value . pack('V', strlen($from)) . $from . pack('V', strlen($to)) . $to;
+        return $this->processCommand($cmd);
+    }
+    public function copy(string $from, string $to, bool $replace = false)
+    {
+        $cmd = EchoRSCommands::Copy->value . pack('V', strlen($from)) . $from . pack('V', strlen($to)) . $to;
+        if ($replace)
+            $cmd .= pack('V', 1) . pack('C', 1);
+        return $this->processCommand($cmd);
+    }
+    public function dump(string $key)
+    {
+        return $this->processCommand(EchoRSCommands::Dump->value . pack('V', strlen($key)) . $key);
+    }
+    /**
+     * $ttl in milliseconds, null keeps the TTL stored in the payload and 0 restores without TTL
+     */
+    public function restore(string $key, string $payload, ?int $ttl = null, bool $replace = false, bool $absTtl = false)
+    {
+        $cmd = EchoRSCommands::Restore->value . pack('V', strlen($key)) . $key . pack('V', strlen($payload)) . $payload;
+        $cmd .= $ttl === null ? pack('V', 0) : pack('V', 8) . pack('P', $ttl);
+        $cmd .= pack('V', 1) . pack('C', ($replace ? 1 : 0) | ($absTtl ? 2 : 0));
+        return $this->processCommand($cmd);
+    }
+    public function listPush(string $key, array $values, ?ExpireParam $expire = null)
     {
         $keylen = pack('V', strlen($key));
-        $cmd = EchoRSCommands::ListPush->value . $keylen . $key;
+        $expireArg = $expire ? $expire->serialize() : "";
+        if ($expireArg === "")
+            $cmd = EchoRSCommands::ListPush->value . $keylen . $key;
+        else
+            $cmd = EchoRSCommands::ListPushEx->value . $keylen . $key . $expireArg;
         foreach ($values as $val) {
             //force parse to string
             $strVal = strval($val);
@@ -172,10 +469,14 @@ class EchoRSClient
         $cmd = EchoRSCommands::ListLength->value . $keylen . $key;
         return $this->processCommand($cmd);
     }
-    public function hllAdd(string $key, array $values)
+    public function hllAdd(string $key, array $values, ?ExpireParam $expire = null)
     {
         $keylen = pack('V', strlen($key));
-        $cmd = EchoRSCommands::HLLAdd->value . $keylen . $key;
+        $expireArg = $expire ? $expire->serialize() : "";
+        if ($expireArg === "")
+            $cmd = EchoRSCommands::HLLAdd->value . $keylen . $key;
+        else
+            $cmd = EchoRSCommands::HLLAddEx->value . $keylen . $key . $expireArg;
         foreach ($values as $val) {
             //force parse to string
             $strVal = strval($val);
@@ -195,12 +496,283 @@ class EchoRSClient
         $cmd = EchoRSCommands::FLUSH->value;
         return $this->processCommand($cmd);
     }
+    public function flushDb()
+    {
+        return $this->processCommand(EchoRSCommands::FlushDb->value);
+    }
+    public function select(int $db)
+    {
+        return $this->processCommand(EchoRSCommands::Select->value . pack('V', 4) . pack('V', $db));
+    }
+    public function swapDb(int $a, int $b)
+    {
+        $cmd = EchoRSCommands::SwapDb->value . pack('V', 4) . pack('V', $a) . pack('V', 4) . pack('V', $b);
+        return $this->processCommand($cmd);
+    }
+    public function move(string $key, int $db)
+    {
+        $cmd = EchoRSCommands::Move->value . pack('V', strlen($key)) . $key . pack('V', 4) . pack('V', $db);
+        return $this->processCommand($cmd);
+    }
+    public function memoryUsage(string $key)
+    {
+        return $this->processCommand(EchoRSCommands::MemoryUsage->value . pack('V', strlen($key)) . $key);
+    }
+    /**
+     * The server replies a flat name, value list, returned here as an associative array
+     */
+    public function memoryStats()
+    {
+        $response = $this->processCommand(EchoRSCommands::MemoryStats->value);
+        if (is_array($response["result"])) {
+            $stats = [];
+            foreach (array_chunk($response["result"], 2) as [$name, $value])
+                $stats[$name] = $value;
+            $response["result"] = $stats;
+        }
+        return $response;
+    }
+    /**
+     * @param EchoRSCommands $command one of ObjectEncoding, ObjectIdleTime or ObjectFreq
+     */
+    public function object(EchoRSCommands $command, string $key)
+    {
+        return $this->processCommand($command->value . pack('V', strlen($key)) . $key);
+    }
+    public function debugObject(string $key)
+    {
+        return $this->processCommand(EchoRSCommands::DebugObject->value . pack('V', strlen($key)) . $key);
+    }
+    public function save()
+    {
+        return $this->processCommand(EchoRSCommands::Save->value);
+    }
+    public function bgSave()
+    {
+        return $this->processCommand(EchoRSCommands::BgSave->value);
+    }
+    public function lastSave()
+    {
+        return $this->processCommand(EchoRSCommands::LastSave->value);
+    }
+    /**
+     * Without host the server stops replicating and accepts writes again
+     */
+    public function replicaOf(?string $host = null, ?int $port = null)
+    {
+        $cmd = EchoRSCommands::ReplicaOf->value;
+        if ($host !== null)
+            $cmd .= pack('V', strlen($host)) . $host . pack('V', 2) . pack('v', $port);
+        return $this->processCommand($cmd);
+    }
+    /**
+     * Commands sent after multi() reply QUEUED and run together on exec()
+     */
+    public function multi()
+    {
+        return $this->processCommand(EchoRSCommands::Multi->value);
+    }
+    // one reply per queued command, null result when a watched key changed
+    public function exec()
+    {
+        return $this->processCommand(EchoRSCommands::Exec->value, true);
+    }
+    public function discard()
+    {
+        return $this->processCommand(EchoRSCommands::Discard->value);
+    }
+    public function watch(array $keys)
+    {
+        $cmd = EchoRSCommands::Watch->value;
+        foreach ($keys as $key)
+            $cmd .= pack('V', strlen($key)) . $key;
+        return $this->processCommand($cmd);
+    }
+    public function unwatch()
+    {
+        return $this->processCommand(EchoRSCommands::Unwatch->value);
+    }
+    public function eval(string $script, array $keys = [], array $args = [])
+    {
+        return $this->processCommand($this->scriptCommand(EchoRSCommands::Eval->value, $script, $keys, $args));
+    }
+    public function evalSha(string $sha, array $keys = [], array $args = [])
+    {
+        return $this->processCommand($this->scriptCommand(EchoRSCommands::EvalSha->value, $sha, $keys, $args));
+    }
+    private function scriptCommand(string $cmd, string $script, array $keys, array $args)
+    {
+        $cmd .= pack('V', strlen($script)) . $script . pack('V', 4) . pack('V', count($keys));
+        foreach (array_merge($keys, $args) as $arg)
+            $cmd .= pack('V', strlen($arg)) . $arg;
+        return $cmd;
+    }
+    public function scriptLoad(string $script)
+    {
+        return $this->processCommand(EchoRSCommands::ScriptLoad->value . pack('V', strlen($script)) . $script);
+    }
+    public function scriptKill()
+    {
+        return $this->processCommand(EchoRSCommands::ScriptKill->value);
+    }
+    public function publish(string $channel, string $message)
+    {
+        $cmd = EchoRSCommands::Publish->value . pack('V', strlen($channel)) . $channel;
+        return $this->processCommand($cmd . pack('V', strlen($message)) . $message);
+    }
+    public function subscribe(array $channels)
+    {
+        return $this->processCommand($this->namesCommand(EchoRSCommands::Subscribe->value, $channels));
+    }
+    public function unsubscribe(array $channels = [])
+    {
+        return $this->processCommand($this->namesCommand(EchoRSCommands::Unsubscribe->value, $channels));
+    }
+    public function psubscribe(array $patterns)
+    {
+        return $this->processCommand($this->namesCommand(EchoRSCommands::PSubscribe->value, $patterns));
+    }
+    public function punsubscribe(array $patterns = [])
+    {
+        return $this->processCommand($this->namesCommand(EchoRSCommands::PUnsubscribe->value, $patterns));
+    }
+    public function pubsubChannels(?string $pattern = null)
+    {
+        $cmd = EchoRSCommands::PubSubChannels->value;
+        if ($pattern !== null)
+            $cmd .= pack('V', strlen($pattern)) . $pattern;
+        return $this->processCommand($cmd);
+    }
+    public function pubsubNumSub(array $channels)
+    {
+        return $this->processCommand($this->namesCommand(EchoRSCommands::PubSubNumSub->value, $channels));
+    }
+    private function namesCommand(string $cmd, array $names)
+    {
+        foreach ($names as $name)
+            $cmd .= pack('V', strlen($name)) . $name;
+        return $cmd;
+    }
+    // waits for the next published message once subscribed
+    public function readMessage()
+    {
+        $response = fread($this->fp, 8096);
+        $result = EchoRSCommandResult::from($response[0]);
+        return [
+            "status" => $result->name,
+            "result" => $this->interpretValue(substr($response, 1))
+        ];
+    }
+    public function clusterSlots()
+    {
+        return $this->processCommand(EchoRSCommands::ClusterSlots->value);
+    }
+    public function clusterNodes()
+    {
+        return $this->processCommand(EchoRSCommands::ClusterNodes->value);
+    }
+    public function clusterKeySlot(string $key)
+    {
+        return $this->processCommand(EchoRSCommands::ClusterKeySlot->value . pack('V', strlen($key)) . $key);
+    }
+    /**
+     * $action: 0 NODE, 1 MIGRATING, 2 IMPORTING, 3 STABLE (no node)
+     */
+    public function clusterSetSlot(int $slot, int $action, ?string $node = null)
+    {
+        $cmd = EchoRSCommands::ClusterSetSlot->value . pack('V', 2) . pack('v', $slot) . pack('V', 1) . pack('C', $action);
+        if ($node !== null)
+            $cmd .= pack('V', strlen($node)) . $node;
+        return $this->processCommand($cmd);
+    }
+    public function clusterGetKeysInSlot(int $slot, int $count)
+    {
+        $cmd = EchoRSCommands::ClusterGetKeysInSlot->value . pack('V', 2) . pack('v', $slot) . pack('V', 4) . pack('V', $count);
+        return $this->processCommand($cmd);
+    }
+    public function clusterCountKeysInSlot(int $slot)
+    {
+        return $this->processCommand(EchoRSCommands::ClusterCountKeysInSlot->value . pack('V', 2) . pack('v', $slot));
+    }
+    public function asking()
+    {
+        return $this->processCommand(EchoRSCommands::Asking->value);
+    }
+    public function migrate(string $host, int $port, string $key, int $timeoutMs = 1000)
+    {
+        $cmd = EchoRSCommands::Migrate->value . pack('V', strlen($host)) . $host . pack('V', 2) . pack('v', $port);
+        $cmd .= pack('V', strlen($key)) . $key . pack('V', 8) . pack('P', $timeoutMs);
+        return $this->processCommand($cmd);
+    }
+    public function bgRewriteAof()
+    {
+        return $this->processCommand(EchoRSCommands::BgRewriteAof->value);
+    }
+    // one byte per register, 2^bucketBits registers
+    public function hllLoad(string $key, int $bucketBits, string $registers)
+    {
+        $value = chr($bucketBits) . $registers;
+        $cmd = EchoRSCommands::HLLLoad->value . pack('V', strlen($key)) . $key;
+        $cmd .= pack('V', strlen($value)) . $value;
+        return $this->processCommand($cmd);
+    }
     public function hllReset(string $key)
     {
         $keylen = pack('V', strlen($key));
         $cmd = EchoRSCommands::HLLReset->value . $keylen . $key;
         return $this->processCommand($cmd);
     }
+    public function jsonSet(string $key, string $path, mixed $value)
+    {
+        $json = json_encode($value);
+        $cmd = EchoRSCommands::JsonSet->value . pack('V', strlen($key)) . $key;
+        $cmd .= pack('V', strlen($path)) . $path . pack('V', strlen($json)) . $json;
+        return $this->processCommand($cmd);
+    }
+    public function jsonGet(string $key, string $path = '$')
+    {
+        $cmd = EchoRSCommands::JsonGet->value . pack('V', strlen($key)) . $key;
+        $cmd .= pack('V', strlen($path)) . $path;
+        $response = $this->processCommand($cmd);
+        if ($response["status"] == EchoRSCommandResult::OK->name)
+            $response["result"] = json_decode($response["result"], true);
+        return $response;
+    }
+    public function jsonDel(string $key, string $path = '$')
+    {
+        $cmd = EchoRSCommands::JsonDel->value . pack('V', strlen($key)) . $key;
+        $cmd .= pack('V', strlen($path)) . $path;
+        return $this->processCommand($cmd);
+    }
+    public function jsonNumIncrBy(string $key, string $path, int|float $by)
+    {
+        $byJ = json_encode($by);
+        $cmd = EchoRSCommands::JsonNumIncrBy->value . pack('V', strlen($key)) . $key;
+        $cmd .= pack('V', strlen($path)) . $path . pack('V', strlen($byJ)) . $byJ;
+        return $this->processCommand($cmd);
+    }
+    public function jsonArrAppend(string $key, string $path, array $values)
+    {
+        $cmd = EchoRSCommands::JsonArrAppend->value . pack('V', strlen($key)) . $key;
+        $cmd .= pack('V', strlen($path)) . $path;
+        foreach ($values as $val) {
+            $json = json_encode($val);
+            $cmd .= pack('V', strlen($json)) . $json;
+        }
+        return $this->processCommand($cmd);
+    }
+    public function jsonArrLen(string $key, string $path = '$')
+    {
+        $cmd = EchoRSCommands::JsonArrLen->value . pack('V', strlen($key)) . $key;
+        $cmd .= pack('V', strlen($path)) . $path;
+        return $this->processCommand($cmd);
+    }
+    public function jsonType(string $key, string $path = '$')
+    {
+        $cmd = EchoRSCommands::JsonType->value . pack('V', strlen($key)) . $key;
+        $cmd .= pack('V', strlen($path)) . $path;
+        return $this->processCommand($cmd);
+    }
     private function interpretList(string $data)
     {
         $data = substr($data, 1); // remove byte of list type
@@ -218,6 +790,33 @@ class EchoRSClient
         $data = substr($data, $len + 4);
         return $str;
     }
+    private function interpretArray(string $data)
+    {
+        $count = unpack('V', substr($data, 1, 4))[1];
+        $data = substr($data, 5);
+        $arr = [];
+        for ($i = 0; $i < $count; $i++) {
+            $len = unpack('V', substr($data, 0, 4))[1];
+            $arr[] = $this->interpretValue(substr($data, 4, $len));
+            $data = substr($data, $len + 4);
+        }
+        return $arr;
+    }
+    private function interpretReplies(string $data)
+    {
+        $count = unpack('V', substr($data, 1, 4))[1];
+        $data = substr($data, 5);
+        $replies = [];
+        for ($i = 0; $i < $count; $i++) {
+            $len = unpack('V', substr($data, 0, 4))[1];
+            $replies[] = [
+                "status" => EchoRSCommandResult::from($data[4])->name,
+                "result" => $this->interpretValue(substr($data, 5, $len - 1))
+            ];
+            $data = substr($data, $len + 4);
+        }
+        return $replies;
+    }
     private function interpretValue(string $data)
     {
         $type = ResultType::from(ord($data[0]));
@@ -226,7 +825,9 @@ class EchoRSClient
             ResultType::FLOAT => unpack('f', substr($data, 1))[1],
             ResultType::STRING => $this->interpretString($data),
             ResultType::LIST => $this->interpretList($data),
-            ResultType::LONG => unpack('P', substr($data, 1))[1]
+            ResultType::LONG => unpack('P', substr($data, 1))[1],
+            ResultType::NIL => null,
+            ResultType::ARRAY => $this->interpretArray($data)
         };
     }
 }
@@ -0,0 +1,247 @@
+use std::{
+    env,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    net::TcpStream,
+};
+
+use echors::{
+    commands::commands::CommandType,
+    persistence::{
+        codec::Reader,
+        jsonlines::{export_line, import_commands},
+    },
+    state::datastate::DataTypeByte,
+    CommandResult,
+};
+use serde_json::{json, Value};
+
+/**
+ * Exports every key of a running server as JSON lines and loads such files back, talking the
+ * native protocol. The line format is described in persistence::jsonlines.
+ */
+const USAGE: &str = "usage: echors-dump export|import [--addr host:port] [--file path]";
+
+// reply type bytes
+const FLOAT: u8 = DataTypeByte::Float as u8;
+const INTEGER: u8 = DataTypeByte::Integer as u8;
+const STRING: u8 = DataTypeByte::String as u8;
+const LONG: u8 = DataTypeByte::Long as u8;
+const NIL: u8 = DataTypeByte::Nil as u8;
+const ARRAY: u8 = DataTypeByte::Array as u8;
+
+const SCAN_COUNT: u32 = 1000;
+
+enum Reply {
+    Nil,
+    // int, float and long replies, only their presence matters here
+    Number,
+    Bytes(Vec<u8>),
+    // elements keep their type byte
+    Array(Vec<Vec<u8>>),
+}
+
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl Connection {
+    fn open(addr: &str) -> Result<Self, String> {
+        let stream = TcpStream::connect(addr).map_err(|e| format!("{}: {}", addr, e))?;
+        let reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+        Ok(Self {
+            reader,
+            writer: BufWriter::new(stream),
+        })
+    }
+    fn call(&mut self, command: CommandType, args: &[&[u8]]) -> Result<Reply, String> {
+        let mut payload = (command as u16).to_le_bytes().to_vec();
+        for arg in args {
+            payload.extend((arg.len() as u32).to_le_bytes());
+            payload.extend(*arg);
+        }
+        let io_err = |e: io::Error| e.to_string();
+        self.writer
+            .write_all(&(payload.len() as u32).to_le_bytes())
+            .map_err(io_err)?;
+        self.writer.write_all(&payload).map_err(io_err)?;
+        self.writer.flush().map_err(io_err)?;
+        let status = self.u8()?;
+        let reply = self.reply()?;
+        match (status, reply) {
+            (status, reply) if status == CommandResult::OK as u8 => Ok(reply),
+            (_, Reply::Bytes(message)) => Err(String::from_utf8_lossy(&message).into_owned()),
+            _ => Err("Invalid error reply".to_owned()),
+        }
+    }
+    fn reply(&mut self) -> Result<Reply, String> {
+        match self.u8()? {
+            INTEGER | FLOAT => self.array::<4>().map(|_| Reply::Number),
+            STRING => Ok(Reply::Bytes(self.sized()?)),
+            LONG => self.array::<8>().map(|_| Reply::Number),
+            NIL => Ok(Reply::Nil),
+            ARRAY => {
+                let count = u32::from_le_bytes(self.array()?);
+                let items = (0..count).map(|_| self.sized()).collect::<Result<_, _>>()?;
+                Ok(Reply::Array(items))
+            }
+            t => Err(format!("Unsupported reply type {}", t)),
+        }
+    }
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.array::<1>()?[0])
+    }
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut buf = [0; N];
+        self.reader
+            .read_exact(&mut buf)
+            .map_err(|e| e.to_string())?;
+        Ok(buf)
+    }
+    fn sized(&mut self) -> Result<Vec<u8>, String> {
+        let len = u32::from_le_bytes(self.array()?) as usize;
+        let mut buf = vec![0; len];
+        self.reader
+            .read_exact(&mut buf)
+            .map_err(|e| e.to_string())?;
+        Ok(buf)
+    }
+    fn ok(&mut self, command: CommandType, args: &[&[u8]]) -> Result<(), String> {
+        self.call(command, args).map(|_| ())
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut addr = "127.0.0.1:3000".to_owned();
+    let mut path: Option<String> = None;
+    let mut options = args.iter().skip(1);
+    while let Some(option) = options.next() {
+        match (option.as_str(), options.next()) {
+            ("--addr", Some(value)) => addr = value.clone(),
+            ("--file", Some(value)) => path = Some(value.clone()),
+            _ => exit_with(USAGE),
+        }
+    }
+    let result =
+        Connection::open(&addr).and_then(|mut conn| match args.first().map(|a| a.as_str()) {
+            Some("export") => {
+                let out: Box<dyn Write> = match &path {
+                    Some(p) => Box::new(File::create(p).map_err(|e| format!("{}: {}", p, e))?),
+                    None => Box::new(io::stdout().lock()),
+                };
+                export(&mut conn, &mut BufWriter::new(out))
+            }
+            Some("import") => {
+                let input: Box<dyn BufRead> = match &path {
+                    Some(p) => Box::new(BufReader::new(
+                        File::open(p).map_err(|e| format!("{}: {}", p, e))?,
+                    )),
+                    None => Box::new(io::stdin().lock()),
+                };
+                import(&mut conn, input)
+            }
+            _ => Err(USAGE.to_owned()),
+        });
+    match result {
+        Ok(keys) => eprintln!("{} keys", keys),
+        Err(e) => exit_with(&e),
+    }
+}
+
+fn exit_with(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+// databases are visited until SELECT refuses the index
+fn export(conn: &mut Connection, out: &mut impl Write) -> Result<usize, String> {
+    let mut exported = 0;
+    for db in 0u32.. {
+        if conn
+            .call(CommandType::Select, &[&db.to_le_bytes()])
+            .is_err()
+        {
+            break;
+        }
+        let mut cursor = 0u64;
+        loop {
+            let (next, keys) = scan(conn, cursor)?;
+            for key in keys {
+                // keys deleted or expired since the SCAN are skipped
+                let payload = match conn.call(CommandType::Dump, &[key.as_bytes()])? {
+                    Reply::Bytes(payload) => payload,
+                    _ => continue,
+                };
+                let mut line = export_line(&payload)?;
+                line.insert("db".to_owned(), json!(db));
+                line.insert("key".to_owned(), json!(key));
+                serde_json::to_writer(&mut *out, &line).map_err(|e| e.to_string())?;
+                out.write_all(b"\n").map_err(|e| e.to_string())?;
+                exported += 1;
+            }
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+    }
+    out.flush().map_err(|e| e.to_string())?;
+    Ok(exported)
+}
+
+// SCAN replies [cursor, keys] with the keys as a list of strings
+fn scan(conn: &mut Connection, cursor: u64) -> Result<(u64, Vec<String>), String> {
+    let invalid = || "Invalid SCAN reply".to_owned();
+    let items = match conn.call(
+        CommandType::Scan,
+        &[&cursor.to_le_bytes(), b"", &SCAN_COUNT.to_le_bytes()],
+    )? {
+        Reply::Array(items) if items.len() == 2 => items,
+        _ => return Err(invalid()),
+    };
+    let next = match items[0].split_first() {
+        Some((&LONG, next)) => u64::from_le_bytes(next.try_into().map_err(|_| invalid())?),
+        _ => return Err(invalid()),
+    };
+    let mut list = Reader::new(items[1].get(1..).ok_or_else(invalid)?);
+    let mut keys = Vec::new();
+    while list.remaining() > 0 {
+        if list.u8()? != STRING {
+            return Err(invalid());
+        }
+        keys.push(String::from_utf8_lossy(list.sized()?).into_owned());
+    }
+    Ok((next, keys))
+}
+
+// existing keys are replaced, the TTL is applied after the value is written
+fn import(conn: &mut Connection, input: Box<dyn BufRead>) -> Result<usize, String> {
+    let mut db: Option<u64> = None;
+    let mut imported = 0;
+    for (number, line) in input.lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Value =
+            serde_json::from_str(&line).map_err(|e| format!("line {}: {}", number + 1, e))?;
+        let line_db = record["db"].as_u64().unwrap_or(0);
+        if db != Some(line_db) {
+            conn.ok(CommandType::Select, &[&(line_db as u32).to_le_bytes()])?;
+            db = Some(line_db);
+        }
+        import_key(conn, &record).map_err(|e| format!("line {}: {}", number + 1, e))?;
+        imported += 1;
+    }
+    Ok(imported)
+}
+
+fn import_key(conn: &mut Connection, record: &Value) -> Result<(), String> {
+    for (command, args) in import_commands(record)? {
+        let args: Vec<&[u8]> = args.iter().map(|arg| arg.as_slice()).collect();
+        conn.ok(command, &args)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use std::{
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use crate::{
+    commands::commands::CommandType,
+    persistence::{aof::encode, codec::RESTORE_REPLACE},
+    CommandResult,
+};
+
+/**
+ * Sends a DUMP payload to another node with ASKING followed by RESTORE REPLACE, so the key is
+ * accepted while its slot is still being imported there. Blocks the calling command until the
+ * target answered or the timeout elapsed.
+ */
+pub fn restore_on(
+    target: &str,
+    key: &[u8],
+    payload: &[u8],
+    timeout: Duration,
+) -> Result<(), String> {
+    let io_err = |e: std::io::Error| format!("IOERR error or timeout talking to {}: {}", target, e);
+    let addr = target
+        .to_socket_addrs()
+        .map_err(io_err)?
+        .next()
+        .ok_or_else(|| format!("Invalid target address {}", target))?;
+    let mut socket = TcpStream::connect_timeout(&addr, timeout).map_err(io_err)?;
+    socket.set_read_timeout(Some(timeout)).map_err(io_err)?;
+    socket.set_write_timeout(Some(timeout)).map_err(io_err)?;
+    let requests = [
+        encode(CommandType::Asking, &[]),
+        encode(
+            CommandType::Restore,
+            &[key, payload, b"", &[RESTORE_REPLACE]],
+        ),
+    ];
+    let mut out = Vec::new();
+    for request in &requests {
+        out.extend((request.len() as u32).to_le_bytes());
+        out.extend(request);
+    }
+    socket.write_all(&out).map_err(io_err)?;
+    for _ in &requests {
+        // both replies are a string, "OK" or the error message
+        let mut header = [0u8; 6];
+        socket.read_exact(&mut header).map_err(io_err)?;
+        let len = u32::from_le_bytes([header[2], header[3], header[4], header[5]]);
+        let mut message = vec![0; len as usize];
+        socket.read_exact(&mut message).map_err(io_err)?;
+        if header[0] != CommandResult::OK as u8 {
+            return Err(format!(
+                "Target instance replied with error: {}",
+                String::from_utf8_lossy(&message)
+            ));
+        }
+    }
+    Ok(())
+}
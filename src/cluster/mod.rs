@@ -0,0 +1,3 @@
+pub mod crc16;
+pub mod migrate;
+pub mod topology;
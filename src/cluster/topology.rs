@@ -0,0 +1,250 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use parking_lot::RwLock;
+
+use super::crc16::{key_slot, SLOT_COUNT};
+
+pub const CROSSSLOT: &str = "CROSSSLOT Keys in request don't hash to the same slot";
+
+// action of CLUSTER SETSLOT, sent as a byte
+#[derive(Debug, Clone, PartialEq)]
+pub enum SlotAction {
+    Node(String),
+    Migrating(String),
+    Importing(String),
+    Stable,
+}
+
+impl SlotAction {
+    pub fn parse(action: u8, addr: Option<String>) -> Result<Self, String> {
+        match (action, addr) {
+            (0, Some(addr)) => Ok(SlotAction::Node(addr)),
+            (1, Some(addr)) => Ok(SlotAction::Migrating(addr)),
+            (2, Some(addr)) => Ok(SlotAction::Importing(addr)),
+            (3, _) => Ok(SlotAction::Stable),
+            (0..=2, None) => Err("SETSLOT needs the address of a node".to_owned()),
+            _ => Err("Invalid SETSLOT action".to_owned()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SlotTable {
+    owners: Vec<Option<Arc<str>>>,
+    // slots moving away from this node and the node receiving them
+    migrating: HashMap<u16, Arc<str>>,
+    // slots moving to this node and the node they come from
+    importing: HashMap<u16, Arc<str>>,
+}
+
+/**
+ * Which node serves each hash slot, as seen by this node. Nodes do not talk to each other,
+ * the ownership is set up by the configuration and changed with CLUSTER SETSLOT on every node.
+ */
+#[derive(Debug)]
+pub struct Topology {
+    myself: Arc<str>,
+    config_path: PathBuf,
+    slots: RwLock<SlotTable>,
+}
+
+impl Topology {
+    /**
+     * Loads the ownership saved in the nodes file, or the configured ranges when the file does
+     * not exist yet.
+     */
+    pub fn new(
+        myself: String,
+        config_path: PathBuf,
+        ranges: &[(String, u16, u16)],
+    ) -> Result<Self, String> {
+        let ranges = match config_path.exists() {
+            true => read_nodes_file(&config_path)?,
+            false => ranges.to_vec(),
+        };
+        let mut owners: Vec<Option<Arc<str>>> = vec![None; SLOT_COUNT];
+        for (addr, start, end) in ranges {
+            if start > end || end as usize >= SLOT_COUNT {
+                return Err(format!("Invalid slot range {}-{}", start, end));
+            }
+            let addr: Arc<str> = addr.into();
+            owners[start as usize..=end as usize].fill(Some(addr));
+        }
+        Ok(Self {
+            myself: myself.into(),
+            config_path,
+            slots: RwLock::new(SlotTable {
+                owners,
+                migrating: HashMap::new(),
+                importing: HashMap::new(),
+            }),
+        })
+    }
+
+    pub fn myself(&self) -> &str {
+        &self.myself
+    }
+
+    /**
+     * Checks that the keys of a command are served here. The error is the redirect to send
+     * back: MOVED when the slot belongs to another node, ASK when the slot is being migrated
+     * and a key is already gone, CROSSSLOT or CLUSTERDOWN otherwise. Keys of a slot being
+     * imported are only served to clients that sent ASKING first.
+     */
+    pub fn route(
+        &self,
+        keys: &[&[u8]],
+        asking: bool,
+        exists: impl Fn(&[u8]) -> bool,
+    ) -> Result<(), String> {
+        let Some(first) = keys.first() else {
+            return Ok(());
+        };
+        let slot = key_slot(first);
+        if keys.iter().any(|key| key_slot(key) != slot) {
+            return Err(CROSSSLOT.to_owned());
+        }
+        let slots = self.slots.read();
+        match &slots.owners[slot as usize] {
+            Some(owner) if *owner == self.myself => match slots.migrating.get(&slot) {
+                Some(target) if !keys.iter().all(|key| exists(key)) => {
+                    Err(format!("ASK {} {}", slot, target))
+                }
+                _ => Ok(()),
+            },
+            _ if asking && slots.importing.contains_key(&slot) => Ok(()),
+            Some(owner) => Err(format!("MOVED {} {}", slot, owner)),
+            None => Err(format!("CLUSTERDOWN Hash slot {} not served", slot)),
+        }
+    }
+
+    pub fn set_slot(&self, slot: u16, action: SlotAction) -> Result<(), String> {
+        if slot as usize >= SLOT_COUNT {
+            return Err("Invalid slot".to_owned());
+        }
+        let mut slots = self.slots.write();
+        match action {
+            SlotAction::Node(addr) => {
+                slots.owners[slot as usize] = Some(addr.into());
+                slots.migrating.remove(&slot);
+                slots.importing.remove(&slot);
+                let ranges = ranges(&slots.owners);
+                drop(slots);
+                return write_nodes_file(&self.config_path, &ranges);
+            }
+            SlotAction::Migrating(addr) => {
+                if slots.owners[slot as usize].as_ref() != Some(&self.myself) {
+                    return Err(format!("I'm not the owner of hash slot {}", slot));
+                }
+                slots.migrating.insert(slot, addr.into());
+            }
+            SlotAction::Importing(addr) => {
+                if slots.owners[slot as usize].as_ref() == Some(&self.myself) {
+                    return Err(format!("I'm already the owner of hash slot {}", slot));
+                }
+                slots.importing.insert(slot, addr.into());
+            }
+            SlotAction::Stable => {
+                slots.migrating.remove(&slot);
+                slots.importing.remove(&slot);
+            }
+        }
+        Ok(())
+    }
+
+    // contiguous ranges of slots served by the same node, in slot order
+    pub fn slot_ranges(&self) -> Vec<(String, u16, u16)> {
+        ranges(&self.slots.read().owners)
+    }
+
+    /**
+     * One line per node: its address, "myself" for this node, then its slot ranges and for
+     * this node the slots in migration as [slot->-addr] or [slot-<-addr].
+     */
+    pub fn nodes(&self) -> String {
+        let ranges = self.slot_ranges();
+        let slots = self.slots.read();
+        let mut nodes: Vec<&str> = vec![&self.myself];
+        for (addr, _, _) in &ranges {
+            if !nodes.contains(&addr.as_str()) {
+                nodes.push(addr);
+            }
+        }
+        let mut out = String::new();
+        for node in nodes {
+            out.push_str(node);
+            if node == &*self.myself {
+                out.push_str(" myself");
+            }
+            for (_, start, end) in ranges.iter().filter(|(addr, _, _)| addr == node) {
+                match start == end {
+                    true => out.push_str(&format!(" {}", start)),
+                    false => out.push_str(&format!(" {}-{}", start, end)),
+                }
+            }
+            if node == &*self.myself {
+                let mut migrating: Vec<_> = slots.migrating.iter().collect();
+                migrating.sort();
+                for (slot, addr) in migrating {
+                    out.push_str(&format!(" [{}->-{}]", slot, addr));
+                }
+                let mut importing: Vec<_> = slots.importing.iter().collect();
+                importing.sort();
+                for (slot, addr) in importing {
+                    out.push_str(&format!(" [{}-<-{}]", slot, addr));
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+fn ranges(owners: &[Option<Arc<str>>]) -> Vec<(String, u16, u16)> {
+    let mut ranges: Vec<(String, u16, u16)> = Vec::new();
+    for (slot, owner) in owners.iter().enumerate() {
+        let Some(owner) = owner else { continue };
+        match ranges.last_mut() {
+            Some((addr, _, end)) if **addr == **owner && *end as usize + 1 == slot => {
+                *end = slot as u16
+            }
+            _ => ranges.push((owner.to_string(), slot as u16, slot as u16)),
+        }
+    }
+    ranges
+}
+
+// the nodes file has one "first last host:port" line per range
+fn read_nodes_file(path: &Path) -> Result<Vec<(String, u16, u16)>, String> {
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let invalid = || format!("Invalid line in {}: {}", path.display(), line);
+            match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+                [start, end, addr] => Ok((
+                    addr.to_string(),
+                    start.parse().map_err(|_| invalid())?,
+                    end.parse().map_err(|_| invalid())?,
+                )),
+                _ => Err(invalid()),
+            }
+        })
+        .collect()
+}
+
+fn write_nodes_file(path: &Path, ranges: &[(String, u16, u16)]) -> Result<(), String> {
+    let content: String = ranges
+        .iter()
+        .map(|(addr, start, end)| format!("{} {} {}\n", start, end, addr))
+        .collect();
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, content).map_err(|e| e.to_string())?;
+    fs::rename(&tmp_path, path).map_err(|e| e.to_string())
+}
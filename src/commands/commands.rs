@@ -1,24 +1,72 @@
-use std::sync::Arc;
+use std::sync::{atomic::Ordering, Arc};
 
 use num_derive::FromPrimitive;
 use parking_lot::RwLock;
 
-use crate::state::{datastate::DataState, serverstate::ServerState};
+use crate::{
+    cluster::topology::Topology,
+    persistence::aof,
+    pubsub::keyspace::{EventClass, KeyspaceEvents},
+    state::{
+        connectionstate::ConnectionState, datastate::DataState, datastore::DataStore,
+        serverstate::ServerState,
+    },
+};
 
 use super::implcommands::{
+    cluster::{
+        asking::AskingCmd, countkeysinslot::ClusterCountKeysInSlotCmd,
+        getkeysinslot::ClusterGetKeysInSlotCmd, keyslot::ClusterKeySlotCmd, migrate::MigrateCmd,
+        nodes::ClusterNodesCmd, setslot::ClusterSetSlotCmd, slots::ClusterSlotsCmd,
+    },
+    db::{flush::FlushCmd, movekey::MoveCmd, select::SelectCmd, swapdb::SwapDbCmd},
     delete::DeleteCmd,
     get::GetCmd,
-    hll::{hlladd::HLLAddCmd, hllcount::HLLCountCmd, hllreset::HLLResetCmd},
+    getdel::GetDelCmd,
+    getex::GetExCmd,
+    getset::GetSetCmd,
+    hll::{hlladd::HLLAddCmd, hllcount::HLLCountCmd, hllload::HLLLoadCmd, hllreset::HLLResetCmd},
     incrf::IncrF,
     incri::IncrI,
     info::InfoCmd,
+    json::{
+        jsonarrappend::JsonArrAppendCmd, jsonarrlen::JsonArrLenCmd, jsondel::JsonDelCmd,
+        jsonget::JsonGetCmd, jsonnumincrby::JsonNumIncrByCmd, jsonset::JsonSetCmd,
+        jsontype::JsonTypeCmd,
+    },
+    keys::{
+        copy::CopyCmd,
+        dbsize::DbSizeCmd,
+        debugobject::DebugObjectCmd,
+        dump::DumpCmd,
+        exists::ExistsCmd,
+        expire::{ExpireCmd, ExpireKind},
+        keytype::TypeCmd,
+        listkeys::KeysCmd,
+        object::{ObjectCmd, ObjectField},
+        persist::PersistCmd,
+        randomkey::RandomKeyCmd,
+        rename::RenameCmd,
+        restore::RestoreCmd,
+        scan::ScanCmd,
+        ttl::TtlCmd,
+    },
     list::{
         lextract::LExtractCmd, llen::LLenCmd, lpop::LPopCmd, lpush::LPushCmd, lrange::LRangeCmd,
     },
+    memory::{stats::MemoryStatsCmd, usage::MemoryUsageCmd},
+    mget::MGetCmd,
+    mset::MSetCmd,
+    persistence::{bgrewriteaof::BgRewriteAofCmd, lastsave::LastSaveCmd, save::SaveCmd},
+    pubsub::{channels::PubSubCmd, publish::PublishCmd, subscribe::SubscribeCmd},
+    replication::replicaof::ReplicaOfCmd,
+    scripting::{eval::EvalCmd, script::ScriptCmd},
     setf::SetF,
     seti::SetI,
     sets::SetSCmd,
+    string::{append::AppendCmd, getrange::GetRangeCmd, setrange::SetRangeCmd, strlen::StrLenCmd},
     test::TestCmd,
+    transaction::{exec::ExecCmd, multi::MultiCmd, watch::WatchCmd},
 };
 
 #[derive(Debug)]
@@ -29,11 +77,106 @@ pub struct Command<'a> {
 impl Command<'_> {
     pub fn execute<'a>(
         self: &Self,
-        data_state: &Arc<RwLock<DataState>>,
+        store: &Arc<DataStore>,
+        conn: &mut ConnectionState,
         server_state_rwl: &Arc<RwLock<ServerState>>,
     ) -> Result<Option<Vec<u8>>, String> {
-        match self.command_type {
-            CommandType::Info => InfoCmd::execute(server_state_rwl),
+        // transactions and scripts run alone, SCRIPT KILL has to get through while they run
+        let (_shared, _exclusive) = match self.command_type {
+            CommandType::Exec | CommandType::Eval | CommandType::EvalSha => {
+                (None, Some(store.transaction_guard()?))
+            }
+            CommandType::ScriptKill => (None, None),
+            _ => (Some(store.command_guard()?), None),
+        };
+        self.run(store, conn, server_state_rwl)
+    }
+    // executes a command of the replication stream, never fails with BUSY
+    pub fn apply(
+        &self,
+        store: &Arc<DataStore>,
+        conn: &mut ConnectionState,
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let _shared = store.replication_guard();
+        self.run(store, conn, server_state_rwl)
+    }
+    // executes without the transaction lock, used by EXEC and scripts for the commands they run
+    pub fn run(
+        &self,
+        store: &Arc<DataStore>,
+        conn: &mut ConnectionState,
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if conn.subscription.is_some() && !self.command_type.allowed_when_subscribed() {
+            return Err(
+                "Only SUBSCRIBE, UNSUBSCRIBE, PSUBSCRIBE, PUNSUBSCRIBE and TEST are allowed while subscribed"
+                    .to_owned(),
+            );
+        }
+        let data_state = store.db(conn.db);
+        // ASKING only applies to the command right after it
+        let asking = std::mem::take(&mut conn.asking);
+        if !conn.replicated {
+            let cluster = server_state_rwl.read().cluster.clone();
+            if let Some(cluster) = cluster {
+                self.route(&cluster, &data_state.read(), asking)?;
+            }
+        }
+        // writes are logged and sent to replicas in execution order, the log and the backlog
+        // stay locked until the records are appended
+        let (aof, replication, events) = match self.command_type.is_write() {
+            true => {
+                let state = server_state_rwl.read();
+                let read_only = state.config.replica_read_only && !conn.replicated;
+                if read_only && state.replication.is_replica() {
+                    return Err("READONLY You can't write against a read only replica".to_owned());
+                }
+                (
+                    state.aof.clone(),
+                    Some(state.replication.clone()),
+                    Some(state.keyspace_events.clone()).filter(|events| events.active()),
+                )
+            }
+            false => (None, None, None),
+        };
+        let mut aof_guard = aof.as_ref().map(|aof| aof.lock());
+        if let Some(guard) = aof_guard.as_mut() {
+            guard.prepare(conn.db, self);
+        }
+        let mut feed_guard = replication.as_ref().map(|replication| replication.feed());
+        // a replica applies the evictions of its primary instead of its own
+        if self.command_type.may_allocate() && !conn.replicated {
+            let mut evicted = Vec::new();
+            let reserved = store.reserve_memory(&mut evicted);
+            if let Some(guard) = aof_guard.as_mut() {
+                guard.append_evicted(&evicted);
+            }
+            if let Some(guard) = feed_guard.as_mut() {
+                guard.append_evicted(&evicted);
+            }
+            if let Some(events) = &events {
+                for (db, key) in &evicted {
+                    events.notify(EventClass::Evicted, "evicted", *db, key.as_bytes());
+                }
+            }
+            reserved?;
+        }
+        // versions of the keys before the write, the keys it changed get an event
+        let versions: Vec<(&[u8], Option<u64>)> = match events {
+            Some(_) => {
+                let data_state = data_state.read();
+                self.command_type
+                    .key_arguments(&self.arguments)
+                    .into_iter()
+                    .map(|key| (key, key_version(&data_state, key)))
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        let result = match self.command_type {
+            CommandType::Info => InfoCmd::execute(server_state_rwl, store),
+            CommandType::Flush => FlushCmd::execute(store, None, self),
             CommandType::Test => TestCmd::execute(),
             CommandType::SetString => SetSCmd::execute(data_state, self),
             CommandType::SetInt => SetI::execute(data_state, self),
@@ -50,12 +193,213 @@ impl Command<'_> {
             CommandType::HLLAdd => HLLAddCmd::execute(data_state, self),
             CommandType::HLLCount => HLLCountCmd::execute(data_state, self),
             CommandType::HLLReset => HLLResetCmd::execute(data_state, self),
+            CommandType::JsonSet => JsonSetCmd::execute(data_state, self),
+            CommandType::JsonGet => JsonGetCmd::execute(data_state, self),
+            CommandType::JsonDel => JsonDelCmd::execute(data_state, self),
+            CommandType::JsonNumIncrBy => JsonNumIncrByCmd::execute(data_state, self),
+            CommandType::JsonArrAppend => JsonArrAppendCmd::execute(data_state, self),
+            CommandType::JsonArrLen => JsonArrLenCmd::execute(data_state, self),
+            CommandType::JsonType => JsonTypeCmd::execute(data_state, self),
+            CommandType::Append => AppendCmd::execute(data_state, self),
+            CommandType::StrLen => StrLenCmd::execute(data_state, self),
+            CommandType::GetRange => GetRangeCmd::execute(data_state, self),
+            CommandType::SetRange => SetRangeCmd::execute(data_state, self),
+            CommandType::GetSet => GetSetCmd::execute(data_state, self),
+            CommandType::GetDel => GetDelCmd::execute(data_state, self),
+            CommandType::GetEx => GetExCmd::execute(data_state, self),
+            CommandType::MGet => MGetCmd::execute(data_state, self),
+            CommandType::MSet => MSetCmd::execute(data_state, self),
+            CommandType::MSetNX => MSetCmd::execute_nx(data_state, self),
+            CommandType::Exists => ExistsCmd::execute(data_state, self),
+            CommandType::Type => TypeCmd::execute(data_state, self),
+            CommandType::Ttl => TtlCmd::execute(data_state, self, false),
+            CommandType::PTtl => TtlCmd::execute(data_state, self, true),
+            CommandType::Persist => PersistCmd::execute(data_state, self),
+            CommandType::Expire => ExpireCmd::execute(data_state, self, ExpireKind::Seconds),
+            CommandType::PExpire => ExpireCmd::execute(data_state, self, ExpireKind::Millis),
+            CommandType::ExpireAt => ExpireCmd::execute(data_state, self, ExpireKind::AtSeconds),
+            CommandType::PExpireAt => ExpireCmd::execute(data_state, self, ExpireKind::AtMillis),
+            CommandType::ListPushEx => LPushCmd::execute_ex(data_state, self),
+            CommandType::HLLAddEx => HLLAddCmd::execute_ex(data_state, self),
+            CommandType::Scan => ScanCmd::execute(data_state, self),
+            CommandType::Keys => KeysCmd::execute(data_state, server_state_rwl, self),
+            CommandType::DbSize => DbSizeCmd::execute(data_state, self),
+            CommandType::RandomKey => RandomKeyCmd::execute(data_state, self),
+            CommandType::Rename => RenameCmd::execute(data_state, self, false),
+            CommandType::RenameNX => RenameCmd::execute(data_state, self, true),
+            CommandType::Copy => CopyCmd::execute(data_state, self),
+            CommandType::Select => SelectCmd::execute(store, conn, self),
+            CommandType::FlushDb => FlushCmd::execute(store, Some(conn.db), self),
+            CommandType::SwapDb => SwapDbCmd::execute(store, self),
+            CommandType::Move => MoveCmd::execute(store, conn, self),
+            CommandType::MemoryUsage => MemoryUsageCmd::execute(data_state, self),
+            CommandType::MemoryStats => MemoryStatsCmd::execute(store, self),
+            CommandType::ObjectEncoding => {
+                ObjectCmd::execute(data_state, self, ObjectField::Encoding)
+            }
+            CommandType::ObjectIdleTime => {
+                ObjectCmd::execute(data_state, self, ObjectField::IdleTime)
+            }
+            CommandType::ObjectFreq => ObjectCmd::execute(data_state, self, ObjectField::Freq),
+            CommandType::DebugObject => DebugObjectCmd::execute(data_state, self),
+            CommandType::Save => SaveCmd::execute(store, server_state_rwl, self),
+            CommandType::BgSave => SaveCmd::execute_background(store, server_state_rwl, self),
+            CommandType::LastSave => LastSaveCmd::execute(server_state_rwl, self),
+            CommandType::HLLLoad => HLLLoadCmd::execute(data_state, self),
+            CommandType::BgRewriteAof => BgRewriteAofCmd::execute(store, server_state_rwl, self),
+            CommandType::Dump => DumpCmd::execute(data_state, self),
+            CommandType::Restore => RestoreCmd::execute(data_state, self),
+            CommandType::ReplicaOf => ReplicaOfCmd::execute(store, server_state_rwl, self),
+            // valid PSYNC commands are handled by the connection, see psync_position
+            CommandType::Psync => Err("Invalid arguments for PSYNC command".to_owned()),
+            CommandType::ClusterSlots => ClusterSlotsCmd::execute(server_state_rwl, self),
+            CommandType::ClusterNodes => ClusterNodesCmd::execute(server_state_rwl, self),
+            CommandType::ClusterKeySlot => ClusterKeySlotCmd::execute(self),
+            CommandType::ClusterSetSlot => ClusterSetSlotCmd::execute(server_state_rwl, self),
+            CommandType::ClusterGetKeysInSlot => ClusterGetKeysInSlotCmd::execute(data_state, self),
+            CommandType::ClusterCountKeysInSlot => {
+                ClusterCountKeysInSlotCmd::execute(data_state, self)
+            }
+            CommandType::Asking => AskingCmd::execute(conn, self),
+            CommandType::Migrate => MigrateCmd::execute(data_state, self),
+            CommandType::Multi => MultiCmd::execute(conn, self),
+            CommandType::Exec => ExecCmd::execute(store, conn, server_state_rwl, self),
+            CommandType::Discard => MultiCmd::discard(conn, self),
+            CommandType::Watch => WatchCmd::execute(data_state, conn, self),
+            CommandType::Unwatch => WatchCmd::unwatch(conn, self),
+            CommandType::Eval => EvalCmd::execute(store, conn, server_state_rwl, self),
+            CommandType::EvalSha => EvalCmd::execute_sha(store, conn, server_state_rwl, self),
+            CommandType::ScriptLoad => ScriptCmd::load(server_state_rwl, self),
+            CommandType::ScriptKill => ScriptCmd::kill(server_state_rwl, self),
+            CommandType::Publish => PublishCmd::execute(server_state_rwl, self),
+            CommandType::Subscribe => SubscribeCmd::subscribe(server_state_rwl, conn, self, false),
+            CommandType::Unsubscribe => {
+                SubscribeCmd::unsubscribe(server_state_rwl, conn, self, false)
+            }
+            CommandType::PSubscribe => SubscribeCmd::subscribe(server_state_rwl, conn, self, true),
+            CommandType::PUnsubscribe => {
+                SubscribeCmd::unsubscribe(server_state_rwl, conn, self, true)
+            }
+            CommandType::PubSubChannels => PubSubCmd::channels(server_state_rwl, self),
+            CommandType::PubSubNumSub => PubSubCmd::numsub(server_state_rwl, self),
             _ => Err("Unknown command".to_owned()),
+        };
+        if result.is_ok() && self.command_type.is_write() {
+            store.record_change();
+            // relative expiries are propagated as the deadline the key got here
+            let deadline = match aof::has_relative_expiry(self) {
+                true => self.arguments.first().and_then(|key| {
+                    let key = std::str::from_utf8(key).ok()?;
+                    let deadline = data_state
+                        .read()
+                        .peek(key)?
+                        .get_expire()?
+                        .load(Ordering::Relaxed);
+                    Some(deadline)
+                }),
+                false => None,
+            };
+            if let Some(guard) = aof_guard.as_mut() {
+                guard.append(conn.db, self, deadline);
+            }
+            if let Some(guard) = feed_guard.as_mut() {
+                guard.append(conn.db, self, deadline);
+            }
+            if let Some(events) = &events {
+                let data_state = data_state.read();
+                let moved = self.command_type == CommandType::Move
+                    && versions.first().is_some_and(|(key, before)| {
+                        before.is_some() && key_version(&data_state, key).is_none()
+                    });
+                self.notify_changes(events, conn.db, &data_state, versions);
+                // the moved key also gets an event in the destination db
+                if let (true, Ok(dest)) = (moved, store.parse_index(self.arguments[1])) {
+                    events.notify(EventClass::Generic, "move_to", dest, self.arguments[0]);
+                }
+            }
+        }
+        result
+    }
+    // a removed key also gets del unless the event of the command already says so
+    fn notify_changes(
+        &self,
+        events: &KeyspaceEvents,
+        db: usize,
+        data_state: &DataState,
+        versions: Vec<(&[u8], Option<u64>)>,
+    ) {
+        for (index, (key, before)) in versions.into_iter().enumerate() {
+            let after = key_version(data_state, key);
+            if after == before {
+                continue;
+            }
+            let event = self.command_type.keyspace_event(index);
+            let mut removal = false;
+            if let Some((name, class)) = event {
+                removal = matches!(name, "del" | "rename_from" | "move_from");
+                let has_deadline = std::str::from_utf8(key)
+                    .ok()
+                    .and_then(|key| data_state.peek(key))
+                    .map(|wrapper| wrapper.get_expire().is_some());
+                // an expiry in the past only deletes the key, GETEX can also persist it
+                match (name, has_deadline) {
+                    ("expire", None) => {}
+                    ("expire", Some(false)) => events.notify(class, "persist", db, key),
+                    _ => events.notify(class, name, db, key),
+                }
+            }
+            if after.is_none() && !removal {
+                events.notify(EventClass::Generic, "del", db, key);
+            }
         }
     }
+    /**
+     * Cluster mode check done before running a command received from a client: its keys must
+     * hash to one slot served here, otherwise the error redirects the client. Only database 0
+     * exists in cluster mode.
+     */
+    fn route(
+        &self,
+        cluster: &Topology,
+        data_state: &DataState,
+        asking: bool,
+    ) -> Result<(), String> {
+        match self.command_type {
+            CommandType::Select
+                if self
+                    .arguments
+                    .first()
+                    .is_some_and(|db| db.iter().any(|b| *b != 0)) =>
+            {
+                return Err("ERR SELECT is not allowed in cluster mode".to_owned())
+            }
+            CommandType::SwapDb | CommandType::Move => {
+                return Err(format!(
+                    "ERR {:?} is not allowed in cluster mode",
+                    self.command_type
+                ))
+            }
+            _ => {}
+        }
+        let keys = self.command_type.key_arguments(&self.arguments);
+        cluster.route(&keys, asking, |key| {
+            std::str::from_utf8(key).is_ok_and(|key| data_state.peek(key).is_some())
+        })
+    }
+    // wire form of the command without the length prefix, as read by the parser
+    pub fn encode(&self) -> Vec<u8> {
+        let args_len: usize = self.arguments.iter().map(|a| 4 + a.len()).sum();
+        let mut out = Vec::with_capacity(2 + args_len);
+        out.extend((self.command_type as u16).to_le_bytes());
+        for arg in &self.arguments {
+            out.extend((arg.len() as u32).to_le_bytes());
+            out.extend(*arg);
+        }
+        out
+    }
 }
 
-#[derive(FromPrimitive, Debug)]
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq)]
 #[repr(u16)]
 pub enum CommandType {
     Info,
@@ -76,8 +420,292 @@ pub enum CommandType {
     HLLCount,
     HLLReset,
     Flush,
+    JsonSet,
+    JsonGet,
+    JsonDel,
+    JsonNumIncrBy,
+    JsonArrAppend,
+    JsonArrLen,
+    JsonType,
+    Append,
+    StrLen,
+    GetRange,
+    SetRange,
+    GetSet,
+    GetDel,
+    GetEx,
+    MGet,
+    MSet,
+    MSetNX,
+    Exists,
+    Type,
+    Ttl,
+    PTtl,
+    Persist,
+    Expire,
+    PExpire,
+    ExpireAt,
+    PExpireAt,
+    ListPushEx,
+    HLLAddEx,
+    Scan,
+    Keys,
+    DbSize,
+    RandomKey,
+    Rename,
+    RenameNX,
+    Copy,
+    Select,
+    FlushDb,
+    SwapDb,
+    Move,
+    MemoryUsage,
+    MemoryStats,
+    ObjectEncoding,
+    ObjectIdleTime,
+    ObjectFreq,
+    DebugObject,
+    Save,
+    BgSave,
+    LastSave,
+    HLLLoad,
+    BgRewriteAof,
+    Dump,
+    Restore,
+    ReplicaOf,
+    Psync,
+    ClusterSlots,
+    ClusterNodes,
+    ClusterKeySlot,
+    ClusterSetSlot,
+    ClusterGetKeysInSlot,
+    ClusterCountKeysInSlot,
+    Asking,
+    Migrate,
+    Multi,
+    Exec,
+    Discard,
+    Watch,
+    Unwatch,
+    Eval,
+    EvalSha,
+    ScriptLoad,
+    ScriptKill,
+    Publish,
+    Subscribe,
+    Unsubscribe,
+    PSubscribe,
+    PUnsubscribe,
+    PubSubChannels,
+    PubSubNumSub,
     Unknown,
 }
+impl CommandType {
+    // commands that can modify the dataset
+    pub fn is_write(&self) -> bool {
+        self.may_allocate()
+            || matches!(
+                self,
+                CommandType::Delete
+                    | CommandType::ListPop
+                    | CommandType::ListExtract
+                    | CommandType::HLLReset
+                    | CommandType::Flush
+                    | CommandType::FlushDb
+                    | CommandType::JsonDel
+                    | CommandType::GetDel
+                    | CommandType::GetEx
+                    | CommandType::Persist
+                    | CommandType::Expire
+                    | CommandType::PExpire
+                    | CommandType::ExpireAt
+                    | CommandType::PExpireAt
+                    | CommandType::Rename
+                    | CommandType::RenameNX
+                    | CommandType::SwapDb
+                    | CommandType::Move
+                    | CommandType::Migrate
+            )
+    }
+    // command with the given variant name, case insensitive, as used by scripts
+    pub fn from_name(name: &str) -> Option<CommandType> {
+        (0..CommandType::Unknown as u16)
+            .filter_map(num::FromPrimitive::from_u16)
+            .find(|t: &CommandType| format!("{:?}", t).eq_ignore_ascii_case(name))
+    }
+    pub fn allowed_in_scripts(&self) -> bool {
+        !self.controls_transaction()
+            && !matches!(
+                self,
+                CommandType::Unwatch
+                    | CommandType::Eval
+                    | CommandType::EvalSha
+                    | CommandType::ScriptLoad
+                    | CommandType::ScriptKill
+                    | CommandType::Subscribe
+                    | CommandType::Unsubscribe
+                    | CommandType::PSubscribe
+                    | CommandType::PUnsubscribe
+                    | CommandType::Psync
+                    | CommandType::ReplicaOf
+                    | CommandType::Unknown
+            )
+    }
+    // the commands of a connection in push mode
+    pub fn allowed_when_subscribed(&self) -> bool {
+        matches!(
+            self,
+            CommandType::Subscribe
+                | CommandType::Unsubscribe
+                | CommandType::PSubscribe
+                | CommandType::PUnsubscribe
+                | CommandType::Test
+        )
+    }
+    // commands run right away after MULTI instead of being queued
+    pub fn controls_transaction(&self) -> bool {
+        matches!(
+            self,
+            CommandType::Multi | CommandType::Exec | CommandType::Discard | CommandType::Watch
+        )
+    }
+    // arguments holding key names, the ones cluster mode routes by
+    pub fn key_arguments<'a>(&self, args: &[&'a [u8]]) -> Vec<&'a [u8]> {
+        match self {
+            CommandType::Delete | CommandType::MGet | CommandType::Exists | CommandType::Watch => {
+                args.to_vec()
+            }
+            CommandType::MSet | CommandType::MSetNX => args.iter().step_by(2).copied().collect(),
+            CommandType::Rename | CommandType::RenameNX | CommandType::Copy => {
+                args.iter().take(2).copied().collect()
+            }
+            CommandType::Migrate => args.get(2).into_iter().copied().collect(),
+            CommandType::Eval | CommandType::EvalSha => {
+                let count = args
+                    .get(1)
+                    .and_then(|n| <[u8; 4]>::try_from(*n).ok())
+                    .map_or(0, |n| u32::from_le_bytes(n) as usize);
+                args.iter().skip(2).take(count).copied().collect()
+            }
+            CommandType::Info
+            | CommandType::Test
+            | CommandType::Flush
+            | CommandType::Scan
+            | CommandType::Keys
+            | CommandType::DbSize
+            | CommandType::RandomKey
+            | CommandType::Select
+            | CommandType::FlushDb
+            | CommandType::SwapDb
+            | CommandType::MemoryStats
+            | CommandType::Save
+            | CommandType::BgSave
+            | CommandType::LastSave
+            | CommandType::BgRewriteAof
+            | CommandType::ReplicaOf
+            | CommandType::Psync
+            | CommandType::ClusterSlots
+            | CommandType::ClusterNodes
+            | CommandType::ClusterKeySlot
+            | CommandType::ClusterSetSlot
+            | CommandType::ClusterGetKeysInSlot
+            | CommandType::ClusterCountKeysInSlot
+            | CommandType::Asking
+            | CommandType::Multi
+            | CommandType::Exec
+            | CommandType::Discard
+            | CommandType::Unwatch
+            | CommandType::ScriptLoad
+            | CommandType::ScriptKill
+            | CommandType::Publish
+            | CommandType::Subscribe
+            | CommandType::Unsubscribe
+            | CommandType::PSubscribe
+            | CommandType::PUnsubscribe
+            | CommandType::PubSubChannels
+            | CommandType::PubSubNumSub
+            | CommandType::Unknown => Vec::new(),
+            _ => args.first().into_iter().copied().collect(),
+        }
+    }
+    // event published for the key at index in key_arguments when the command changed it
+    pub fn keyspace_event(&self, index: usize) -> Option<(&'static str, EventClass)> {
+        let event = match (self, index) {
+            (CommandType::Delete | CommandType::GetDel, _) => ("del", EventClass::Generic),
+            (
+                CommandType::Expire
+                | CommandType::PExpire
+                | CommandType::ExpireAt
+                | CommandType::PExpireAt
+                | CommandType::GetEx,
+                _,
+            ) => ("expire", EventClass::Generic),
+            (CommandType::Persist, _) => ("persist", EventClass::Generic),
+            (CommandType::Rename | CommandType::RenameNX, 0) => {
+                ("rename_from", EventClass::Generic)
+            }
+            (CommandType::Rename | CommandType::RenameNX, _) => ("rename_to", EventClass::Generic),
+            (CommandType::Copy, _) => ("copy_to", EventClass::Generic),
+            (CommandType::Move, _) => ("move_from", EventClass::Generic),
+            (CommandType::Restore, _) => ("restore", EventClass::Generic),
+            (
+                CommandType::SetString
+                | CommandType::SetInt
+                | CommandType::SetFloat
+                | CommandType::GetSet
+                | CommandType::MSet
+                | CommandType::MSetNX,
+                _,
+            ) => ("set", EventClass::String),
+            (CommandType::IncrementInt, _) => ("incrby", EventClass::String),
+            (CommandType::IncrementFloat, _) => ("incrbyfloat", EventClass::String),
+            (CommandType::Append, _) => ("append", EventClass::String),
+            (CommandType::SetRange, _) => ("setrange", EventClass::String),
+            (CommandType::ListPush | CommandType::ListPushEx, _) => ("lpush", EventClass::List),
+            (CommandType::ListPop, _) => ("lpop", EventClass::List),
+            (CommandType::ListExtract, _) => ("lextract", EventClass::List),
+            (CommandType::HLLAdd | CommandType::HLLAddEx, _) => ("hlladd", EventClass::HyperLogLog),
+            (CommandType::HLLReset, _) => ("hllreset", EventClass::HyperLogLog),
+            (CommandType::HLLLoad, _) => ("hllload", EventClass::HyperLogLog),
+            (CommandType::JsonSet, _) => ("json.set", EventClass::Json),
+            (CommandType::JsonDel, _) => ("json.del", EventClass::Json),
+            (CommandType::JsonNumIncrBy, _) => ("json.numincrby", EventClass::Json),
+            (CommandType::JsonArrAppend, _) => ("json.arrappend", EventClass::Json),
+            _ => return None,
+        };
+        Some(event)
+    }
+    // commands that can grow the dataset, refused when max_memory is reached and nothing can be evicted
+    pub fn may_allocate(&self) -> bool {
+        matches!(
+            self,
+            CommandType::SetString
+                | CommandType::SetInt
+                | CommandType::SetFloat
+                | CommandType::IncrementInt
+                | CommandType::IncrementFloat
+                | CommandType::ListPush
+                | CommandType::ListPushEx
+                | CommandType::HLLAdd
+                | CommandType::HLLAddEx
+                | CommandType::JsonSet
+                | CommandType::JsonNumIncrBy
+                | CommandType::JsonArrAppend
+                | CommandType::Append
+                | CommandType::SetRange
+                | CommandType::GetSet
+                | CommandType::MSet
+                | CommandType::MSetNX
+                | CommandType::Copy
+                | CommandType::HLLLoad
+                | CommandType::Restore
+        )
+    }
+}
+// version of the live key, None for a missing key or one that is not utf8
+fn key_version(data_state: &DataState, key: &[u8]) -> Option<u64> {
+    data_state.version(std::str::from_utf8(key).ok()?)
+}
 impl From<[u8; 2]> for CommandType {
     fn from(value: [u8; 2]) -> Self {
         let num = u16::from_le_bytes([value[0], value[1]]);
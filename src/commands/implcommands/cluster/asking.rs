@@ -0,0 +1,13 @@
+use crate::{commands::commands::Command, state::connectionstate::ConnectionState};
+
+pub struct AskingCmd {}
+impl AskingCmd {
+    // lets the next command of the connection use a slot this node is importing
+    pub fn execute(conn: &mut ConnectionState, cmd: &Command) -> Result<Option<Vec<u8>>, String> {
+        if !cmd.arguments.is_empty() {
+            return Err("Invalid number of arguments for ASKING command".to_owned());
+        }
+        conn.asking = true;
+        Ok(None)
+    }
+}
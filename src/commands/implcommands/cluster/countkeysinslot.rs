@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    cluster::crc16::key_slot,
+    commands::commands::Command,
+    state::datastate::{DataState, DataTypeByte},
+};
+
+use super::parse_slot;
+
+pub struct ClusterCountKeysInSlotCmd {}
+impl ClusterCountKeysInSlotCmd {
+    // number of keys of the u16 slot as a long, walks the whole database
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 1 {
+            return Err(
+                "Invalid number of arguments for CLUSTER COUNTKEYSINSLOT command".to_owned(),
+            );
+        }
+        let slot = parse_slot(cmd.arguments[0])?;
+        let rlock = data_state.read();
+        let mut count: u64 = 0;
+        let mut cursor = 0;
+        loop {
+            let (next, found) = rlock.scan(cursor, 1024, |key, _| key_slot(key.as_bytes()) == slot);
+            count += found.len() as u64;
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        let mut result: Vec<u8> = vec![DataTypeByte::Long as u8];
+        result.extend(count.to_le_bytes());
+        Ok(Some(result))
+    }
+}
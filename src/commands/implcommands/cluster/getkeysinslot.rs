@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    cluster::crc16::key_slot,
+    commands::commands::Command,
+    state::datastate::{srlz_str_list, DataState},
+};
+
+use super::parse_slot;
+
+pub struct ClusterGetKeysInSlotCmd {}
+impl ClusterGetKeysInSlotCmd {
+    // u16 slot and u32 count, at most count keys of the slot, used to drive a migration
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 2 {
+            return Err("Invalid number of arguments for CLUSTER GETKEYSINSLOT command".to_owned());
+        }
+        let slot = parse_slot(cmd.arguments[0])?;
+        let count_b: [u8; 4] = cmd.arguments[1]
+            .try_into()
+            .map_err(|_| "Invalid u32 count".to_owned())?;
+        let count = u32::from_le_bytes(count_b) as usize;
+        let rlock = data_state.read();
+        let mut keys = Vec::new();
+        let mut cursor = 0;
+        while keys.len() < count {
+            let (next, found) =
+                rlock.scan(cursor, count, |key, _| key_slot(key.as_bytes()) == slot);
+            keys.extend(found);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        keys.truncate(count);
+        Ok(Some(srlz_str_list(keys)))
+    }
+}
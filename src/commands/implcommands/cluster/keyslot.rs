@@ -0,0 +1,16 @@
+use crate::{
+    cluster::crc16::key_slot, commands::commands::Command, state::datastate::DataTypeByte,
+};
+
+pub struct ClusterKeySlotCmd {}
+impl ClusterKeySlotCmd {
+    // hash slot of the key as a long, available without cluster mode
+    pub fn execute(cmd: &Command) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 1 {
+            return Err("Invalid number of arguments for CLUSTER KEYSLOT command".to_owned());
+        }
+        let mut result: Vec<u8> = vec![DataTypeByte::Long as u8];
+        result.extend((key_slot(cmd.arguments[0]) as u64).to_le_bytes());
+        Ok(Some(result))
+    }
+}
@@ -0,0 +1,69 @@
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use parking_lot::RwLock;
+
+use crate::{
+    cluster::migrate::restore_on,
+    commands::commands::Command,
+    persistence::codec::encode_dump,
+    state::{
+        datastate::{DataState, StringType},
+        expires::current_millis,
+    },
+};
+
+pub struct MigrateCmd {}
+impl MigrateCmd {
+    const DEFAULT_TIMEOUT_MS: u64 = 1000;
+    /**
+     * host, u16 port, key and optional u64 timeout in milliseconds. The key is restored on the
+     * target with its TTL and deleted here once the target accepted it, NOKEY is returned
+     * when the key does not exist. The database stays locked during the transfer so the key
+     * can not change in between.
+     */
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() < 3 || cmd.arguments.len() > 4 {
+            return Err("Invalid number of arguments for MIGRATE command".to_owned());
+        }
+        let host = std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 host")?;
+        let port_b: [u8; 2] = cmd.arguments[1]
+            .try_into()
+            .map_err(|_| "Invalid u16 port")?;
+        let target = format!("{}:{}", host, u16::from_le_bytes(port_b));
+        let key =
+            std::str::from_utf8(cmd.arguments[2]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let timeout = match cmd.arguments.get(3).filter(|t| !t.is_empty()) {
+            None => Self::DEFAULT_TIMEOUT_MS,
+            Some(t) => {
+                let timeout_b: [u8; 8] = (*t).try_into().map_err(|_| "Invalid u64 timeout")?;
+                u64::from_le_bytes(timeout_b).max(1)
+            }
+        };
+        let wlock = data_state.write();
+        let payload = match wlock.peek(key) {
+            None => return Ok(Some(StringType::new("NOKEY".to_owned()).serialize())),
+            Some(value) => {
+                let ttl = value.get_expire().map(|e| {
+                    e.load(Ordering::Relaxed)
+                        .saturating_sub(current_millis())
+                        .max(1)
+                });
+                encode_dump(value.get_data(), ttl)
+            }
+        };
+        restore_on(
+            &target,
+            key.as_bytes(),
+            &payload,
+            Duration::from_millis(timeout),
+        )?;
+        wlock.remove(key);
+        Ok(None)
+    }
+}
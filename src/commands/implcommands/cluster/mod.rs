@@ -0,0 +1,28 @@
+pub mod asking;
+pub mod countkeysinslot;
+pub mod getkeysinslot;
+pub mod keyslot;
+pub mod migrate;
+pub mod nodes;
+pub mod setslot;
+pub mod slots;
+
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{cluster::topology::Topology, state::serverstate::ServerState};
+
+// topology of the node, cluster commands fail when cluster mode is off
+fn topology(server_state_rwl: &Arc<RwLock<ServerState>>) -> Result<Arc<Topology>, String> {
+    server_state_rwl
+        .read()
+        .cluster
+        .clone()
+        .ok_or_else(|| "ERR This instance has cluster support disabled".to_owned())
+}
+
+fn parse_slot(arg: &[u8]) -> Result<u16, String> {
+    let slot_b: [u8; 2] = arg.try_into().map_err(|_| "Invalid u16 slot".to_owned())?;
+    Ok(u16::from_le_bytes(slot_b))
+}
@@ -0,0 +1,25 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{datastate::StringType, serverstate::ServerState},
+};
+
+use super::topology;
+
+pub struct ClusterNodesCmd {}
+impl ClusterNodesCmd {
+    // one line per node with its slot ranges, see Topology::nodes
+    pub fn execute(
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if !cmd.arguments.is_empty() {
+            return Err("Invalid number of arguments for CLUSTER NODES command".to_owned());
+        }
+        let nodes = topology(server_state_rwl)?.nodes();
+        Ok(Some(StringType::new(nodes).serialize()))
+    }
+}
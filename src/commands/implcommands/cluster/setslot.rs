@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    cluster::topology::SlotAction, commands::commands::Command, state::serverstate::ServerState,
+};
+
+use super::{parse_slot, topology};
+
+pub struct ClusterSetSlotCmd {}
+impl ClusterSetSlotCmd {
+    /**
+     * u16 slot, action byte and "host:port" of a node. Actions are 0 NODE (the node now owns
+     * the slot, saved in the nodes file), 1 MIGRATING and 2 IMPORTING (the node the keys move
+     * to or come from) and 3 STABLE, which takes no address and cancels a migration.
+     */
+    pub fn execute(
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() < 2 || cmd.arguments.len() > 3 {
+            return Err("Invalid number of arguments for CLUSTER SETSLOT command".to_owned());
+        }
+        let slot = parse_slot(cmd.arguments[0])?;
+        let action = match cmd.arguments[1] {
+            [action] => *action,
+            _ => return Err("Invalid SETSLOT action".to_owned()),
+        };
+        let addr = match cmd.arguments.get(2) {
+            None => None,
+            Some(addr) => Some(
+                std::str::from_utf8(addr)
+                    .map_err(|_| "Invalid utf8 address")?
+                    .to_owned(),
+            ),
+        };
+        topology(server_state_rwl)?.set_slot(slot, SlotAction::parse(action, addr)?)?;
+        Ok(None)
+    }
+}
@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{
+        datastate::{srlz_array, DataTypeByte, StringType},
+        serverstate::ServerState,
+    },
+};
+
+use super::topology;
+
+pub struct ClusterSlotsCmd {}
+impl ClusterSlotsCmd {
+    // array of [first slot, last slot, "host:port"] arrays, one per contiguous range
+    pub fn execute(
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if !cmd.arguments.is_empty() {
+            return Err("Invalid number of arguments for CLUSTER SLOTS command".to_owned());
+        }
+        let long = |n: u16| {
+            let mut srlz = vec![DataTypeByte::Long as u8];
+            srlz.extend((n as u64).to_le_bytes());
+            srlz
+        };
+        let ranges = topology(server_state_rwl)?
+            .slot_ranges()
+            .into_iter()
+            .map(|(addr, start, end)| {
+                srlz_array(vec![
+                    long(start),
+                    long(end),
+                    StringType::new(addr).serialize(),
+                ])
+            })
+            .collect();
+        Ok(Some(srlz_array(ranges)))
+    }
+}
@@ -0,0 +1,20 @@
+use crate::{commands::commands::Command, state::datastore::DataStore};
+
+pub struct FlushCmd {}
+impl FlushCmd {
+    // FLUSHALL empties every database, FLUSHDB only the selected one
+    pub fn execute(
+        store: &DataStore,
+        db: Option<usize>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if !cmd.arguments.is_empty() {
+            return Err("Flush commands take no parameters".to_owned());
+        }
+        match db {
+            Some(index) => store.db(index).write().flush(),
+            None => store.flush_all(),
+        }
+        Ok(None)
+    }
+}
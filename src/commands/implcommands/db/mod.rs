@@ -0,0 +1,4 @@
+pub mod flush;
+pub mod movekey;
+pub mod select;
+pub mod swapdb;
@@ -0,0 +1,23 @@
+use crate::{
+    commands::commands::Command,
+    state::{connectionstate::ConnectionState, datastate::IntType, datastore::DataStore},
+};
+
+pub struct MoveCmd {}
+impl MoveCmd {
+    // key db, replies 1 if moved and 0 if the key is missing or already in the destination
+    pub fn execute(
+        store: &DataStore,
+        conn: &ConnectionState,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 2 {
+            return Err("Invalid number of arguments for MOVE command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let dest = store.parse_index(cmd.arguments[1])?;
+        let moved = store.move_key(key, conn.db, dest)?;
+        Ok(Some(IntType::new(moved as i32).serialize().to_vec()))
+    }
+}
@@ -0,0 +1,20 @@
+use crate::{
+    commands::commands::Command,
+    state::{connectionstate::ConnectionState, datastore::DataStore},
+};
+
+pub struct SelectCmd {}
+impl SelectCmd {
+    // binds the connection to a database until the next SELECT
+    pub fn execute(
+        store: &DataStore,
+        conn: &mut ConnectionState,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 1 {
+            return Err("Invalid number of arguments for SELECT command".to_owned());
+        }
+        conn.db = store.parse_index(cmd.arguments[0])?;
+        Ok(None)
+    }
+}
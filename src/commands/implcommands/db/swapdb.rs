@@ -0,0 +1,14 @@
+use crate::{commands::commands::Command, state::datastore::DataStore};
+
+pub struct SwapDbCmd {}
+impl SwapDbCmd {
+    pub fn execute(store: &DataStore, cmd: &Command) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 2 {
+            return Err("Invalid number of arguments for SWAPDB command".to_owned());
+        }
+        let a = store.parse_index(cmd.arguments[0])?;
+        let b = store.parse_index(cmd.arguments[1])?;
+        store.swap(a, b);
+        Ok(None)
+    }
+}
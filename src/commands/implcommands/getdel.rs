@@ -0,0 +1,30 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::datastate::{srlz_nil, DataState},
+};
+
+pub struct GetDelCmd {}
+impl GetDelCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 1 {
+            return Err("Invalid number of arguments for GETDEL command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let rlock = data_state.read();
+        match rlock.take_if(key, |v| v.get_data().is_simple()) {
+            Some(old) => old.get_data().srlz_simple().map(Some),
+            None => match rlock.get(key) {
+                Some(_) => Err("Data type is not simple".to_owned()),
+                None => Ok(Some(srlz_nil())),
+            },
+        }
+    }
+}
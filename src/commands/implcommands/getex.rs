@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{
+        datastate::{srlz_nil, DataState},
+        expires::ExpireParameter,
+    },
+};
+
+pub struct GetExCmd {}
+impl GetExCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.is_empty() || cmd.arguments.len() > 2 {
+            return Err("Invalid number of arguments for GETEX command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let rlock = data_state.read();
+        let opt_key = rlock.get(key);
+        if opt_key.is_none() {
+            return Ok(Some(srlz_nil()));
+        }
+        let result = opt_key.unwrap();
+        let value = result.value().get_data().srlz_simple()?;
+        // without an expire argument the TTL is left untouched, an empty one removes it
+        if cmd.arguments.len() > 1 {
+            let expire = ExpireParameter::from(cmd.arguments[1])?;
+            rlock.update_expire(key, &result, expire.calc_new_expire(Some(result.value())));
+        }
+        Ok(Some(value))
+    }
+}
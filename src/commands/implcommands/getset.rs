@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{
+        datastate::{DataState, DataType, SetFlags, StringType},
+        expires::ExpireParameter,
+    },
+};
+
+pub struct GetSetCmd {}
+impl GetSetCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 2 {
+            return Err("Invalid number of arguments for GETSET command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let value =
+            std::str::from_utf8(cmd.arguments[1]).map_err(|_| "Invalid utf8 value".to_owned())?;
+        let mut flags = SetFlags::new();
+        flags.get = true;
+        let outcome = data_state.read().set_with_flags(
+            key,
+            DataType::String(StringType::new(value.to_owned())),
+            ExpireParameter::None,
+            &flags,
+        )?;
+        Ok(outcome.srlz(&flags))
+    }
+}
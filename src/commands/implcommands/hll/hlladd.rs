@@ -20,9 +20,35 @@ impl HLLAddCmd {
         if cmd.arguments.len() < 2 {
             return Err("Invalid number of arguments for HLLADD command".to_owned());
         }
-        let key =
-            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
-        let values = cmd.arguments.split_at(1).1;
+        Self::add(
+            data_state,
+            cmd.arguments[0],
+            cmd.arguments.split_at(1).1,
+            ExpireParameter::None,
+        )
+    }
+    // HLLADDEX key expire values...
+    pub fn execute_ex(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() < 3 {
+            return Err("Invalid number of arguments for HLLADDEX command".to_owned());
+        }
+        Self::add(
+            data_state,
+            cmd.arguments[0],
+            cmd.arguments.split_at(2).1,
+            ExpireParameter::from(cmd.arguments[1])?,
+        )
+    }
+    fn add(
+        data_state: &Arc<RwLock<DataState>>,
+        key: &[u8],
+        values: &[&[u8]],
+        expire: ExpireParameter,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let key = std::str::from_utf8(key).map_err(|_| "Invalid utf8 key".to_owned())?;
         let rlock = data_state.read();
         let opt_key = rlock.get_mut(key);
         if opt_key.is_none() {
@@ -34,11 +60,7 @@ impl HLLAddCmd {
                         .map_err(|_| format!("Invalid utf8 value at index {}", i).to_owned())?,
                 );
             }
-            let _ = data_state.read().set(
-                key,
-                DataType::HLL(HLLType::new_from_hll(hll)),
-                ExpireParameter::None,
-            );
+            let _ = rlock.set(key, DataType::HLL(HLLType::new_from_hll(hll)), expire);
             return Ok(None);
         }
         let mut result = opt_key.unwrap();
@@ -49,6 +71,9 @@ impl HLLAddCmd {
                     .map_err(|_| format!("Invalid utf8 value at index {}", i))?;
                 mut_storage.add(value);
             }
+            if expire.is_set() {
+                rlock.update_expire(key, &result, expire.calc_new_expire(Some(&result)));
+            }
             return Ok(None);
         }
         return Err("Data at specified key is not a valid HLL".to_owned());
@@ -4,7 +4,7 @@ use parking_lot::RwLock;
 
 use crate::{
     commands::commands::Command,
-    state::datastate::{DataState, DataType},
+    state::datastate::{srlz_nil, DataState, DataType},
 };
 
 pub struct HLLCountCmd {}
@@ -19,9 +19,9 @@ impl HLLCountCmd {
         let key =
             std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
         let rlock = data_state.read();
-        let opt_key = rlock.data.get(key);
+        let opt_key = rlock.get(key);
         if opt_key.is_none() {
-            return Err("Key not found".to_owned());
+            return Ok(Some(srlz_nil()));
         }
         let result = opt_key.unwrap();
         let result_val = result.value().get_data();
@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    data::HLL,
+    state::{
+        datastate::{DataState, DataType, HLLType},
+        expires::ExpireParameter,
+    },
+};
+
+pub struct HLLLoadCmd {}
+impl HLLLoadCmd {
+    /**
+     * key, u8 bucket bits followed by the registers. Replaces the key with the given sketch,
+     * used by the AOF rewrite and to import sketches built elsewhere.
+     */
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 2 {
+            return Err("Invalid number of arguments for HLLLOAD command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let (bits, registers) = cmd.arguments[1]
+            .split_first()
+            .ok_or("Invalid HLL registers")?;
+        let hll = HLL::from_registers(*bits, registers.to_vec())?;
+        let _ = data_state.read().set(
+            key,
+            DataType::HLL(HLLType::new_from_hll(hll)),
+            ExpireParameter::None,
+        );
+        Ok(None)
+    }
+}
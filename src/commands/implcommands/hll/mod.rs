@@ -1,3 +1,4 @@
 pub mod hlladd;
 pub mod hllcount;
+pub mod hllload;
 pub mod hllreset;
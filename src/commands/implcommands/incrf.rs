@@ -6,7 +6,7 @@ use crate::{
     commands::commands::Command,
     state::{
         datastate::{Data, DataState, DataType},
-        expires::ExpireParameter,
+        expires::{ExpireMode, ExpireParameter},
     },
 };
 pub struct IncrF {}
@@ -21,7 +21,11 @@ impl IncrF {
         let key =
             std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
         let mut by: f32 = 1.0;
-        if cmd.arguments.len() > 1 {
+        if cmd.arguments.len() > 4 {
+            return Err("Invalid number of arguments for INCR command".to_owned());
+        }
+        // an empty increment argument keeps the default, allowing an expire to follow
+        if cmd.arguments.len() > 1 && !cmd.arguments[1].is_empty() {
             if cmd.arguments[1].len() < 4 {
                 return Err("Invalid f32 value".to_owned());
             }
@@ -34,6 +38,14 @@ impl IncrF {
             by = f32::from_le_bytes(by_b);
         }
 
+        let mut expire = ExpireParameter::None;
+        let mut mode = ExpireMode::Always;
+        if cmd.arguments.len() > 2 {
+            expire = ExpireParameter::from(cmd.arguments[2])?;
+        }
+        if cmd.arguments.len() > 3 {
+            mode = ExpireMode::from(cmd.arguments[3])?;
+        }
         let response: Vec<u8>;
         {
             let rlock = data_state.read();
@@ -49,10 +61,13 @@ impl IncrF {
 
                     _ => return Err("Invalid type".to_owned()),
                 };
+                if expire.is_set() && mode == ExpireMode::Always {
+                    rlock.update_expire(key, &d, expire.calc_new_expire(Some(&d)));
+                }
             } else {
                 let _data = Data::<f32>::new(by);
                 response = _data.serialize().to_vec();
-                let _ = rlock.set(key, DataType::Float(_data), ExpireParameter::None);
+                let _ = rlock.set(key, DataType::Float(_data), expire);
             }
         }
         Ok(Some(response))
@@ -3,12 +3,47 @@ use std::sync::Arc;
 use parking_lot::RwLock;
 use std::string::String;
 
-use crate::state::serverstate::ServerState;
+use crate::state::{datastore::DataStore, serverstate::ServerState};
 
 pub struct InfoCmd {}
 impl InfoCmd {
-    pub fn execute(server_state_rwl: &Arc<RwLock<ServerState>>) -> Result<Option<Vec<u8>>, String> {
+    pub fn execute(
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        store: &DataStore,
+    ) -> Result<Option<Vec<u8>>, String> {
         let state = server_state_rwl.read();
-        return Ok(Some(state.to_string().as_bytes().to_vec()));
+        let mut info = state.to_string();
+        info.push_str(&format!(
+            "\nchanges_since_last_save: {}\nlast_save: {}\nsave_in_progress: {}",
+            store.changes(),
+            state.snapshots.last_save(),
+            state.snapshots.in_progress()
+        ));
+        info.push_str(&format!("\naof_enabled: {}", state.aof.is_some()));
+        if let Some(aof) = &state.aof {
+            info.push_str(&format!(
+                "\naof_current_size: {}\naof_rewrite_in_progress: {}",
+                aof.current_size(),
+                aof.rewriting()
+            ));
+        }
+        info.push_str(&state.replication.info());
+        info.push_str(&format!("\ncluster_enabled: {}", state.cluster.is_some()));
+        drop(state);
+        info.push_str(&format!(
+            "\nused_memory: {}\nmax_memory: {}\nmax_memory_policy: {:?}\nevicted_keys: {}",
+            store.used_memory(),
+            store.memory_limit().max_memory,
+            store.memory_limit().policy,
+            store.evicted_keys()
+        ));
+        // keyspace section, empty databases are omitted
+        for (index, db) in store.dbs().iter().enumerate() {
+            let keys = db.read().len();
+            if keys > 0 {
+                info.push_str(&format!("\ndb{}: keys={}", index, keys));
+            }
+        }
+        return Ok(Some(info.as_bytes().to_vec()));
     }
 }
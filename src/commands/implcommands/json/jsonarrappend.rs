@@ -0,0 +1,50 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use serde_json::Value;
+
+use crate::{
+    commands::commands::Command,
+    state::datastate::{DataState, DataType, IntType},
+};
+
+use super::parse_path;
+
+pub struct JsonArrAppendCmd {}
+impl JsonArrAppendCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() < 3 {
+            return Err("Invalid number of arguments for JSONARRAPPEND command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let path = parse_path(cmd.arguments.get(1))?;
+        let mut values: Vec<Value> = Vec::new();
+        for (i, raw) in cmd.arguments.split_at(2).1.iter().enumerate() {
+            values.push(
+                serde_json::from_slice(raw)
+                    .map_err(|e| format!("Invalid JSON value at index {}: {}", i, e))?,
+            );
+        }
+        let rlock = data_state.read();
+        let opt_key = rlock.get_mut(key);
+        if opt_key.is_none() {
+            return Err("Key not found".to_owned());
+        }
+        let mut result = opt_key.unwrap();
+        if let DataType::Json(ref mut doc) = result.value_mut().get_data_mut() {
+            return match path.get_mut(doc.get_mut()) {
+                Some(Value::Array(arr)) => {
+                    arr.extend(values);
+                    Ok(Some(IntType::new(arr.len() as i32).serialize().to_vec()))
+                }
+                Some(_) => Err("Value at path is not an array".to_owned()),
+                None => Err("Path not found".to_owned()),
+            };
+        }
+        Err("Data at specified key is not a JSON document".to_owned())
+    }
+}
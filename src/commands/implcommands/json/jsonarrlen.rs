@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use serde_json::Value;
+
+use crate::{
+    commands::commands::Command,
+    state::datastate::{srlz_nil, DataState, DataType, IntType},
+};
+
+use super::parse_path;
+
+pub struct JsonArrLenCmd {}
+impl JsonArrLenCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.is_empty() || cmd.arguments.len() > 2 {
+            return Err("Invalid number of arguments for JSONARRLEN command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let path = parse_path(cmd.arguments.get(1))?;
+        let rlock = data_state.read();
+        let opt_key = rlock.get(key);
+        if opt_key.is_none() {
+            return Ok(Some(srlz_nil()));
+        }
+        let result = opt_key.unwrap();
+        if let DataType::Json(doc) = result.value().get_data() {
+            return match path.get(doc.get()) {
+                Some(Value::Array(arr)) => {
+                    Ok(Some(IntType::new(arr.len() as i32).serialize().to_vec()))
+                }
+                Some(_) => Err("Value at path is not an array".to_owned()),
+                None => Err("Path not found".to_owned()),
+            };
+        }
+        Err("Data at specified key is not a JSON document".to_owned())
+    }
+}
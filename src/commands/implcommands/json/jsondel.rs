@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::datastate::{DataState, DataType, IntType},
+};
+
+use super::parse_path;
+
+pub struct JsonDelCmd {}
+impl JsonDelCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.is_empty() || cmd.arguments.len() > 2 {
+            return Err("Invalid number of arguments for JSONDEL command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let path = parse_path(cmd.arguments.get(1))?;
+        let rlock = data_state.read();
+        let deleted = {
+            let opt_key = rlock.get_mut(key);
+            if opt_key.is_none() {
+                return Ok(Some(IntType::new(0).serialize().to_vec()));
+            }
+            let mut result = opt_key.unwrap();
+            match result.value_mut().get_data_mut() {
+                DataType::Json(_) if path.is_root() => None,
+                DataType::Json(doc) => Some(path.delete(doc.get_mut())),
+                _ => return Err("Data at specified key is not a JSON document".to_owned()),
+            }
+        };
+        // deleting the root removes the whole document, the key lock must be released first
+        let deleted = deleted.unwrap_or_else(|| {
+            rlock.remove(key);
+            true
+        });
+        Ok(Some(IntType::new(deleted as i32).serialize().to_vec()))
+    }
+}
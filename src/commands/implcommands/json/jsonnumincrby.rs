@@ -0,0 +1,53 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use serde_json::{Number, Value};
+
+use crate::{
+    commands::commands::Command,
+    state::datastate::{DataState, DataType, StringType},
+};
+
+use super::parse_path;
+
+pub struct JsonNumIncrByCmd {}
+impl JsonNumIncrByCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 3 {
+            return Err("Invalid number of arguments for JSONNUMINCRBY command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let path = parse_path(cmd.arguments.get(1))?;
+        let by = match serde_json::from_slice::<Value>(cmd.arguments[2]) {
+            Ok(Value::Number(n)) => n,
+            _ => return Err("Increment must be a JSON number".to_owned()),
+        };
+        let rlock = data_state.read();
+        let opt_key = rlock.get_mut(key);
+        if opt_key.is_none() {
+            return Err("Key not found".to_owned());
+        }
+        let mut result = opt_key.unwrap();
+        if let DataType::Json(ref mut doc) = result.value_mut().get_data_mut() {
+            let target = path.get_mut(doc.get_mut()).ok_or("Path not found")?;
+            let current = match target {
+                Value::Number(n) => n,
+                _ => return Err("Value at path is not a number".to_owned()),
+            };
+            let sum = match (current.as_i64(), by.as_i64()) {
+                (Some(a), Some(b)) if a.checked_add(b).is_some() => Number::from(a + b),
+                _ => {
+                    let sum = current.as_f64().unwrap_or(0.0) + by.as_f64().unwrap_or(0.0);
+                    Number::from_f64(sum).ok_or("Increment result is not a valid JSON number")?
+                }
+            };
+            *current = sum;
+            return Ok(Some(StringType::new(current.to_string()).serialize()));
+        }
+        Err("Data at specified key is not a JSON document".to_owned())
+    }
+}
@@ -0,0 +1,50 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use serde_json::Value;
+
+use crate::{
+    commands::commands::Command,
+    state::{
+        datastate::{DataState, DataType, JsonType},
+        expires::ExpireParameter,
+    },
+};
+
+use super::parse_path;
+
+pub struct JsonSetCmd {}
+impl JsonSetCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 3 {
+            return Err("Invalid number of arguments for JSONSET command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let path = parse_path(cmd.arguments.get(1))?;
+        let value: Value = serde_json::from_slice(cmd.arguments[2])
+            .map_err(|e| format!("Invalid JSON value: {}", e))?;
+        let rlock = data_state.read();
+        let opt_key = rlock.get_mut(key);
+        if opt_key.is_none() {
+            if !path.is_root() {
+                return Err("New JSON documents must be created at the root path".to_owned());
+            }
+            let _ = rlock.set(
+                key,
+                DataType::Json(JsonType::new(value)),
+                ExpireParameter::None,
+            );
+            return Ok(None);
+        }
+        let mut result = opt_key.unwrap();
+        if let DataType::Json(ref mut doc) = result.value_mut().get_data_mut() {
+            path.set(doc.get_mut(), value)?;
+            return Ok(None);
+        }
+        Err("Data at specified key is not a JSON document".to_owned())
+    }
+}
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    data::json_type_name,
+    state::datastate::{srlz_nil, DataState, DataType, StringType},
+};
+
+use super::parse_path;
+
+pub struct JsonTypeCmd {}
+impl JsonTypeCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.is_empty() || cmd.arguments.len() > 2 {
+            return Err("Invalid number of arguments for JSONTYPE command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let path = parse_path(cmd.arguments.get(1))?;
+        let rlock = data_state.read();
+        let opt_key = rlock.get(key);
+        if opt_key.is_none() {
+            return Ok(Some(srlz_nil()));
+        }
+        let result = opt_key.unwrap();
+        if let DataType::Json(doc) = result.value().get_data() {
+            let value = path.get(doc.get()).ok_or("Path not found")?;
+            return Ok(Some(
+                StringType::new(json_type_name(value).to_owned()).serialize(),
+            ));
+        }
+        Err("Data at specified key is not a JSON document".to_owned())
+    }
+}
@@ -0,0 +1,20 @@
+use crate::data::JsonPath;
+
+pub mod jsonarrappend;
+pub mod jsonarrlen;
+pub mod jsondel;
+pub mod jsonget;
+pub mod jsonnumincrby;
+pub mod jsonset;
+pub mod jsontype;
+
+// path arguments are optional for read commands and default to the document root
+fn parse_path(arg: Option<&&[u8]>) -> Result<JsonPath, String> {
+    match arg {
+        None => Ok(JsonPath::root()),
+        Some(p) => {
+            let path = std::str::from_utf8(p).map_err(|_| "Invalid utf8 path".to_owned())?;
+            JsonPath::parse(path)
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::datastate::{DataState, IntType},
+};
+
+pub struct CopyCmd {}
+impl CopyCmd {
+    // source destination [replace], a non empty replace argument with a value other than 0 overwrites
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() < 2 || cmd.arguments.len() > 3 {
+            return Err("Invalid number of arguments for COPY command".to_owned());
+        }
+        let from =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let to =
+            std::str::from_utf8(cmd.arguments[1]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let replace = cmd
+            .arguments
+            .get(2)
+            .is_some_and(|r| r.first().is_some_and(|b| *b != 0));
+        let copied = data_state.read().copy(from, to, replace)?;
+        Ok(Some(IntType::new(copied as i32).serialize().to_vec()))
+    }
+}
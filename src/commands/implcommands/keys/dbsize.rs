@@ -0,0 +1,24 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::datastate::{DataState, DataTypeByte},
+};
+
+pub struct DbSizeCmd {}
+impl DbSizeCmd {
+    // keys that expired but were not collected yet are still counted
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if !cmd.arguments.is_empty() {
+            return Err("Command DBSIZE takes no parameters".to_owned());
+        }
+        let mut result: Vec<u8> = vec![DataTypeByte::Long as u8];
+        result.extend((data_state.read().len() as i64).to_le_bytes());
+        Ok(Some(result))
+    }
+}
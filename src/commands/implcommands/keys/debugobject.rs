@@ -0,0 +1,48 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{
+        datastate::{srlz_nil, DataState, StringType},
+        eviction::lru_clock,
+        expires::current_millis,
+    },
+};
+
+pub struct DebugObjectCmd {}
+impl DebugObjectCmd {
+    // single line of space separated name:value fields, ttl_ms is -1 without TTL
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 1 {
+            return Err("Invalid number of arguments for DEBUG OBJECT command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let rlock = data_state.read();
+        let value = match rlock.peek(key) {
+            Some(v) => v,
+            None => return Ok(Some(srlz_nil())),
+        };
+        let data = value.get_data();
+        let ttl: i64 = match value.get_expire() {
+            Some(e) => e.load(Ordering::Relaxed).saturating_sub(current_millis()) as i64,
+            None => -1,
+        };
+        let description = format!(
+            "type:{} encoding:{} serializedlength:{} ttl_ms:{} created_at:{} idle:{} freq:{}",
+            data.type_name(),
+            data.encoding(),
+            data.serialized_len(),
+            ttl,
+            value.created(),
+            value.idle_seconds(lru_clock()),
+            value.frequency(lru_clock())
+        );
+        Ok(Some(StringType::new(description).serialize()))
+    }
+}
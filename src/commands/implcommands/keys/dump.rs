@@ -0,0 +1,40 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    persistence::codec::encode_dump,
+    state::{
+        datastate::{srlz_nil, DataState, StringType},
+        expires::current_millis,
+    },
+};
+
+pub struct DumpCmd {}
+impl DumpCmd {
+    // value and remaining TTL as an opaque blob accepted by RESTORE, nil for a missing key
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 1 {
+            return Err("Invalid number of arguments for DUMP command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let rlock = data_state.read();
+        let value = match rlock.get(key) {
+            Some(v) => v,
+            None => return Ok(Some(srlz_nil())),
+        };
+        // a key about to expire keeps at least 1ms so it is not restored without TTL
+        let ttl = value.get_expire().map(|e| {
+            e.load(Ordering::Relaxed)
+                .saturating_sub(current_millis())
+                .max(1)
+        });
+        let blob = encode_dump(value.get_data(), ttl);
+        Ok(Some(StringType::from_bytes(blob).serialize()))
+    }
+}
@@ -0,0 +1,30 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::datastate::{DataState, IntType},
+};
+
+pub struct ExistsCmd {}
+impl ExistsCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.is_empty() {
+            return Err("Command EXISTS requires at least 1 parameter".to_owned());
+        }
+        let rlock = data_state.read();
+        let mut count: i32 = 0;
+        // repeated keys are counted every time they appear
+        for key_b in cmd.arguments.iter() {
+            let key = std::str::from_utf8(key_b).map_err(|_| "Invalid utf8 key.")?;
+            if rlock.get(key).is_some() {
+                count += 1;
+            }
+        }
+        Ok(Some(IntType::new(count).serialize().to_vec()))
+    }
+}
@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{
+        datastate::{DataState, IntType},
+        expires::{current_millis, ExpireCondition},
+    },
+};
+
+pub enum ExpireKind {
+    Seconds,
+    Millis,
+    AtSeconds,
+    AtMillis,
+}
+impl ExpireKind {
+    fn name(&self) -> &'static str {
+        match self {
+            ExpireKind::Seconds => "EXPIRE",
+            ExpireKind::Millis => "PEXPIRE",
+            ExpireKind::AtSeconds => "EXPIREAT",
+            ExpireKind::AtMillis => "PEXPIREAT",
+        }
+    }
+}
+
+pub struct ExpireCmd {}
+impl ExpireCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+        kind: ExpireKind,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() < 2 || cmd.arguments.len() > 3 {
+            return Err(format!(
+                "Invalid number of arguments for {} command",
+                kind.name()
+            ));
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let time_b: [u8; 8] = cmd.arguments[1]
+            .try_into()
+            .map_err(|_| "Invalid u64 value".to_owned())?;
+        let time = u64::from_le_bytes(time_b);
+        let condition = match cmd.arguments.get(2) {
+            Some(c) => ExpireCondition::from(c)?,
+            None => ExpireCondition::Always,
+        };
+        let deadline = match kind {
+            ExpireKind::Seconds => current_millis().saturating_add(time.saturating_mul(1000)),
+            ExpireKind::Millis => current_millis().saturating_add(time),
+            ExpireKind::AtSeconds => time.saturating_mul(1000),
+            ExpireKind::AtMillis => time,
+        };
+        let changed = data_state.read().expire(key, deadline, condition);
+        Ok(Some(IntType::new(changed as i32).serialize().to_vec()))
+    }
+}
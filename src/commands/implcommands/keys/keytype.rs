@@ -0,0 +1,28 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::datastate::{DataState, StringType},
+};
+
+pub struct TypeCmd {}
+impl TypeCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 1 {
+            return Err("Invalid number of arguments for TYPE command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let rlock = data_state.read();
+        let name = match rlock.get(key) {
+            Some(value) => value.get_data().type_name(),
+            None => "none",
+        };
+        Ok(Some(StringType::new(name.to_owned()).serialize()))
+    }
+}
@@ -0,0 +1,47 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    data::glob_match,
+    state::{
+        datastate::{srlz_str_list, DataState},
+        serverstate::ServerState,
+    },
+};
+
+pub struct KeysCmd {}
+impl KeysCmd {
+    // walks the whole keyspace, SCAN should be preferred on large datasets
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 1 {
+            return Err("Invalid number of arguments for KEYS command".to_owned());
+        }
+        let max_keys = server_state_rwl.read().config.keys_command_max_keys;
+        let pattern = cmd.arguments[0];
+        let rlock = data_state.read();
+        let mut keys: Vec<String> = Vec::new();
+        let mut cursor: u64 = 0;
+        loop {
+            let (next, found) =
+                rlock.scan(cursor, 1000, |key, _| glob_match(pattern, key.as_bytes()));
+            keys.extend(found);
+            if keys.len() > max_keys {
+                return Err(format!(
+                    "KEYS matched more than {} keys, use SCAN instead",
+                    max_keys
+                ));
+            }
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        Ok(Some(srlz_str_list(keys)))
+    }
+}
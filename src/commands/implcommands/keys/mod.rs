@@ -0,0 +1,15 @@
+pub mod copy;
+pub mod dbsize;
+pub mod debugobject;
+pub mod dump;
+pub mod exists;
+pub mod expire;
+pub mod keytype;
+pub mod listkeys;
+pub mod object;
+pub mod persist;
+pub mod randomkey;
+pub mod rename;
+pub mod restore;
+pub mod scan;
+pub mod ttl;
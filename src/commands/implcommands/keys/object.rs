@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{
+        datastate::{srlz_nil, DataState, DataTypeByte, StringType},
+        eviction::lru_clock,
+    },
+};
+
+pub enum ObjectField {
+    Encoding,
+    IdleTime,
+    Freq,
+}
+
+pub struct ObjectCmd {}
+impl ObjectCmd {
+    // reads through peek so that inspecting a key does not count as an access
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+        field: ObjectField,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 1 {
+            return Err("Invalid number of arguments for OBJECT command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let rlock = data_state.read();
+        let value = match rlock.peek(key) {
+            Some(v) => v,
+            None => return Ok(Some(srlz_nil())),
+        };
+        let number: i64 = match field {
+            ObjectField::Encoding => {
+                let encoding = value.get_data().encoding().to_owned();
+                return Ok(Some(StringType::new(encoding).serialize()));
+            }
+            ObjectField::IdleTime => value.idle_seconds(lru_clock()) as i64,
+            ObjectField::Freq => value.frequency(lru_clock()) as i64,
+        };
+        let mut result: Vec<u8> = vec![DataTypeByte::Long as u8];
+        result.extend(number.to_le_bytes());
+        Ok(Some(result))
+    }
+}
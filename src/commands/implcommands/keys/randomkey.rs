@@ -0,0 +1,24 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::datastate::{srlz_nil, DataState, StringType},
+};
+
+pub struct RandomKeyCmd {}
+impl RandomKeyCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if !cmd.arguments.is_empty() {
+            return Err("Command RANDOMKEY takes no parameters".to_owned());
+        }
+        match data_state.read().random_key() {
+            Some(key) => Ok(Some(StringType::new(key).serialize())),
+            None => Ok(Some(srlz_nil())),
+        }
+    }
+}
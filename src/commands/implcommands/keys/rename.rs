@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::datastate::{DataState, IntType},
+};
+
+pub struct RenameCmd {}
+impl RenameCmd {
+    // RENAME replies OK, RENAMENX replies 1 if renamed and 0 if the destination already exists
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+        nx: bool,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 2 {
+            return Err("Invalid number of arguments for RENAME command".to_owned());
+        }
+        let from =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let to =
+            std::str::from_utf8(cmd.arguments[1]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let renamed = data_state.read().rename(from, to, nx)?;
+        match nx {
+            true => Ok(Some(IntType::new(renamed as i32).serialize().to_vec())),
+            false => Ok(None),
+        }
+    }
+}
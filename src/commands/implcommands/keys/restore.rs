@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    persistence::codec::{decode_dump, RESTORE_ABSTTL, RESTORE_REPLACE},
+    state::{
+        datastate::{DataState, SetCondition, SetFlags},
+        expires::{current_millis, ExpireParameter},
+    },
+};
+
+pub struct RestoreCmd {}
+impl RestoreCmd {
+    /**
+     * key, DUMP payload, optional u64 TTL in milliseconds and optional flags byte (REPLACE | ABSTTL).
+     * A missing or empty TTL keeps the one stored in the payload, 0 restores without TTL and
+     * with ABSTTL the value is a unix time in milliseconds.
+     */
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() < 2 || cmd.arguments.len() > 4 {
+            return Err("Invalid number of arguments for RESTORE command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let flags = match cmd.arguments.get(3) {
+            None | Some([]) => 0,
+            Some([f]) if f & !(RESTORE_REPLACE | RESTORE_ABSTTL) == 0 => *f,
+            Some(_) => return Err("Invalid RESTORE flags".to_owned()),
+        };
+        let (data, payload_ttl) = decode_dump(cmd.arguments[1])?;
+        let deadline = match cmd.arguments.get(2) {
+            None | Some([]) => payload_ttl.map(|ttl| current_millis().saturating_add(ttl)),
+            Some(ttl) => {
+                let ttl_b: [u8; 8] = (*ttl)
+                    .try_into()
+                    .map_err(|_| "Invalid u64 value".to_owned())?;
+                match (u64::from_le_bytes(ttl_b), flags & RESTORE_ABSTTL != 0) {
+                    (0, _) => None,
+                    (at, true) => Some(at),
+                    (ttl, false) => Some(current_millis().saturating_add(ttl)),
+                }
+            }
+        };
+        let replace = flags & RESTORE_REPLACE != 0;
+        let rlock = data_state.read();
+        // an already elapsed deadline behaves as an immediate expiry of the restored key
+        if deadline.is_some_and(|d| d <= current_millis()) {
+            if rlock.get(key).is_some() && !replace {
+                return Err("Target key name is busy".to_owned());
+            }
+            rlock.remove(key);
+            return Ok(None);
+        }
+        let expire = match deadline {
+            Some(d) => ExpireParameter::EXPIREAT(d),
+            None => ExpireParameter::None,
+        };
+        let mut set_flags = SetFlags::new();
+        if !replace {
+            set_flags.condition = SetCondition::NotExists;
+        }
+        let outcome = rlock.set_with_flags(key, data, expire, &set_flags)?;
+        if !outcome.applied {
+            return Err("Target key name is busy".to_owned());
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    data::glob_match,
+    state::datastate::{srlz_array, srlz_str_list, DataState, DataTypeByte},
+};
+
+pub struct ScanCmd {}
+impl ScanCmd {
+    const DEFAULT_COUNT: usize = 10;
+    // cursor [pattern] [count] [type], empty optional arguments keep the defaults
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.is_empty() || cmd.arguments.len() > 4 {
+            return Err("Invalid number of arguments for SCAN command".to_owned());
+        }
+        let cursor_b: [u8; 8] = cmd.arguments[0]
+            .try_into()
+            .map_err(|_| "Invalid cursor for SCAN command".to_owned())?;
+        let cursor = u64::from_le_bytes(cursor_b);
+        let pattern = cmd.arguments.get(1).filter(|p| !p.is_empty());
+        let count = match cmd.arguments.get(2).filter(|c| !c.is_empty()) {
+            None => Self::DEFAULT_COUNT,
+            Some(c) => {
+                let count_b: [u8; 4] = (*c)
+                    .try_into()
+                    .map_err(|_| "Invalid count for SCAN command".to_owned())?;
+                u32::from_le_bytes(count_b) as usize
+            }
+        };
+        if count == 0 {
+            return Err("SCAN count must be positive".to_owned());
+        }
+        let type_name = match cmd.arguments.get(3).filter(|t| !t.is_empty()) {
+            None => None,
+            Some(t) => Some(std::str::from_utf8(t).map_err(|_| "Invalid utf8 type name")?),
+        };
+        let rlock = data_state.read();
+        let (next, keys) = rlock.scan(cursor, count, |key, wrapper| {
+            pattern.is_none_or(|p| glob_match(p, key.as_bytes()))
+                && type_name.is_none_or(|t| wrapper.get_data().type_name() == t)
+        });
+        let mut cursor_srlz: Vec<u8> = vec![DataTypeByte::Long as u8];
+        cursor_srlz.extend(next.to_le_bytes());
+        Ok(Some(srlz_array(vec![cursor_srlz, srlz_str_list(keys)])))
+    }
+}
@@ -0,0 +1,45 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{
+        datastate::{DataState, DataTypeByte},
+        expires::current_millis,
+    },
+};
+
+pub struct TtlCmd {}
+impl TtlCmd {
+    // -2 for a missing key, -1 for a key without TTL, otherwise the remaining time
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+        millis: bool,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 1 {
+            return Err("Invalid number of arguments for TTL command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let rlock = data_state.read();
+        let ttl: i64 = match rlock.get(key) {
+            None => -2,
+            Some(value) => match value.get_expire() {
+                None => -1,
+                Some(e) => {
+                    let remaining = e.load(Ordering::Relaxed).saturating_sub(current_millis());
+                    match millis {
+                        true => remaining as i64,
+                        false => ((remaining + 500) / 1000) as i64,
+                    }
+                }
+            },
+        };
+        let mut result: Vec<u8> = Vec::new();
+        result.push(DataTypeByte::Long as u8);
+        result.extend(ttl.to_le_bytes());
+        Ok(Some(result))
+    }
+}
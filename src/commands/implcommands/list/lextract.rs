@@ -4,7 +4,7 @@ use parking_lot::RwLock;
 
 use crate::{
     commands::commands::Command,
-    state::datastate::{DataState, DataType},
+    state::datastate::{srlz_nil, DataState, DataType},
 };
 
 pub struct LExtractCmd {}
@@ -21,7 +21,7 @@ impl LExtractCmd {
         let rlock = data_state.read();
         let opt_list = rlock.get_mut(key);
         if opt_list.is_none() {
-            return Err("Key not found".to_owned());
+            return Ok(Some(srlz_nil()));
         }
         let start_b = cmd.arguments.get(1).unwrap();
         let start_pos = u32::from_le_bytes([start_b[0], start_b[1], start_b[2], start_b[3]]);
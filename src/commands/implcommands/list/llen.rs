@@ -4,7 +4,7 @@ use parking_lot::RwLock;
 
 use crate::{
     commands::commands::Command,
-    state::datastate::{DataState, DataType},
+    state::datastate::{srlz_nil, DataState, DataType},
 };
 
 pub struct LLenCmd {}
@@ -22,7 +22,7 @@ impl LLenCmd {
         let rlock = data_state.read();
         let opt_list = rlock.get(key);
         if opt_list.is_none() {
-            return Err("Key not found".to_owned());
+            return Ok(Some(srlz_nil()));
         }
         let result = opt_list.unwrap();
         let result_val = result.value().get_data();
@@ -4,7 +4,7 @@ use parking_lot::RwLock;
 
 use crate::{
     commands::commands::Command,
-    state::datastate::{DataState, DataType},
+    state::datastate::{srlz_nil, DataState, DataType},
 };
 
 pub struct LPopCmd {}
@@ -21,7 +21,7 @@ impl LPopCmd {
         let rlock = data_state.read();
         let opt_list = rlock.get_mut(key);
         if opt_list.is_none() {
-            return Err("Key not found".to_owned());
+            return Ok(Some(srlz_nil()));
         }
         let mut value_count: u32 = 1;
         if cmd.arguments.len() == 2 {
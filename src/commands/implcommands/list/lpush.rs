@@ -19,9 +19,35 @@ impl LPushCmd {
         if cmd.arguments.len() < 2 {
             return Err("Invalid number of arguments for LPUSH command".to_owned());
         }
-        let key =
-            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
-        let values = cmd.arguments.split_at(1).1;
+        Self::push(
+            data_state,
+            cmd.arguments[0],
+            cmd.arguments.split_at(1).1,
+            ExpireParameter::None,
+        )
+    }
+    // LPUSHEX key expire values...
+    pub fn execute_ex(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() < 3 {
+            return Err("Invalid number of arguments for LPUSHEX command".to_owned());
+        }
+        Self::push(
+            data_state,
+            cmd.arguments[0],
+            cmd.arguments.split_at(2).1,
+            ExpireParameter::from(cmd.arguments[1])?,
+        )
+    }
+    fn push(
+        data_state: &Arc<RwLock<DataState>>,
+        key: &[u8],
+        values: &[&[u8]],
+        expire: ExpireParameter,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let key = std::str::from_utf8(key).map_err(|_| "Invalid utf8 key".to_owned())?;
         let rlock = data_state.read();
         let opt_key = rlock.get_mut(key);
         if opt_key.is_none() {
@@ -33,11 +59,7 @@ impl LPushCmd {
                         .to_owned(),
                 );
             }
-            let _ = data_state.read().set(
-                key,
-                DataType::List(ListType::new(vec_values)),
-                ExpireParameter::None,
-            );
+            let _ = rlock.set(key, DataType::List(ListType::new(vec_values)), expire);
             return Ok(None);
         }
         let mut result = opt_key.unwrap();
@@ -47,6 +69,9 @@ impl LPushCmd {
                     .map_err(|_| format!("Invalid utf8 value at index {}", i).to_owned())?;
                 let _ = l.push(StringType::new(value.to_owned()));
             }
+            if expire.is_set() {
+                rlock.update_expire(key, &result, expire.calc_new_expire(Some(&result)));
+            }
             return Ok(None);
         }
         return Err("Data at specified key is not a list".to_owned());
@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    commands::commands::Command,
+    state::{
+        datastate::{srlz_array, DataTypeByte, FloatType, StringType},
+        datastore::DataStore,
+    },
+};
+
+pub struct MemoryStatsCmd {}
+impl MemoryStatsCmd {
+    /**
+     * Flat array of name, value pairs: accounted totals, per type key count and bytes
+     * and the allocator view of the process when available.
+     */
+    pub fn execute(store: &DataStore, cmd: &Command) -> Result<Option<Vec<u8>>, String> {
+        if !cmd.arguments.is_empty() {
+            return Err("Command MEMORY STATS takes no parameters".to_owned());
+        }
+        let mut by_type: BTreeMap<&'static str, (usize, usize)> = BTreeMap::new();
+        for db in store.dbs() {
+            db.read().memory_by_type(&mut by_type);
+        }
+        let mut items: Vec<Vec<u8>> = Vec::new();
+        let mut push = |name: String, value: Vec<u8>| {
+            items.push(StringType::new(name).serialize());
+            items.push(value);
+        };
+        let total: usize = by_type.values().map(|(_, bytes)| bytes).sum();
+        push("dataset.bytes".to_owned(), srlz_long(total));
+        push(
+            "max_memory".to_owned(),
+            srlz_long(store.memory_limit().max_memory),
+        );
+        for (type_name, (keys, bytes)) in by_type {
+            push(format!("{}.keys", type_name), srlz_long(keys));
+            push(format!("{}.bytes", type_name), srlz_long(bytes));
+        }
+        if let Some((allocated, resident)) = allocator_stats() {
+            push("allocator.allocated".to_owned(), srlz_long(allocated));
+            push("allocator.resident".to_owned(), srlz_long(resident));
+            let fragmentation = resident as f32 / allocated.max(1) as f32;
+            push(
+                "allocator.fragmentation".to_owned(),
+                FloatType::new(fragmentation).serialize().to_vec(),
+            );
+        }
+        Ok(Some(srlz_array(items)))
+    }
+}
+
+fn srlz_long(value: usize) -> Vec<u8> {
+    let mut result: Vec<u8> = vec![DataTypeByte::Long as u8];
+    result.extend((value as i64).to_le_bytes());
+    result
+}
+
+// jemalloc caches its statistics, advancing the epoch refreshes them
+#[cfg(not(target_env = "msvc"))]
+fn allocator_stats() -> Option<(usize, usize)> {
+    use tikv_jemalloc_ctl::{epoch, stats};
+    epoch::advance().ok()?;
+    Some((
+        stats::allocated::read().ok()?,
+        stats::resident::read().ok()?,
+    ))
+}
+#[cfg(target_env = "msvc")]
+fn allocator_stats() -> Option<(usize, usize)> {
+    None
+}
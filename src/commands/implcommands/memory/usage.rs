@@ -0,0 +1,32 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::datastate::{srlz_nil, DataState, DataTypeByte, DataWrapper},
+};
+
+pub struct MemoryUsageCmd {}
+impl MemoryUsageCmd {
+    // estimated bytes of key, wrapper and value, nil for a missing key, does not count as access
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 1 {
+            return Err("Invalid number of arguments for MEMORY USAGE command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let rlock = data_state.read();
+        let value = match rlock.peek(key) {
+            Some(v) => v,
+            None => return Ok(Some(srlz_nil())),
+        };
+        let usage = DataWrapper::entry_size(key, value.get_data());
+        let mut result: Vec<u8> = vec![DataTypeByte::Long as u8];
+        result.extend((usage as i64).to_le_bytes());
+        Ok(Some(result))
+    }
+}
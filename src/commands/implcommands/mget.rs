@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::datastate::{srlz_array, srlz_nil, DataState},
+};
+
+pub struct MGetCmd {}
+impl MGetCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.is_empty() {
+            return Err("Command MGET requires at least 1 parameter".to_owned());
+        }
+        let mut keys: Vec<&str> = Vec::new();
+        for key_b in cmd.arguments.iter() {
+            keys.push(std::str::from_utf8(key_b).map_err(|_| "Invalid utf8 key.")?);
+        }
+        let rlock = data_state.read();
+        let values = keys
+            .iter()
+            .map(|key| match rlock.get(key) {
+                // keys holding non simple types are reported as missing
+                Some(v) => v
+                    .value()
+                    .get_data()
+                    .srlz_simple()
+                    .unwrap_or_else(|_| srlz_nil()),
+                None => srlz_nil(),
+            })
+            .collect();
+        Ok(Some(srlz_array(values)))
+    }
+}
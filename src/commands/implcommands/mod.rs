@@ -1,11 +1,27 @@
+pub mod cluster;
+pub mod db;
 pub mod delete;
 pub mod get;
+pub mod getdel;
+pub mod getex;
+pub mod getset;
 pub mod hll;
 pub mod incrf;
 pub mod incri;
 pub mod info;
+pub mod json;
+pub mod keys;
 pub mod list;
+pub mod memory;
+pub mod mget;
+pub mod mset;
+pub mod persistence;
+pub mod pubsub;
+pub mod replication;
+pub mod scripting;
 pub mod setf;
 pub mod seti;
 pub mod sets;
+pub mod string;
 pub mod test;
+pub mod transaction;
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{
+        datastate::{DataState, DataType, IntType},
+        expires::ExpireParameter,
+    },
+};
+
+pub struct MSetCmd {}
+impl MSetCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let pairs = Self::parse_pairs(cmd, "MSET")?;
+        let rlock = data_state.read();
+        for (key, value) in pairs {
+            let _ = rlock.set(key, value, ExpireParameter::None);
+        }
+        Ok(None)
+    }
+    // all or nothing, only the shards of the keys are locked between the check and the writes
+    pub fn execute_nx(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let pairs = Self::parse_pairs(cmd, "MSETNX")?;
+        let written = data_state.read().set_if_none_exist(pairs);
+        Ok(Some(IntType::new(written as i32).serialize().to_vec()))
+    }
+    fn parse_pairs<'a>(cmd: &Command<'a>, name: &str) -> Result<Vec<(&'a str, DataType)>, String> {
+        if cmd.arguments.is_empty() || cmd.arguments.len() % 2 == 1 {
+            return Err(format!("Invalid number of arguments for {} command", name));
+        }
+        let mut pairs: Vec<(&str, DataType)> = Vec::new();
+        for pair in cmd.arguments.chunks(2) {
+            let key = std::str::from_utf8(pair[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+            let value = DataType::deserialize_simple(pair[1])
+                .map_err(|e| format!("{} for key {}", e, key))?;
+            pairs.push((key, value));
+        }
+        Ok(pairs)
+    }
+}
@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{datastate::StringType, datastore::DataStore, serverstate::ServerState},
+};
+
+pub struct BgRewriteAofCmd {}
+impl BgRewriteAofCmd {
+    // replies once the dataset is copied, the new log replaces the old one in the background
+    pub fn execute(
+        store: &Arc<DataStore>,
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if !cmd.arguments.is_empty() {
+            return Err("Command BGREWRITEAOF takes no parameters".to_owned());
+        }
+        let aof = server_state_rwl
+            .read()
+            .aof
+            .clone()
+            .ok_or("AOF is not enabled")?;
+        aof.rewrite_in_background(store)?;
+        Ok(Some(
+            StringType::new("Background append only file rewriting started".to_owned()).serialize(),
+        ))
+    }
+}
@@ -0,0 +1,25 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{datastate::DataTypeByte, serverstate::ServerState},
+};
+
+pub struct LastSaveCmd {}
+impl LastSaveCmd {
+    // unix seconds of the last successful save
+    pub fn execute(
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if !cmd.arguments.is_empty() {
+            return Err("Command LASTSAVE takes no parameters".to_owned());
+        }
+        let last_save = server_state_rwl.read().snapshots.last_save();
+        let mut result: Vec<u8> = vec![DataTypeByte::Long as u8];
+        result.extend((last_save as i64).to_le_bytes());
+        Ok(Some(result))
+    }
+}
@@ -0,0 +1,3 @@
+pub mod bgrewriteaof;
+pub mod lastsave;
+pub mod save;
@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{datastate::StringType, datastore::DataStore, serverstate::ServerState},
+};
+
+pub struct SaveCmd {}
+impl SaveCmd {
+    // blocks the calling connection until the snapshot is on disk
+    pub fn execute(
+        store: &Arc<DataStore>,
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if !cmd.arguments.is_empty() {
+            return Err("Command SAVE takes no parameters".to_owned());
+        }
+        let snapshots = server_state_rwl.read().snapshots.clone();
+        snapshots.save(store)?;
+        Ok(None)
+    }
+    // replies as soon as the save started, LASTSAVE tells when it completed
+    pub fn execute_background(
+        store: &Arc<DataStore>,
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if !cmd.arguments.is_empty() {
+            return Err("Command BGSAVE takes no parameters".to_owned());
+        }
+        let snapshots = server_state_rwl.read().snapshots.clone();
+        snapshots.save_in_background(store)?;
+        Ok(Some(
+            StringType::new("Background saving started".to_owned()).serialize(),
+        ))
+    }
+}
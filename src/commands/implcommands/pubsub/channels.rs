@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{
+        datastate::{srlz_array, DataTypeByte, StringType},
+        serverstate::ServerState,
+    },
+};
+
+pub struct PubSubCmd {}
+impl PubSubCmd {
+    // channels with subscribers as a string list, those matching the optional glob pattern
+    pub fn channels(
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let pattern = match cmd.arguments.as_slice() {
+            [] => None,
+            [pattern] => Some(*pattern),
+            _ => return Err("Invalid number of arguments for PUBSUB CHANNELS command".to_owned()),
+        };
+        let pubsub = server_state_rwl.read().pubsub.clone();
+        let mut result: Vec<u8> = vec![DataTypeByte::StrList as u8];
+        for channel in pubsub.active_channels(pattern) {
+            result.extend(StringType::from_bytes(channel).serialize());
+        }
+        Ok(Some(result))
+    }
+    // number of subscribers of every channel, as an array of longs in the order of the arguments
+    pub fn numsub(
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let pubsub = server_state_rwl.read().pubsub.clone();
+        let counts = cmd
+            .arguments
+            .iter()
+            .map(|channel| {
+                let mut count: Vec<u8> = vec![DataTypeByte::Long as u8];
+                count.extend((pubsub.subscriber_count(channel) as u64).to_le_bytes());
+                count
+            })
+            .collect();
+        Ok(Some(srlz_array(counts)))
+    }
+}
@@ -0,0 +1,3 @@
+pub mod channels;
+pub mod publish;
+pub mod subscribe;
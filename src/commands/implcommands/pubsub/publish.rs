@@ -0,0 +1,26 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{datastate::DataTypeByte, serverstate::ServerState},
+};
+
+pub struct PublishCmd {}
+impl PublishCmd {
+    // channel and message, replies the number of subscriptions it was pushed to as a long
+    pub fn execute(
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let [channel, message] = cmd.arguments.as_slice() else {
+            return Err("Invalid number of arguments for PUBLISH command".to_owned());
+        };
+        let pubsub = server_state_rwl.read().pubsub.clone();
+        let delivered = pubsub.publish(channel, message);
+        let mut result: Vec<u8> = vec![DataTypeByte::Long as u8];
+        result.extend(delivered.to_le_bytes());
+        Ok(Some(result))
+    }
+}
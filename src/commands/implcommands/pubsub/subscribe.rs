@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{connectionstate::ConnectionState, datastate::DataTypeByte, serverstate::ServerState},
+};
+
+pub struct SubscribeCmd {}
+impl SubscribeCmd {
+    /**
+     * Channels, or glob patterns for PSUBSCRIBE, to receive the messages of. The connection is
+     * in push mode until it unsubscribes from everything. Replies the number of channels and
+     * patterns the connection is subscribed to as a long.
+     */
+    pub fn subscribe(
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        conn: &mut ConnectionState,
+        cmd: &Command,
+        pattern: bool,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.is_empty() {
+            return Err("Invalid number of arguments for SUBSCRIBE command".to_owned());
+        }
+        let pubsub = server_state_rwl.read().pubsub.clone();
+        let subscription = conn
+            .subscription
+            .get_or_insert_with(|| pubsub.subscription());
+        for name in &cmd.arguments {
+            pubsub.subscribe(subscription, name, pattern);
+        }
+        Ok(Some(srlz_count(subscription.count())))
+    }
+    // channels or patterns to leave, all of them without arguments
+    pub fn unsubscribe(
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        conn: &mut ConnectionState,
+        cmd: &Command,
+        pattern: bool,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let Some(subscription) = conn.subscription.as_mut() else {
+            return Ok(Some(srlz_count(0)));
+        };
+        let pubsub = server_state_rwl.read().pubsub.clone();
+        let names: Vec<Vec<u8>> = match (cmd.arguments.is_empty(), pattern) {
+            (true, true) => subscription.patterns.iter().cloned().collect(),
+            (true, false) => subscription.channels.iter().cloned().collect(),
+            (false, _) => cmd.arguments.iter().map(|name| name.to_vec()).collect(),
+        };
+        for name in names {
+            pubsub.unsubscribe(subscription, &name, pattern);
+        }
+        let count = subscription.count();
+        if count == 0 {
+            conn.subscription = None;
+        }
+        Ok(Some(srlz_count(count)))
+    }
+}
+
+fn srlz_count(count: usize) -> Vec<u8> {
+    let mut result: Vec<u8> = vec![DataTypeByte::Long as u8];
+    result.extend((count as u64).to_le_bytes());
+    result
+}
@@ -0,0 +1,34 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{datastore::DataStore, serverstate::ServerState},
+};
+
+pub struct ReplicaOfCmd {}
+impl ReplicaOfCmd {
+    /**
+     * host and u16 port of the primary to replicate from, the current dataset is replaced by
+     * the one of the primary. Without arguments the server stops replicating and accepts writes.
+     */
+    pub fn execute(
+        store: &Arc<DataStore>,
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let primary = match cmd.arguments.as_slice() {
+            [] => None,
+            [host, port] => {
+                let host = std::str::from_utf8(host).map_err(|_| "Invalid utf8 host")?;
+                let port_b: [u8; 2] = (*port).try_into().map_err(|_| "Invalid u16 port")?;
+                Some(format!("{}:{}", host, u16::from_le_bytes(port_b)))
+            }
+            _ => return Err("Invalid number of arguments for REPLICAOF command".to_owned()),
+        };
+        let replication = server_state_rwl.read().replication.clone();
+        replication.set_primary(primary, store, server_state_rwl);
+        Ok(None)
+    }
+}
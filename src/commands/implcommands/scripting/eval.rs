@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    scripting::lua::run_script,
+    state::{connectionstate::ConnectionState, datastore::DataStore, serverstate::ServerState},
+};
+
+pub struct EvalCmd {}
+impl EvalCmd {
+    /**
+     * script, u32 number of keys, the keys then the other arguments. The script is cached for
+     * EVALSHA and runs while no other command runs, see run_script.
+     */
+    pub fn execute(
+        store: &Arc<DataStore>,
+        conn: &ConnectionState,
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let scripts = server_state_rwl.read().scripts.clone();
+        let source = match cmd.arguments.first() {
+            Some(source) => std::str::from_utf8(source).map_err(|_| "Invalid utf8 script")?,
+            None => return Err("Invalid number of arguments for EVAL command".to_owned()),
+        };
+        scripts.load(source);
+        Self::run(source, store, conn, server_state_rwl, cmd)
+    }
+    // same as EVAL with the SHA1 of a cached script as first argument
+    pub fn execute_sha(
+        store: &Arc<DataStore>,
+        conn: &ConnectionState,
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let scripts = server_state_rwl.read().scripts.clone();
+        let sha = match cmd.arguments.first() {
+            Some(sha) => std::str::from_utf8(sha).map_err(|_| "Invalid utf8 SHA1")?,
+            None => return Err("Invalid number of arguments for EVALSHA command".to_owned()),
+        };
+        let source = scripts
+            .get(sha)
+            .ok_or("NOSCRIPT No matching script, use EVAL")?;
+        Self::run(&source, store, conn, server_state_rwl, cmd)
+    }
+    fn run(
+        source: &str,
+        store: &Arc<DataStore>,
+        conn: &ConnectionState,
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let key_count_b: [u8; 4] = cmd
+            .arguments
+            .get(1)
+            .and_then(|n| (*n).try_into().ok())
+            .ok_or("Invalid u32 number of keys")?;
+        let key_count = u32::from_le_bytes(key_count_b) as usize;
+        if key_count > cmd.arguments.len() - 2 {
+            return Err("Number of keys can't be greater than number of args".to_owned());
+        }
+        let (keys, args) = cmd.arguments[2..].split_at(key_count);
+        let scripts = server_state_rwl.read().scripts.clone();
+        let _running = scripts.begin(store);
+        // other connections keep being served on the remaining workers, and get BUSY past the limit
+        tokio::task::block_in_place(|| {
+            run_script(source, keys, args, store, conn, server_state_rwl, &scripts)
+        })
+    }
+}
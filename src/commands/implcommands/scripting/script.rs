@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{datastate::StringType, serverstate::ServerState},
+};
+
+pub struct ScriptCmd {}
+impl ScriptCmd {
+    // caches the script without running it, replies its SHA1 for EVALSHA
+    pub fn load(
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 1 {
+            return Err("Invalid number of arguments for SCRIPT LOAD command".to_owned());
+        }
+        let source = std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 script")?;
+        let sha = server_state_rwl.read().scripts.load(source);
+        Ok(Some(StringType::new(sha).serialize()))
+    }
+    // stops the running script if it did not write yet
+    pub fn kill(
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if !cmd.arguments.is_empty() {
+            return Err("Invalid number of arguments for SCRIPT KILL command".to_owned());
+        }
+        server_state_rwl.read().scripts.request_kill()?;
+        Ok(None)
+    }
+}
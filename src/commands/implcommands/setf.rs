@@ -5,7 +5,7 @@ use parking_lot::RwLock;
 use crate::{
     commands::commands::Command,
     state::{
-        datastate::{Data, DataState, DataType},
+        datastate::{Data, DataState, DataType, SetFlags},
         expires::ExpireParameter,
     },
 };
@@ -15,9 +15,17 @@ impl SetF {
         data_state: &Arc<RwLock<DataState>>,
         cmd: &Command,
     ) -> Result<Option<Vec<u8>>, String> {
-        if cmd.arguments.len() != 2 {
+        if cmd.arguments.len() < 2 || cmd.arguments.len() > 4 {
             return Err("Invalid number of arguments for SETF command".to_owned());
         }
+        let mut expire: ExpireParameter = ExpireParameter::None;
+        let mut flags = SetFlags::new();
+        if cmd.arguments.len() > 2 {
+            expire = ExpireParameter::from(cmd.arguments[2])?;
+        }
+        if cmd.arguments.len() > 3 {
+            flags = SetFlags::from(cmd.arguments[3])?;
+        }
         let key =
             std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
         if cmd.arguments[1].len() < 4 {
@@ -32,12 +40,13 @@ impl SetF {
         let value: f32 = f32::from_le_bytes(numb);
         {
             let rlock = data_state.read();
-            let _ = rlock.set(
+            let outcome = rlock.set_with_flags(
                 key,
                 DataType::Float(Data::<f32>::new(value)),
-                ExpireParameter::None,
-            );
-            Ok(None)
+                expire,
+                &flags,
+            )?;
+            Ok(outcome.srlz(&flags))
         }
     }
 }
@@ -5,7 +5,7 @@ use parking_lot::RwLock;
 use crate::{
     commands::commands::Command,
     state::{
-        datastate::{DataState, DataType, StringType},
+        datastate::{DataState, DataType, SetFlags, StringType},
         expires::ExpireParameter,
     },
 };
@@ -20,12 +20,16 @@ impl SetSCmd {
             return Err("Invalid number of arguments for SETS command".to_owned());
         }
         let mut expire: ExpireParameter = ExpireParameter::None;
+        let mut flags = SetFlags::new();
 
         if cmd.arguments.len() > 2 {
-            if cmd.arguments.len() > 3 {
+            if cmd.arguments.len() > 4 {
                 return Err("Invalid number of arguments for SETS command".to_owned());
             }
-            expire = ExpireParameter::from(cmd.arguments[2]);
+            expire = ExpireParameter::from(cmd.arguments[2])?;
+        }
+        if cmd.arguments.len() > 3 {
+            flags = SetFlags::from(cmd.arguments[3])?;
         }
 
         let key =
@@ -34,12 +38,13 @@ impl SetSCmd {
             std::str::from_utf8(cmd.arguments[1]).map_err(|_| "Invalid utf8 value".to_owned())?;
         {
             let rlock = data_state.read();
-            let _ = rlock.set(
+            let outcome = rlock.set_with_flags(
                 key,
                 DataType::String(StringType::new(value.to_owned())),
                 expire,
-            );
-            Ok(None)
+                &flags,
+            )?;
+            Ok(outcome.srlz(&flags))
         }
     }
 }
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{
+        datastate::{DataState, DataType, IntType, StringType},
+        expires::ExpireParameter,
+    },
+};
+
+pub struct AppendCmd {}
+impl AppendCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 2 {
+            return Err("Invalid number of arguments for APPEND command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let rlock = data_state.read();
+        let opt_key = rlock.get_mut(key);
+        if opt_key.is_none() {
+            let value = StringType::from_bytes(cmd.arguments[1].to_vec());
+            let len = value.len();
+            let _ = rlock.set(key, DataType::String(value), ExpireParameter::None);
+            return Ok(Some(IntType::new(len as i32).serialize().to_vec()));
+        }
+        let mut result = opt_key.unwrap();
+        if let DataType::String(ref mut s) = result.value_mut().get_data_mut() {
+            let len = s.append(cmd.arguments[1])?;
+            return Ok(Some(IntType::new(len as i32).serialize().to_vec()));
+        }
+        Err("Data at specified key is not a string".to_owned())
+    }
+}
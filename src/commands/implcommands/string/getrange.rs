@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::datastate::{DataState, DataType, StringType},
+};
+
+pub struct GetRangeCmd {}
+impl GetRangeCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 3 {
+            return Err("Invalid number of arguments for GETRANGE command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        if cmd.arguments[1].len() < 4 || cmd.arguments[2].len() < 4 {
+            return Err("Invalid i32 value".to_owned());
+        }
+        let start_b = cmd.arguments[1];
+        let start = i32::from_le_bytes([start_b[0], start_b[1], start_b[2], start_b[3]]);
+        let end_b = cmd.arguments[2];
+        let end = i32::from_le_bytes([end_b[0], end_b[1], end_b[2], end_b[3]]);
+        let rlock = data_state.read();
+        let opt_key = rlock.get(key);
+        if opt_key.is_none() {
+            return Ok(Some(StringType::from_bytes(Vec::new()).serialize()));
+        }
+        let result = opt_key.unwrap();
+        if let DataType::String(s) = result.value().get_data() {
+            return Ok(Some(s.srlz_substr(start as i64, end as i64)));
+        }
+        Err("Data at specified key is not a string".to_owned())
+    }
+}
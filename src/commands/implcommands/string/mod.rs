@@ -0,0 +1,4 @@
+pub mod append;
+pub mod getrange;
+pub mod setrange;
+pub mod strlen;
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{
+        datastate::{DataState, DataType, IntType, StringType},
+        expires::ExpireParameter,
+    },
+};
+
+pub struct SetRangeCmd {}
+impl SetRangeCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 3 {
+            return Err("Invalid number of arguments for SETRANGE command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        if cmd.arguments[1].len() < 4 {
+            return Err("Invalid u32 value".to_owned());
+        }
+        let offset_b = cmd.arguments[1];
+        let offset =
+            u32::from_le_bytes([offset_b[0], offset_b[1], offset_b[2], offset_b[3]]) as usize;
+        let value = cmd.arguments[2];
+        let rlock = data_state.read();
+        let opt_key = rlock.get_mut(key);
+        if opt_key.is_none() {
+            if value.is_empty() {
+                return Ok(Some(IntType::new(0).serialize().to_vec()));
+            }
+            let mut s = StringType::from_bytes(Vec::new());
+            let len = s.set_range(offset, value)?;
+            let _ = rlock.set(key, DataType::String(s), ExpireParameter::None);
+            return Ok(Some(IntType::new(len as i32).serialize().to_vec()));
+        }
+        let mut result = opt_key.unwrap();
+        if let DataType::String(ref mut s) = result.value_mut().get_data_mut() {
+            let len = s.set_range(offset, value)?;
+            return Ok(Some(IntType::new(len as i32).serialize().to_vec()));
+        }
+        Err("Data at specified key is not a string".to_owned())
+    }
+}
@@ -0,0 +1,32 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::datastate::{DataState, DataType, IntType},
+};
+
+pub struct StrLenCmd {}
+impl StrLenCmd {
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.len() != 1 {
+            return Err("Invalid number of arguments for STRLEN command".to_owned());
+        }
+        let key =
+            std::str::from_utf8(cmd.arguments[0]).map_err(|_| "Invalid utf8 key".to_owned())?;
+        let rlock = data_state.read();
+        let opt_key = rlock.get(key);
+        if opt_key.is_none() {
+            return Ok(Some(IntType::new(0).serialize().to_vec()));
+        }
+        let result = opt_key.unwrap();
+        if let DataType::String(s) = result.value().get_data() {
+            return Ok(Some(IntType::new(s.len() as i32).serialize().to_vec()));
+        }
+        Err("Data at specified key is not a string".to_owned())
+    }
+}
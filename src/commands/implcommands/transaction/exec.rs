@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::{commands::Command, parser::Parser},
+    srlz_reply,
+    state::{
+        connectionstate::ConnectionState,
+        datastate::{srlz_array, srlz_nil},
+        datastore::DataStore,
+        serverstate::ServerState,
+    },
+};
+
+pub struct ExecCmd {}
+impl ExecCmd {
+    /**
+     * Runs the commands queued since MULTI, Command::execute holds the transaction lock so no
+     * other command runs meanwhile. Replies an array with
+     * the full reply of every command, status byte included, or nil when a watched key changed.
+     * A command that could not be queued discards the whole transaction.
+     */
+    pub fn execute(
+        store: &Arc<DataStore>,
+        conn: &mut ConnectionState,
+        server_state_rwl: &Arc<RwLock<ServerState>>,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if !cmd.arguments.is_empty() {
+            return Err("Invalid number of arguments for EXEC command".to_owned());
+        }
+        let queued = conn.queued.take().ok_or("EXEC without MULTI")?;
+        let watched = std::mem::take(&mut conn.watched);
+        if std::mem::take(&mut conn.queue_failed) {
+            return Err("EXECABORT Transaction discarded because of previous errors".to_owned());
+        }
+        let changed = watched
+            .iter()
+            .any(|(db, key, version)| store.db(*db).read().version(key) != *version);
+        if changed {
+            return Ok(Some(srlz_nil()));
+        }
+        let replies = queued
+            .iter()
+            .map(|payload| {
+                let result = match Parser::parse(payload) {
+                    Ok(queued_cmd) => queued_cmd.run(store, conn, server_state_rwl),
+                    Err(()) => Err("Could not process command".to_owned()),
+                };
+                srlz_reply(result)
+            })
+            .collect();
+        Ok(Some(srlz_array(replies)))
+    }
+}
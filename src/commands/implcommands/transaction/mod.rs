@@ -0,0 +1,3 @@
+pub mod exec;
+pub mod multi;
+pub mod watch;
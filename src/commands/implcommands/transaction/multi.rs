@@ -0,0 +1,28 @@
+use crate::{commands::commands::Command, state::connectionstate::ConnectionState};
+
+pub struct MultiCmd {}
+impl MultiCmd {
+    // starts queuing the commands of the connection until EXEC or DISCARD
+    pub fn execute(conn: &mut ConnectionState, cmd: &Command) -> Result<Option<Vec<u8>>, String> {
+        if !cmd.arguments.is_empty() {
+            return Err("Invalid number of arguments for MULTI command".to_owned());
+        }
+        if conn.queued.is_some() {
+            return Err("MULTI calls can not be nested".to_owned());
+        }
+        conn.queued = Some(Vec::new());
+        Ok(None)
+    }
+    // drops the queued commands and the watched keys
+    pub fn discard(conn: &mut ConnectionState, cmd: &Command) -> Result<Option<Vec<u8>>, String> {
+        if !cmd.arguments.is_empty() {
+            return Err("Invalid number of arguments for DISCARD command".to_owned());
+        }
+        if conn.queued.take().is_none() {
+            return Err("DISCARD without MULTI".to_owned());
+        }
+        conn.queue_failed = false;
+        conn.watched.clear();
+        Ok(None)
+    }
+}
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::Command,
+    state::{connectionstate::ConnectionState, datastate::DataState},
+};
+
+pub struct WatchCmd {}
+impl WatchCmd {
+    // EXEC of the connection fails if one of the keys is written before it
+    pub fn execute(
+        data_state: &Arc<RwLock<DataState>>,
+        conn: &mut ConnectionState,
+        cmd: &Command,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if cmd.arguments.is_empty() {
+            return Err("Invalid number of arguments for WATCH command".to_owned());
+        }
+        if conn.queued.is_some() {
+            return Err("WATCH inside MULTI is not allowed".to_owned());
+        }
+        let rlock = data_state.read();
+        for key in &cmd.arguments {
+            let key = std::str::from_utf8(key).map_err(|_| "Invalid utf8 key".to_owned())?;
+            conn.watched
+                .push((conn.db, key.to_owned(), rlock.version(key)));
+        }
+        Ok(None)
+    }
+    pub fn unwatch(conn: &mut ConnectionState, cmd: &Command) -> Result<Option<Vec<u8>>, String> {
+        if !cmd.arguments.is_empty() {
+            return Err("Invalid number of arguments for UNWATCH command".to_owned());
+        }
+        conn.watched.clear();
+        Ok(None)
+    }
+}
@@ -9,6 +9,7 @@ enum ArgResult {
 }
 pub struct Parser {}
 impl Parser {
+    #[allow(clippy::result_unit_err)]
     pub fn parse<'a>(command_data: &'a [u8]) -> Result<Command<'a>, ()> {
         if command_data.len() < 2 {
             return Err(());
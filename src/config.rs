@@ -1,9 +1,146 @@
 use serde::Deserialize;
 use std::net::SocketAddr;
 
+use crate::{
+    persistence::aof::FsyncPolicy,
+    state::eviction::{EvictionPolicy, MemoryLimit},
+};
+
 #[derive(Debug, Deserialize)]
 pub struct ApplicationConfig {
     pub bind: SocketAddr,
     pub max_connections: u16,
     pub log_config_path: String,
+    #[serde(default = "default_expire_scan_interval_ms")]
+    pub expire_scan_interval_ms: u64,
+    #[serde(default = "default_expire_max_keys_per_tick")]
+    pub expire_max_keys_per_tick: usize,
+    // KEYS fails instead of building a reply larger than this
+    #[serde(default = "default_keys_command_max_keys")]
+    pub keys_command_max_keys: usize,
+    // number of logical databases selectable with SELECT
+    #[serde(default = "default_databases")]
+    pub databases: usize,
+    // bytes of keys and values allowed before eviction, 0 means no limit
+    #[serde(default)]
+    pub max_memory: usize,
+    #[serde(default = "default_max_memory_policy")]
+    pub max_memory_policy: EvictionPolicy,
+    #[serde(default = "default_max_memory_samples")]
+    pub max_memory_samples: usize,
+    #[serde(default = "default_snapshot_path")]
+    pub snapshot_path: String,
+    // [seconds, changes]: save when at least changes writes happened in the last seconds
+    #[serde(default = "default_save_rules")]
+    pub save_rules: Vec<[u64; 2]>,
+    // when enabled the append only log is replayed at startup instead of the snapshot
+    #[serde(default)]
+    pub aof_enabled: bool,
+    #[serde(default = "default_aof_path")]
+    pub aof_path: String,
+    #[serde(default = "default_aof_fsync")]
+    pub aof_fsync: FsyncPolicy,
+    // rewrite when the log grew by this percentage since the last rewrite, 0 disables it
+    #[serde(default = "default_aof_rewrite_percentage")]
+    pub aof_rewrite_percentage: u64,
+    #[serde(default = "default_aof_rewrite_min_size")]
+    pub aof_rewrite_min_size: u64,
+    // "host:port" of the primary to replicate from at startup
+    #[serde(default)]
+    pub replica_of: Option<String>,
+    // replicas refuse writes from their clients
+    #[serde(default = "default_replica_read_only")]
+    pub replica_read_only: bool,
+    // bytes of recent writes kept for replicas resuming after a disconnection
+    #[serde(default = "default_repl_backlog_size")]
+    pub repl_backlog_size: usize,
+    // shards the keyspace over several nodes by hash slot
+    #[serde(default)]
+    pub cluster_enabled: bool,
+    // "host:port" clients are redirected to for the slots of this node, defaults to bind
+    #[serde(default)]
+    pub cluster_announce: Option<String>,
+    // ["host:port", first slot, last slot] ranges used until the nodes file exists
+    #[serde(default)]
+    pub cluster_slots: Vec<(String, u16, u16)>,
+    // slot ownership changed at runtime is kept here across restarts
+    #[serde(default = "default_cluster_config_path")]
+    pub cluster_config_path: String,
+    // scripts running longer make other commands fail with BUSY until they end or are killed
+    #[serde(default = "default_lua_time_limit_ms")]
+    pub lua_time_limit_ms: u64,
+    // bytes of published messages a subscriber may leave unread before it is disconnected, 0
+    // for no limit
+    #[serde(default = "default_pubsub_output_buffer_limit")]
+    pub pubsub_output_buffer_limit: usize,
+    // letters selecting the published keyspace events, see KeyspaceEvents, empty to disable
+    #[serde(default)]
+    pub notify_keyspace_events: String,
+}
+
+impl ApplicationConfig {
+    pub fn cluster_announce(&self) -> String {
+        self.cluster_announce
+            .clone()
+            .unwrap_or_else(|| self.bind.to_string())
+    }
+    pub fn memory_limit(&self) -> MemoryLimit {
+        MemoryLimit {
+            max_memory: self.max_memory,
+            policy: self.max_memory_policy,
+            samples: self.max_memory_samples.max(1),
+        }
+    }
+}
+
+fn default_expire_scan_interval_ms() -> u64 {
+    100
+}
+fn default_expire_max_keys_per_tick() -> usize {
+    2000
+}
+fn default_keys_command_max_keys() -> usize {
+    10000
+}
+fn default_databases() -> usize {
+    16
+}
+fn default_max_memory_policy() -> EvictionPolicy {
+    EvictionPolicy::NoEviction
+}
+fn default_max_memory_samples() -> usize {
+    5
+}
+fn default_snapshot_path() -> String {
+    "dump.echors".to_owned()
+}
+fn default_save_rules() -> Vec<[u64; 2]> {
+    vec![[900, 1], [300, 10], [60, 10000]]
+}
+fn default_aof_path() -> String {
+    "appendonly.echors".to_owned()
+}
+fn default_aof_fsync() -> FsyncPolicy {
+    FsyncPolicy::Everysec
+}
+fn default_aof_rewrite_percentage() -> u64 {
+    100
+}
+fn default_aof_rewrite_min_size() -> u64 {
+    64 * 1024 * 1024
+}
+fn default_replica_read_only() -> bool {
+    true
+}
+fn default_repl_backlog_size() -> usize {
+    1024 * 1024
+}
+fn default_cluster_config_path() -> String {
+    "nodes.echors".to_owned()
+}
+fn default_lua_time_limit_ms() -> u64 {
+    5000
+}
+fn default_pubsub_output_buffer_limit() -> usize {
+    32 * 1024 * 1024
 }
@@ -0,0 +1,75 @@
+/**
+ * Glob style matching as used by KEYS/SCAN patterns:
+ * `*` any sequence, `?` any byte, `[abc]`, `[^abc]`, `[a-z]` classes and `\` escapes.
+ */
+pub fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // position after the last `*` and the text position it is currently matched up to
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        let step = match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+                continue;
+            }
+            Some(b'?') => Some(p + 1),
+            Some(b'[') => match_class(pattern, p, text[t]),
+            Some(b'\\') if p + 1 < pattern.len() => (pattern[p + 1] == text[t]).then_some(p + 2),
+            Some(c) => (*c == text[t]).then_some(p + 1),
+            None => None,
+        };
+        match (step, backtrack) {
+            (Some(next), _) => {
+                p = next;
+                t += 1;
+            }
+            (None, Some((star_p, star_t))) => {
+                p = star_p;
+                t = star_t + 1;
+                backtrack = Some((star_p, star_t + 1));
+            }
+            (None, None) => return false,
+        }
+    }
+    pattern[p.min(pattern.len())..].iter().all(|c| *c == b'*')
+}
+
+// returns the pattern position after the class when c matches it
+fn match_class(pattern: &[u8], start: usize, c: u8) -> Option<usize> {
+    let mut p = start + 1;
+    let negate = pattern.get(p) == Some(&b'^');
+    if negate {
+        p += 1;
+    }
+    let mut matched = false;
+    loop {
+        match pattern.get(p) {
+            // unterminated class, treat the end of the pattern as its end
+            None => break,
+            Some(b']') => {
+                p += 1;
+                break;
+            }
+            Some(b'\\') if p + 1 < pattern.len() => {
+                matched |= pattern[p + 1] == c;
+                p += 2;
+            }
+            Some(from) if pattern.get(p + 1) == Some(&b'-') && p + 2 < pattern.len() => {
+                let to = pattern[p + 2];
+                let (low, high) = if *from <= to {
+                    (*from, to)
+                } else {
+                    (to, *from)
+                };
+                matched |= low <= c && c <= high;
+                p += 3;
+            }
+            Some(other) => {
+                matched |= *other == c;
+                p += 1;
+            }
+        }
+    }
+    (matched != negate).then_some(p)
+}
@@ -1,5 +1,6 @@
 use std::hash::{Hash, Hasher};
 #[allow(non_snake_case)]
+#[derive(Clone)]
 pub struct HLL {
     M: Vec<u8>,
     m: u16,
@@ -51,6 +52,24 @@ impl HLL {
             .iter()
             .fold(0, |s, i| if *i == 0 { s + 1 } else { s });
     }
+    // rebuilds a sketch from the registers of another one, used when loading persisted data
+    pub fn from_registers(bucket_bits: u8, registers: Vec<u8>) -> Result<Self, String> {
+        if !(1..=15).contains(&bucket_bits) || registers.len() != 1usize << bucket_bits {
+            return Err("Invalid HLL registers".to_owned());
+        }
+        let mut hll = HLL::new(bucket_bits);
+        hll.M = registers;
+        Ok(hll)
+    }
+    pub fn bucket_bits(&self) -> u8 {
+        self.register_split_bits
+    }
+    pub fn registers(&self) -> &[u8] {
+        &self.M
+    }
+    pub fn mem_usage(&self) -> usize {
+        self.M.capacity()
+    }
     pub fn reset(&mut self) {
         self.M = vec![0; self.m as usize];
     }
@@ -0,0 +1,235 @@
+use serde_json::Value;
+
+#[derive(Debug, PartialEq)]
+pub enum JsonPathSegment {
+    Key(String),
+    Index(i64),
+}
+
+/**
+ * Subset of JSONPath: root `$` followed by `.name`, `['name']` or `[index]` segments.
+ * Negative indexes count from the end of the array.
+ */
+#[derive(Debug)]
+pub struct JsonPath {
+    segments: Vec<JsonPathSegment>,
+}
+
+impl JsonPath {
+    pub fn root() -> Self {
+        Self {
+            segments: Vec::new(),
+        }
+    }
+    pub fn parse(path: &str) -> Result<Self, String> {
+        let chars: Vec<char> = path.chars().collect();
+        if chars.first() != Some(&'$') {
+            return Err("JSON path must start with $".to_owned());
+        }
+        let mut segments: Vec<JsonPathSegment> = Vec::new();
+        let mut pos = 1;
+        while pos < chars.len() {
+            match chars[pos] {
+                '.' => {
+                    let start = pos + 1;
+                    pos = start;
+                    while pos < chars.len() && chars[pos] != '.' && chars[pos] != '[' {
+                        pos += 1;
+                    }
+                    if pos == start {
+                        return Err(format!("Empty key in JSON path at position {}", start));
+                    }
+                    segments.push(JsonPathSegment::Key(chars[start..pos].iter().collect()));
+                }
+                '[' => {
+                    pos += 1;
+                    match chars.get(pos) {
+                        Some(q) if *q == '\'' || *q == '"' => {
+                            let quote = *q;
+                            pos += 1;
+                            let mut key = String::new();
+                            loop {
+                                match chars.get(pos) {
+                                    None => return Err("Unterminated key in JSON path".to_owned()),
+                                    Some('\\') if pos + 1 < chars.len() => {
+                                        key.push(chars[pos + 1]);
+                                        pos += 2;
+                                    }
+                                    Some(c) if *c == quote => {
+                                        pos += 1;
+                                        break;
+                                    }
+                                    Some(c) => {
+                                        key.push(*c);
+                                        pos += 1;
+                                    }
+                                }
+                            }
+                            segments.push(JsonPathSegment::Key(key));
+                        }
+                        _ => {
+                            let start = pos;
+                            while pos < chars.len() && chars[pos] != ']' {
+                                pos += 1;
+                            }
+                            let idx: String = chars[start..pos].iter().collect();
+                            let idx = idx
+                                .trim()
+                                .parse::<i64>()
+                                .map_err(|_| format!("Invalid index in JSON path: {}", idx))?;
+                            segments.push(JsonPathSegment::Index(idx));
+                        }
+                    }
+                    if chars.get(pos) != Some(&']') {
+                        return Err("Missing ] in JSON path".to_owned());
+                    }
+                    pos += 1;
+                }
+                c => return Err(format!("Unexpected character '{}' in JSON path", c)),
+            }
+        }
+        Ok(Self { segments })
+    }
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+    pub fn get<'a>(&self, root: &'a Value) -> Option<&'a Value> {
+        Self::walk(root, &self.segments)
+    }
+    pub fn get_mut<'a>(&self, root: &'a mut Value) -> Option<&'a mut Value> {
+        Self::walk_mut(root, &self.segments)
+    }
+    /**
+     * Sets the value at the path. Object keys are created if missing, array indexes must exist.
+     */
+    pub fn set(&self, root: &mut Value, value: Value) -> Result<(), String> {
+        let (last, parent_path) = match self.segments.split_last() {
+            None => {
+                *root = value;
+                return Ok(());
+            }
+            Some(split) => split,
+        };
+        let parent = Self::walk_mut(root, parent_path).ok_or("Path not found")?;
+        match (parent, last) {
+            (Value::Object(map), JsonPathSegment::Key(k)) => {
+                map.insert(k.clone(), value);
+                Ok(())
+            }
+            (Value::Array(arr), JsonPathSegment::Index(i)) => {
+                let idx = resolve_index(arr.len(), *i).ok_or("Index out of range")?;
+                arr[idx] = value;
+                Ok(())
+            }
+            _ => Err("Path does not match document structure".to_owned()),
+        }
+    }
+    /**
+     * Removes the value at the path, the root can not be removed this way.
+     */
+    pub fn delete(&self, root: &mut Value) -> bool {
+        let (last, parent_path) = match self.segments.split_last() {
+            None => return false,
+            Some(split) => split,
+        };
+        match (Self::walk_mut(root, parent_path), last) {
+            (Some(Value::Object(map)), JsonPathSegment::Key(k)) => map.remove(k).is_some(),
+            (Some(Value::Array(arr)), JsonPathSegment::Index(i)) => {
+                match resolve_index(arr.len(), *i) {
+                    Some(idx) => {
+                        arr.remove(idx);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            _ => false,
+        }
+    }
+    fn walk<'a>(value: &'a Value, segments: &[JsonPathSegment]) -> Option<&'a Value> {
+        let mut current = value;
+        for segment in segments {
+            current = match (current, segment) {
+                (Value::Object(map), JsonPathSegment::Key(k)) => map.get(k)?,
+                (Value::Array(arr), JsonPathSegment::Index(i)) => {
+                    arr.get(resolve_index(arr.len(), *i)?)?
+                }
+                _ => return None,
+            };
+        }
+        Some(current)
+    }
+    fn walk_mut<'a>(value: &'a mut Value, segments: &[JsonPathSegment]) -> Option<&'a mut Value> {
+        let mut current = value;
+        for segment in segments {
+            current = match (current, segment) {
+                (Value::Object(map), JsonPathSegment::Key(k)) => map.get_mut(k)?,
+                (Value::Array(arr), JsonPathSegment::Index(i)) => {
+                    let idx = resolve_index(arr.len(), *i)?;
+                    arr.get_mut(idx)?
+                }
+                _ => return None,
+            };
+        }
+        Some(current)
+    }
+}
+
+fn resolve_index(len: usize, index: i64) -> Option<usize> {
+    let idx = if index < 0 { len as i64 + index } else { index };
+    (idx >= 0 && (idx as usize) < len).then_some(idx as usize)
+}
+
+pub fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/**
+ * Approximate heap bytes of a document. Containers with many entries are sampled
+ * and extrapolated so the estimate stays cheap on large documents.
+ */
+pub fn json_mem_usage(value: &Value) -> usize {
+    const SAMPLES: usize = 64;
+    let node = std::mem::size_of::<Value>();
+    match value {
+        Value::String(s) => s.capacity(),
+        Value::Array(arr) => {
+            let step = (arr.len() / SAMPLES).max(1);
+            let sampled: Vec<usize> = arr
+                .iter()
+                .step_by(step)
+                .take(SAMPLES)
+                .map(json_mem_usage)
+                .collect();
+            arr.capacity() * node + extrapolate(&sampled, arr.len())
+        }
+        Value::Object(map) => {
+            let step = (map.len() / SAMPLES).max(1);
+            let sampled: Vec<usize> = map
+                .iter()
+                .step_by(step)
+                .take(SAMPLES)
+                .map(|(k, v)| {
+                    k.capacity() + std::mem::size_of::<String>() + node + json_mem_usage(v)
+                })
+                .collect();
+            extrapolate(&sampled, map.len())
+        }
+        _ => 0,
+    }
+}
+
+fn extrapolate(sampled: &[usize], total: usize) -> usize {
+    match sampled.len() {
+        0 => 0,
+        n => sampled.iter().sum::<usize>() * total / n,
+    }
+}
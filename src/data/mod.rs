@@ -1,2 +1,6 @@
+mod glob;
 mod hll;
+mod json;
+pub use glob::glob_match;
 pub use hll::HLL;
+pub use json::{json_mem_usage, json_type_name, JsonPath};
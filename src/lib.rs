@@ -0,0 +1,51 @@
+// the library holds everything shared by the server and echors-dump, they are its only users
+
+pub mod cluster;
+pub mod commands;
+pub mod config;
+pub mod data;
+pub mod persistence;
+pub mod pubsub;
+pub mod replication;
+pub mod scripting;
+pub mod state;
+mod tests;
+
+use log::trace;
+
+use crate::{
+    pubsub::keyspace::{EventClass, KeyspaceEvents},
+    state::{datastate::StringType, datastore::DataStore},
+};
+
+#[repr(u8)]
+pub enum CommandResult {
+    OK = 1,
+    ERR = 2,
+    // message published to a channel the connection subscribed to
+    PUSH = 3,
+}
+// status byte followed by the typed value, commands without a value reply the string OK
+pub fn srlz_reply(result: Result<Option<Vec<u8>>, String>) -> Vec<u8> {
+    match result {
+        Ok(data) => {
+            let mut response = vec![CommandResult::OK as u8];
+            response.extend(data.unwrap_or_else(|| StringType::new("OK".to_owned()).serialize()));
+            response
+        }
+        Err(message) => {
+            let mut response = vec![CommandResult::ERR as u8];
+            response.extend(StringType::new(message).serialize());
+            response
+        }
+    }
+}
+pub fn run_maintenance(store: &DataStore, events: &KeyspaceEvents, max_expired: usize) {
+    trace!("Maintenance start");
+    for (index, db) in store.dbs().iter().enumerate() {
+        for key in db.read().maintenance_work(max_expired) {
+            events.notify(EventClass::Expired, "expired", index, key.as_bytes());
+        }
+    }
+    trace!("Maintenance end");
+}
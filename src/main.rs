@@ -1,24 +1,31 @@
-mod commands;
-mod config;
-mod data;
-mod state;
-mod tests;
-
-use commands::commands::Command;
-use config::ApplicationConfig;
 use config_file::FromConfigFile;
-use log::{info, trace, warn};
+use echors::{
+    cluster::topology::Topology,
+    commands::{
+        commands::{Command, CommandType},
+        parser::Parser,
+    },
+    config::ApplicationConfig,
+    persistence::{
+        aof::{self, Aof},
+        snapshot::Snapshotter,
+    },
+    pubsub::keyspace::KeyspaceEvents,
+    replication::primary::serve_replica,
+    run_maintenance, srlz_reply,
+    state::{
+        connectionstate::ConnectionState, datastate::StringType, datastore::DataStore,
+        expires::current_millis, serverstate::ServerState,
+    },
+};
+use log::{info, warn};
 use parking_lot::RwLock;
-use state::datastate::DataState;
 use std::{path::PathBuf, sync::Arc, time::Duration};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     net::{TcpListener, TcpStream},
 };
 
-use crate::{commands::commands::CommandType, state::serverstate::ServerState};
-use crate::{commands::parser::Parser, state::datastate::DataTypeByte};
-
 #[cfg(not(target_env = "msvc"))]
 use tikv_jemallocator::Jemalloc;
 
@@ -26,76 +33,77 @@ use tikv_jemallocator::Jemalloc;
 #[global_allocator]
 static GLOBAL: Jemalloc = Jemalloc;
 
-#[repr(u8)]
-pub enum CommandResult {
-    OK = 1,
-    ERR = 2,
-}
 async fn manage_socket(
     mut socket: TcpStream,
     server_state: Arc<RwLock<ServerState>>,
-    data_state: Arc<RwLock<DataState>>,
+    store: Arc<DataStore>,
 ) {
     let socket_addr = socket.peer_addr().unwrap();
+    let mut conn = ConnectionState::default();
     info!("Client {} connected.", socket_addr);
     let result = loop {
+        // subscribed connections get the published messages while waiting for their next command
+        if let Some(subscription) = conn.subscription.as_mut() {
+            let mut peek_buf = [0u8];
+            tokio::select! {
+                push = subscription.next_push() => {
+                    let Some(push) = push else {
+                        break "subscriber over the output buffer limit".to_owned();
+                    };
+                    if let Err(e) = socket.write_all(&push).await {
+                        break format!("failed to write to socket; err = {:?}", e);
+                    }
+                    continue;
+                }
+                // waits for the next command without consuming it
+                peeked = socket.peek(&mut peek_buf) => {
+                    if let Err(e) = peeked {
+                        break format!("failed to read from socket; err = {:?}", e);
+                    }
+                }
+            }
+        }
         let buf_len = match socket.read_u32_le().await {
             Ok(n) => n,
             Err(e) => break format!("failed to read from socket; err = {:?}", e),
         } as usize;
         let mut next_buff: Vec<u8> = vec![0; buf_len];
-        let _ = match socket.read(&mut next_buff).await {
-            // socket closed
-            Ok(n) if n != buf_len => {
-                break format!(
-                    "Expected {:?} bytes long message. Found {:?} bytes.",
-                    buf_len, n
-                )
-            }
-            Ok(n) => n,
-            Err(e) => break format!("failed to read from socket; err = {:?}", e),
-        };
+        // large commands can arrive in several segments
+        if let Err(e) = socket.read_exact(&mut next_buff).await {
+            break format!("Expected {:?} bytes long message. err = {:?}", buf_len, e);
+        }
 
         //let cmd = String::from_utf8_lossy(&next_buff).into_owned();
         let command_result = Parser::parse(&next_buff);
-        let mut response: Vec<u8> = Vec::new();
-        match command_result {
-            Ok(cmd) => {
-                if let CommandType::Flush = cmd.command_type {
-                    data_state.write().flush();
-                    response.push(CommandResult::OK as u8);
-                    response.push(DataTypeByte::String as u8);
-                    response.append(&mut u32::to_le_bytes(2 as u32).to_vec());
-                    response.append(&mut "OK".as_bytes().to_vec());
-                } else {
-                    match process_cmd(&cmd, &server_state, &data_state).await {
-                        Ok(data) => {
-                            response.push(CommandResult::OK as u8);
-                            response.append(&mut (data.to_vec()));
-                        }
-                        Err(message) => {
-                            response.push(CommandResult::ERR as u8);
-                            response.push(DataTypeByte::String as u8);
-                            response.append(&mut u32::to_le_bytes(message.len() as u32).to_vec());
-                            response.append(&mut message.as_bytes().to_vec());
-                        }
-                    }
-                }
+        // a replica asking for the write stream, the connection is only used for that afterwards
+        if let Some((id, offset)) = command_result.as_ref().ok().and_then(psync_position) {
+            let replication = server_state.read().replication.clone();
+            break serve_replica(&mut socket, store.clone(), replication, id, offset).await;
+        }
+        let result = match command_result {
+            // commands after MULTI are only queued until EXEC or DISCARD
+            Ok(cmd) if conn.queued.is_some() && !cmd.command_type.controls_transaction() => {
+                queue_cmd(&cmd, &next_buff, &mut conn)
             }
+            Ok(cmd) => process_cmd(&cmd, &server_state, &store, &mut conn).await,
             Err(()) => {
-                let err_msg = "Could not process command";
-                response.push(CommandResult::ERR as u8);
-                response.push(DataTypeByte::String as u8);
-                response.append(&mut u32::to_le_bytes(err_msg.len() as u32).to_vec());
-                response.append(&mut err_msg.as_bytes().to_vec());
+                if conn.queued.is_some() {
+                    conn.queue_failed = true;
+                }
+                Err("Could not process command".to_owned())
             }
-        }
+        };
+        let response = srlz_reply(result);
 
         // Write the data back
         if let Err(e) = socket.write_all(&response).await {
             break format!("failed to write to socket; err = {:?}", e);
         }
     };
+    if let Some(subscription) = conn.subscription.take() {
+        let pubsub = server_state.read().pubsub.clone();
+        pubsub.remove(subscription);
+    }
     {
         let mut mut_ser_state = server_state.write();
         mut_ser_state.current_connections -= 1;
@@ -103,37 +111,84 @@ async fn manage_socket(
     warn!("Closing socket {} due to {}", socket_addr, result);
 }
 
+// history id and offset of a PSYNC command
+fn psync_position(cmd: &Command) -> Option<(String, u64)> {
+    match (cmd.command_type, cmd.arguments.as_slice()) {
+        (CommandType::Psync, [id, offset]) => Some((
+            String::from_utf8_lossy(id).into_owned(),
+            u64::from_le_bytes((*offset).try_into().ok()?),
+        )),
+        _ => None,
+    }
+}
+fn queue_cmd(
+    cmd: &Command,
+    payload: &[u8],
+    conn: &mut ConnectionState,
+) -> Result<Option<Vec<u8>>, String> {
+    if cmd.command_type == CommandType::Unknown {
+        conn.queue_failed = true;
+        return Err("Unknown command".to_owned());
+    }
+    if let Some(queued) = conn.queued.as_mut() {
+        queued.push(payload.to_vec());
+    }
+    Ok(Some(StringType::new("QUEUED".to_owned()).serialize()))
+}
 async fn process_cmd<'a>(
     cmd: &Command<'_>,
     server_state: &Arc<RwLock<ServerState>>,
-    data_state: &Arc<RwLock<DataState>>,
-) -> Result<Vec<u8>, String> {
-    let result = cmd.execute(data_state, server_state);
+    store: &Arc<DataStore>,
+    conn: &mut ConnectionState,
+) -> Result<Option<Vec<u8>>, String> {
+    let result = cmd.execute(store, conn, server_state);
     if !result.is_err() {
         let mut state = server_state.write();
         state.processed_commands += 1;
     }
-    let mut ok_result_message: Vec<u8> = Vec::new();
-    ok_result_message.push(DataTypeByte::String as u8);
-    ok_result_message.append(&mut u32::to_le_bytes(2).to_vec());
-    ok_result_message.append(&mut "OK".as_bytes().to_vec());
-    match result {
-        Ok(o) => match o {
-            Some(o) => Ok(o),
-            None => Ok(ok_result_message),
-        },
-        Err(e) => Err(e),
+    result
+}
+async fn maintenance_work(
+    store: Arc<DataStore>,
+    events: Arc<KeyspaceEvents>,
+    interval: u64,
+    max_expired: usize,
+) {
+    loop {
+        run_maintenance(&store, &events, max_expired);
+        tokio::time::sleep(Duration::from_millis(interval)).await;
     }
 }
-fn _maintenance_work(data_state: &Arc<RwLock<DataState>>) {
-    trace!("Maintenance start");
-    data_state.read().maintenance_work();
-    trace!("Maintenance end");
+// checks the save rules every second, a running save postpones the next one
+async fn auto_save(store: Arc<DataStore>, snapshots: Arc<Snapshotter>, rules: Vec<[u64; 2]>) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let elapsed = (current_millis() / 1000).saturating_sub(snapshots.last_save());
+        let changes = store.changes();
+        let due = rules
+            .iter()
+            .any(|[seconds, min_changes]| changes >= *min_changes && elapsed >= *seconds);
+        if due && !snapshots.in_progress() {
+            info!("{} changes in {} seconds, saving", changes, elapsed);
+            if let Err(e) = snapshots.save_in_background(&store) {
+                warn!("Could not start auto save: {}", e);
+            }
+        }
+    }
 }
-async fn maintenance_work(data_state: Arc<RwLock<DataState>>) {
+// fsyncs for the everysec policy and starts the automatic rewrite when the log grew enough
+async fn aof_maintenance(store: Arc<DataStore>, aof: Arc<Aof>, app_cfg: Arc<ApplicationConfig>) {
     loop {
-        _maintenance_work(&data_state);
-        tokio::time::sleep(Duration::from_millis(5000)).await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        aof.sync_pending();
+        if app_cfg.aof_rewrite_percentage > 0
+            && aof.should_rewrite(app_cfg.aof_rewrite_min_size, app_cfg.aof_rewrite_percentage)
+        {
+            info!("AOF grew to {} bytes, rewriting", aof.current_size());
+            if let Err(e) = aof.rewrite_in_background(&store) {
+                warn!("Could not start AOF rewrite: {}", e);
+            }
+        }
     }
 }
 fn init_logging(app_config: &ApplicationConfig) {
@@ -141,15 +196,63 @@ fn init_logging(app_config: &ApplicationConfig) {
 }
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let app_cfg: ApplicationConfig =
-        ApplicationConfig::from_config_file(PathBuf::from("./echors.toml")).unwrap();
+    let app_cfg: Arc<ApplicationConfig> =
+        Arc::new(ApplicationConfig::from_config_file(PathBuf::from("./echors.toml")).unwrap());
     init_logging(&app_cfg);
-    let server_state = Arc::new(RwLock::new(ServerState::new(env!("CARGO_PKG_VERSION"))));
-    let data_state = Arc::new(RwLock::new(DataState::new()));
+    let server_state = Arc::new(RwLock::new(ServerState::new(
+        env!("CARGO_PKG_VERSION"),
+        app_cfg.clone(),
+    )));
+    let store = Arc::new(DataStore::new(app_cfg.databases, app_cfg.memory_limit()));
+    if app_cfg.cluster_enabled {
+        let topology = Topology::new(
+            app_cfg.cluster_announce(),
+            PathBuf::from(&app_cfg.cluster_config_path),
+            &app_cfg.cluster_slots,
+        )?;
+        info!("Cluster mode enabled, announced as {}", topology.myself());
+        server_state.write().cluster = Some(Arc::new(topology));
+    }
+    let snapshots = server_state.read().snapshots.clone();
+    let aof_path = PathBuf::from(&app_cfg.aof_path);
+    // an existing log is the most complete copy, the snapshot seeds a log created now
+    let seed_aof = !aof_path.exists();
+    if app_cfg.aof_enabled && !seed_aof {
+        let replayed = aof::replay(&aof_path, &store, &server_state)?;
+        info!("Replayed {} commands from {}", replayed, app_cfg.aof_path);
+    } else {
+        let loaded = snapshots.load(&store)?;
+        info!("Loaded {} keys from {}", loaded, app_cfg.snapshot_path);
+    }
+    if app_cfg.aof_enabled {
+        let aof = Arc::new(Aof::open(aof_path, app_cfg.aof_fsync)?);
+        if seed_aof {
+            aof.rewrite_in_background(&store)?;
+        }
+        server_state.write().aof = Some(aof.clone());
+        tokio::spawn(aof_maintenance(store.clone(), aof, app_cfg.clone()));
+    }
     info!("Starting server. Binding on: {}", &app_cfg.bind);
     let listener: TcpListener = TcpListener::bind(&app_cfg.bind).await?;
+    if let Some(primary) = &app_cfg.replica_of {
+        let replication = server_state.read().replication.clone();
+        replication.set_primary(Some(primary.clone()), &store, &server_state);
+    }
     //let max_conn_limiter = Arc::new(Semaphore::new(app_cfg.max_connections as usize));
-    tokio::spawn(maintenance_work(data_state.clone()));
+    let keyspace_events = server_state.read().keyspace_events.clone();
+    tokio::spawn(maintenance_work(
+        store.clone(),
+        keyspace_events,
+        app_cfg.expire_scan_interval_ms,
+        app_cfg.expire_max_keys_per_tick,
+    ));
+    if !app_cfg.save_rules.is_empty() {
+        tokio::spawn(auto_save(
+            store.clone(),
+            snapshots,
+            app_cfg.save_rules.clone(),
+        ));
+    }
     loop {
         // let permit = max_conn_limiter.clone().acquire_owned().await.unwrap();
         match listener.accept().await {
@@ -167,11 +270,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         mut_state_data.current_connections += 1;
                         mut_state_data.total_connections += 1;
                     }
-                    tokio::spawn(manage_socket(
-                        _socket,
-                        server_state.clone(),
-                        data_state.clone(),
-                    ));
+                    tokio::spawn(manage_socket(_socket, server_state.clone(), store.clone()));
                 }
             }
             Err(e) => warn!("{:?}", e),
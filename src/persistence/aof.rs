@@ -0,0 +1,604 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use log::{error, info, warn};
+use parking_lot::{Mutex, MutexGuard, RwLock};
+use serde::Deserialize;
+
+use crate::{
+    commands::{
+        commands::{Command, CommandType},
+        parser::Parser,
+    },
+    persistence::codec::{dump_ttl, encode_dump, RESTORE_ABSTTL},
+    state::{
+        connectionstate::ConnectionState,
+        datastate::DataType,
+        datastore::DataStore,
+        expires::{current_millis, ExpireParameter},
+        serverstate::ServerState,
+    },
+};
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FsyncPolicy {
+    Always,
+    Everysec,
+    No,
+}
+
+// items per LPUSH emitted by the rewrite
+const REWRITE_LIST_BATCH: usize = 1000;
+
+/**
+ * Append only log of the write commands, every record is a command in its wire encoding:
+ * u32 length followed by the command type and arguments. SELECT records are inserted when the
+ * database changes and relative expiries are logged as absolute deadlines so replay is exact.
+ */
+#[derive(Debug)]
+pub struct Aof {
+    path: PathBuf,
+    policy: FsyncPolicy,
+    writer: Mutex<AofWriter>,
+    rewriting: AtomicBool,
+    current_size: AtomicU64,
+    // size right after the last rewrite, base of the automatic rewrite growth check
+    base_size: AtomicU64,
+}
+
+#[derive(Debug)]
+struct AofWriter {
+    file: BufWriter<File>,
+    // database of the last record, None forces the next record to select it again
+    db: Option<usize>,
+    rewrite: Option<Rewrite>,
+    unsynced: bool,
+}
+
+type ShardCopy = Vec<(String, Option<u64>, DataType)>;
+
+/**
+ * State of a running rewrite. Shards are copied one at a time with writes paused, in database
+ * then shard order. Writes to shards already copied are buffered and added to the rewritten
+ * log at the end, writes to the others reach it through the copy.
+ */
+struct Rewrite {
+    store: Arc<DataStore>,
+    // next shard the rewrite thread looks at as (database, shard)
+    cursor: (usize, usize),
+    // shards copied or emptied by a buffered flush, as (database, shard)
+    copied: HashSet<(usize, usize)>,
+    // shards copied by AofGuard::prepare, not written by the rewrite thread yet
+    pending: Vec<(usize, ShardCopy)>,
+    buffer: Vec<u8>,
+    // database of the last buffered record
+    db: Option<usize>,
+}
+
+impl std::fmt::Debug for Rewrite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Rewrite")
+            .field("cursor", &self.cursor)
+            .field("copied", &self.copied.len())
+            .field("buffered", &self.buffer.len())
+            .finish_non_exhaustive()
+    }
+}
+
+// what a write changes, as seen by a running rewrite
+enum Touched {
+    // shards as (database, shard)
+    Shards(Vec<(usize, usize)>),
+    // databases emptied by the write, their previous content does not matter
+    Emptied(Vec<usize>),
+}
+
+impl Rewrite {
+    // next shard not copied yet, None once every database was visited
+    fn copy_next(&mut self) -> Option<(usize, ShardCopy)> {
+        loop {
+            let (db, shard) = self.cursor;
+            let state = self.store.dbs().get(db)?.read();
+            self.cursor = match shard + 1 < state.shard_count() {
+                true => (db, shard + 1),
+                false => (db + 1, 0),
+            };
+            if self.copied.insert((db, shard)) {
+                return Some((db, state.copy_shard(shard)));
+            }
+        }
+    }
+    fn shards_of(&self, db: usize) -> Vec<(usize, usize)> {
+        let count = self.store.db(db).read().shard_count();
+        (0..count).map(|shard| (db, shard)).collect()
+    }
+    fn touched(&self, db: usize, cmd: &Command) -> Touched {
+        let args = &cmd.arguments;
+        let shard_of = |db: usize, key: &[u8]| {
+            let key = std::str::from_utf8(key).ok()?;
+            Some((db, self.store.db(db).read().shard_of(key)))
+        };
+        match cmd.command_type {
+            CommandType::FlushDb => Touched::Emptied(vec![db]),
+            CommandType::Flush => Touched::Emptied((0..self.store.dbs().len()).collect()),
+            // both databases change completely, invalid indexes fail the command
+            CommandType::SwapDb => Touched::Shards(
+                args.iter()
+                    .filter_map(|index| self.store.parse_index(index).ok())
+                    .flat_map(|db| self.shards_of(db))
+                    .collect(),
+            ),
+            CommandType::Move => {
+                let dest = args.get(1).and_then(|i| self.store.parse_index(i).ok());
+                let key = args.first().copied().unwrap_or_default();
+                Touched::Shards(
+                    [Some(db), dest]
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|db| shard_of(db, key))
+                        .collect(),
+                )
+            }
+            command_type => Touched::Shards(
+                command_type
+                    .key_arguments(args)
+                    .iter()
+                    .filter_map(|key| shard_of(db, key))
+                    .collect(),
+            ),
+        }
+    }
+    /**
+     * Copies the shards the write touches that are not copied yet, so that the whole write can
+     * be buffered. Emptied databases need no copy, the buffered flush clears what was copied.
+     */
+    fn prepare(&mut self, db: usize, cmd: &Command) {
+        match self.touched(db, cmd) {
+            Touched::Shards(shards) => {
+                for (db, shard) in shards {
+                    if self.copied.insert((db, shard)) {
+                        let copy = self.store.db(db).read().copy_shard(shard);
+                        self.pending.push((db, copy));
+                    }
+                }
+            }
+            Touched::Emptied(dbs) => {
+                for db in dbs {
+                    let shards = self.shards_of(db);
+                    self.copied.extend(shards);
+                }
+            }
+        }
+    }
+    // whether the write reaches the rewritten log through the buffer instead of a copy
+    fn buffers(&self, db: usize, cmd: &Command) -> bool {
+        let shards = match self.touched(db, cmd) {
+            Touched::Shards(shards) => shards,
+            Touched::Emptied(dbs) => dbs.into_iter().flat_map(|db| self.shards_of(db)).collect(),
+        };
+        shards.iter().all(|shard| self.copied.contains(shard))
+    }
+}
+
+/**
+ * Held while a write command executes so that the log order is the execution order.
+ */
+pub struct AofGuard<'a> {
+    aof: &'a Aof,
+    writer: MutexGuard<'a, AofWriter>,
+}
+
+impl AofGuard<'_> {
+    // called before the write executes, see Rewrite::prepare
+    pub fn prepare(&mut self, db: usize, cmd: &Command) {
+        if let Some(rewrite) = self.writer.rewrite.as_mut() {
+            rewrite.prepare(db, cmd);
+        }
+    }
+    // deadline is the expiry of the key once the command ran, see propagation_form
+    pub fn append(&mut self, db: usize, cmd: &Command, deadline: Option<u64>) {
+        if let Some(rewrite) = self.writer.rewrite.as_mut() {
+            if rewrite.buffers(db, cmd) {
+                let records = propagation_records(&mut rewrite.db, db, cmd, deadline);
+                rewrite.buffer.extend(records);
+            }
+        }
+        let records = propagation_records(&mut self.writer.db, db, cmd, deadline);
+        if let Err(e) = self.aof.write(&mut self.writer, &records) {
+            error!("Could not append to {:?}: {}", self.aof.path, e);
+        }
+    }
+    // keys evicted by the memory limit before the command are logged as deletes
+    pub fn append_evicted(&mut self, evicted: &[(usize, String)]) {
+        for (db, key) in evicted {
+            self.append(*db, &delete_command(key), None);
+        }
+    }
+}
+
+impl Aof {
+    pub fn open(path: PathBuf, policy: FsyncPolicy) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| e.to_string())?;
+        let size = file.metadata().map_err(|e| e.to_string())?.len();
+        Ok(Self {
+            path,
+            policy,
+            writer: Mutex::new(AofWriter {
+                file: BufWriter::new(file),
+                db: None,
+                rewrite: None,
+                unsynced: false,
+            }),
+            rewriting: AtomicBool::new(false),
+            current_size: AtomicU64::new(size),
+            base_size: AtomicU64::new(size),
+        })
+    }
+    pub fn lock(&self) -> AofGuard<'_> {
+        AofGuard {
+            aof: self,
+            writer: self.writer.lock(),
+        }
+    }
+    pub fn current_size(&self) -> u64 {
+        self.current_size.load(Ordering::Relaxed)
+    }
+    pub fn rewriting(&self) -> bool {
+        self.rewriting.load(Ordering::Relaxed)
+    }
+    // records reach the OS on every append, fsync depends on the policy
+    fn write(&self, writer: &mut AofWriter, records: &[u8]) -> std::io::Result<()> {
+        writer.file.write_all(records)?;
+        writer.file.flush()?;
+        self.current_size
+            .fetch_add(records.len() as u64, Ordering::Relaxed);
+        match self.policy {
+            FsyncPolicy::Always => writer.file.get_ref().sync_data()?,
+            _ => writer.unsynced = true,
+        }
+        Ok(())
+    }
+    // called every second, only does something with the everysec policy
+    pub fn sync_pending(&self) {
+        if self.policy != FsyncPolicy::Everysec {
+            return;
+        }
+        let mut writer = self.writer.lock();
+        if writer.unsynced {
+            if let Err(e) = writer.file.get_ref().sync_data() {
+                error!("Could not fsync {:?}: {}", self.path, e);
+            }
+            writer.unsynced = false;
+        }
+    }
+    pub fn should_rewrite(&self, min_size: u64, percentage: u64) -> bool {
+        let (current, base) = (self.current_size(), self.base_size.load(Ordering::Relaxed));
+        !self.rewriting() && current >= min_size && current >= base + base * percentage / 100
+    }
+    /**
+     * Writes the dataset to a new log on another thread. The log lock is only held to copy one
+     * shard at a time and to append the buffered writes before the new log replaces the old one.
+     */
+    pub fn rewrite_in_background(self: &Arc<Self>, store: &Arc<DataStore>) -> Result<(), String> {
+        if self.rewriting.swap(true, Ordering::AcqRel) {
+            return Err("An AOF rewrite is already in progress".to_owned());
+        }
+        self.writer.lock().rewrite = Some(Rewrite {
+            store: store.clone(),
+            cursor: (0, 0),
+            copied: HashSet::new(),
+            pending: Vec::new(),
+            buffer: Vec::new(),
+            db: None,
+        });
+        let aof = self.clone();
+        std::thread::spawn(move || {
+            match aof.finish_rewrite() {
+                Ok(size) => info!("AOF rewritten, {} bytes", size),
+                Err(e) => {
+                    warn!("AOF rewrite failed: {}", e);
+                    aof.writer.lock().rewrite = None;
+                }
+            }
+            aof.rewriting.store(false, Ordering::Release);
+        });
+        Ok(())
+    }
+    fn finish_rewrite(&self) -> Result<u64, String> {
+        let tmp_path = self.path.with_extension("rewrite");
+        let mut file = BufWriter::new(File::create(&tmp_path).map_err(|e| e.to_string())?);
+        let mut records: Vec<u8> = Vec::new();
+        let mut last_db: Option<usize> = None;
+        loop {
+            let copies = {
+                let mut writer = self.writer.lock();
+                let rewrite = writer.rewrite.as_mut().ok_or("Rewrite state missing")?;
+                let mut copies = std::mem::take(&mut rewrite.pending);
+                copies.extend(rewrite.copy_next());
+                copies
+            };
+            if copies.is_empty() {
+                break;
+            }
+            for (db, entries) in copies.iter().filter(|(_, e)| !e.is_empty()) {
+                if last_db != Some(*db) {
+                    let index = (*db as u32).to_le_bytes();
+                    put_record(&mut records, &encode(CommandType::Select, &[&index]));
+                    last_db = Some(*db);
+                }
+                for (key, deadline, data) in entries {
+                    rewrite_entry(&mut records, key, *deadline, data);
+                    if records.len() > 1 << 20 {
+                        file.write_all(&records).map_err(|e| e.to_string())?;
+                        records.clear();
+                    }
+                }
+            }
+        }
+        file.write_all(&records).map_err(|e| e.to_string())?;
+        file.flush().map_err(|e| e.to_string())?;
+        // the swap happens with writes paused again, the buffer is complete at this point
+        let mut writer = self.writer.lock();
+        let buffer = writer.rewrite.take().map(|r| r.buffer).unwrap_or_default();
+        file.write_all(&buffer).map_err(|e| e.to_string())?;
+        let file = file.into_inner().map_err(|e| e.into_error().to_string())?;
+        file.sync_all().map_err(|e| e.to_string())?;
+        fs::rename(&tmp_path, &self.path).map_err(|e| e.to_string())?;
+        let size = file.metadata().map_err(|e| e.to_string())?.len();
+        writer.file = BufWriter::new(file);
+        writer.db = None;
+        self.current_size.store(size, Ordering::Relaxed);
+        self.base_size.store(size, Ordering::Relaxed);
+        Ok(size)
+    }
+}
+
+/**
+ * Replays the log through the normal command path. A final record cut by a crash is removed
+ * from the file, any other damage stops the startup.
+ */
+pub fn replay(
+    path: &PathBuf,
+    store: &Arc<DataStore>,
+    server_state: &Arc<RwLock<ServerState>>,
+) -> Result<usize, String> {
+    let bytes = match fs::read(path) {
+        Ok(b) => b,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.to_string()),
+    };
+    let mut conn = ConnectionState {
+        replicated: true,
+        ..Default::default()
+    };
+    let mut pos = 0;
+    let mut replayed = 0;
+    while pos < bytes.len() {
+        let len = match bytes.get(pos..pos + 4) {
+            Some(l) => u32::from_le_bytes(l.try_into().unwrap()) as usize,
+            None => break,
+        };
+        let payload = match bytes.get(pos + 4..pos + 4 + len) {
+            Some(p) => p,
+            None => break,
+        };
+        let cmd =
+            Parser::parse(payload).map_err(|_| format!("Invalid AOF record at offset {}", pos))?;
+        cmd.execute(store, &mut conn, server_state)
+            .map_err(|e| format!("AOF record at offset {} failed: {}", pos, e))?;
+        pos += 4 + len;
+        replayed += 1;
+    }
+    if pos < bytes.len() {
+        warn!(
+            "AOF {:?} ends with an incomplete record, truncating {} bytes",
+            path,
+            bytes.len() - pos
+        );
+        let file = OpenOptions::new()
+            .write(true)
+            .open(path)
+            .map_err(|e| e.to_string())?;
+        file.set_len(pos as u64).map_err(|e| e.to_string())?;
+        file.sync_all().map_err(|e| e.to_string())?;
+    }
+    // the replayed writes are already durable, they do not count toward the save rules
+    store.forget_changes(store.changes());
+    Ok(replayed)
+}
+
+/**
+ * Records for a write as logged and sent to replicas, preceded by a SELECT when the database
+ * differs from the one of the previous record.
+ */
+pub fn propagation_records(
+    last_db: &mut Option<usize>,
+    db: usize,
+    cmd: &Command,
+    deadline: Option<u64>,
+) -> Vec<u8> {
+    let mut records: Vec<u8> = Vec::new();
+    // FLUSHALL and SWAPDB do not depend on the selected database
+    let db_bound = !matches!(cmd.command_type, CommandType::Flush | CommandType::SwapDb);
+    if db_bound && *last_db != Some(db) {
+        let index = (db as u32).to_le_bytes();
+        put_record(&mut records, &encode(CommandType::Select, &[&index]));
+        *last_db = Some(db);
+    }
+    put_record(&mut records, &propagation_form(cmd, deadline));
+    records
+}
+
+pub fn delete_command(key: &str) -> Command<'_> {
+    Command {
+        command_type: CommandType::Delete,
+        arguments: vec![key.as_bytes()],
+    }
+}
+
+/**
+ * Form in which a write is logged or sent to replicas: relative expiries become absolute
+ * deadlines so that applying the command later gives the same TTL. deadline is the one the
+ * command gave to its key, when unknown it is computed again from the current time.
+ */
+pub fn propagation_form(cmd: &Command, deadline: Option<u64>) -> Vec<u8> {
+    if let Some(idx) = relative_expire_arg(cmd) {
+        let deadline = deadline
+            .or_else(|| {
+                ExpireParameter::from(cmd.arguments[idx])
+                    .ok()
+                    .and_then(|e| e.calc_new_expire(None))
+            })
+            .unwrap_or(0);
+        let mut absolute = vec![ExpireParameter::TAG_EXPIREAT_MILLIS];
+        absolute.extend(deadline.to_le_bytes());
+        let mut args = cmd.arguments.clone();
+        args[idx] = &absolute;
+        return encode(cmd.command_type, &args);
+    }
+    if cmd.command_type == CommandType::Restore {
+        return restore_propagation_form(cmd, deadline);
+    }
+    // the key left this node, whatever the target did with it
+    if cmd.command_type == CommandType::Migrate {
+        return encode(CommandType::Delete, &cmd.arguments[2..3]);
+    }
+    let scale = match cmd.command_type {
+        CommandType::Expire => 1000,
+        CommandType::PExpire => 1,
+        _ => return encode(cmd.command_type, &cmd.arguments),
+    };
+    let deadline = match cmd
+        .arguments
+        .get(1)
+        .and_then(|t| <[u8; 8]>::try_from(*t).ok())
+    {
+        Some(t) => deadline.unwrap_or_else(|| {
+            current_millis().saturating_add(u64::from_le_bytes(t).saturating_mul(scale))
+        }),
+        None => return encode(cmd.command_type, &cmd.arguments),
+    };
+    let deadline = deadline.to_le_bytes();
+    let mut args = cmd.arguments.clone();
+    args[1] = &deadline;
+    encode(CommandType::PExpireAt, &args)
+}
+
+// index of an expire argument relative to the current time
+fn relative_expire_arg(cmd: &Command) -> Option<usize> {
+    let idx = match cmd.command_type {
+        CommandType::SetString
+        | CommandType::SetInt
+        | CommandType::SetFloat
+        | CommandType::IncrementInt
+        | CommandType::IncrementFloat => 2,
+        CommandType::ListPushEx | CommandType::HLLAddEx | CommandType::GetEx => 1,
+        _ => return None,
+    };
+    let tag = cmd.arguments.get(idx)?.first()?;
+    matches!(
+        *tag,
+        ExpireParameter::TAG_EXPIREIN_SECONDS | ExpireParameter::TAG_EXPIREIN_MILLIS
+    )
+    .then_some(idx)
+}
+
+// whether propagation_form turns a relative expiry of the first key into its deadline
+pub fn has_relative_expiry(cmd: &Command) -> bool {
+    matches!(
+        cmd.command_type,
+        CommandType::Expire | CommandType::PExpire | CommandType::Restore
+    ) || relative_expire_arg(cmd).is_some()
+}
+
+// RESTORE is logged with the deadline it got as an ABSTTL argument
+fn restore_propagation_form(cmd: &Command, restored_deadline: Option<u64>) -> Vec<u8> {
+    let flags = cmd
+        .arguments
+        .get(3)
+        .and_then(|f| f.first())
+        .copied()
+        .unwrap_or(0);
+    let ttl = match cmd.arguments.get(2) {
+        None | Some([]) => dump_ttl(cmd.arguments[1]),
+        Some(ttl) => <[u8; 8]>::try_from(*ttl)
+            .ok()
+            .map(u64::from_le_bytes)
+            .filter(|ttl| *ttl > 0),
+    };
+    let deadline = match ttl {
+        Some(at) if flags & RESTORE_ABSTTL != 0 => at,
+        Some(ttl) => restored_deadline.unwrap_or_else(|| current_millis().saturating_add(ttl)),
+        None => 0,
+    };
+    let deadline = deadline.to_le_bytes();
+    let flags = [flags | RESTORE_ABSTTL];
+    let args: Vec<&[u8]> = vec![cmd.arguments[0], cmd.arguments[1], &deadline, &flags];
+    encode(CommandType::Restore, &args)
+}
+
+// minimal commands recreating a key, its TTL is set as an absolute deadline afterwards
+fn rewrite_entry(out: &mut Vec<u8>, key: &str, deadline: Option<u64>, data: &DataType) {
+    let key_b = key.as_bytes();
+    match data {
+        DataType::Int(i) => put_record(out, &encode(CommandType::SetInt, &[key_b, &i.to_bytes()])),
+        DataType::Float(f) => {
+            put_record(out, &encode(CommandType::SetFloat, &[key_b, &f.to_bytes()]))
+        }
+        // APPEND on a missing key stores any bytes, SETS only accepts utf8
+        DataType::String(s) => put_record(out, &encode(CommandType::Append, &[key_b, s.get()])),
+        // LPUSH needs an item, an emptied list is restored from its dump
+        DataType::List(l) if l.get().is_empty() => {
+            let dump = encode_dump(data, None);
+            put_record(out, &encode(CommandType::Restore, &[key_b, &dump]));
+        }
+        DataType::List(l) => {
+            for chunk in l.get().chunks(REWRITE_LIST_BATCH) {
+                let mut args: Vec<&[u8]> = vec![key_b];
+                args.extend(chunk.iter().map(|item| item.get().as_slice()));
+                put_record(out, &encode(CommandType::ListPush, &args));
+            }
+        }
+        DataType::HLL(h) => {
+            let mut registers = vec![h.get().bucket_bits()];
+            registers.extend(h.get().registers());
+            put_record(out, &encode(CommandType::HLLLoad, &[key_b, &registers]));
+        }
+        DataType::Json(j) => {
+            let json = serde_json::to_vec(j.get()).unwrap_or_default();
+            put_record(out, &encode(CommandType::JsonSet, &[key_b, b"$", &json]));
+        }
+    }
+    if let Some(deadline) = deadline {
+        put_record(
+            out,
+            &encode(CommandType::PExpireAt, &[key_b, &deadline.to_le_bytes()]),
+        );
+    }
+}
+
+pub fn encode(command_type: CommandType, args: &[&[u8]]) -> Vec<u8> {
+    Command {
+        command_type,
+        arguments: args.to_vec(),
+    }
+    .encode()
+}
+
+fn put_record(out: &mut Vec<u8>, payload: &[u8]) {
+    out.extend((payload.len() as u32).to_le_bytes());
+    out.extend(payload);
+}
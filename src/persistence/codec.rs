@@ -0,0 +1,182 @@
+use serde_json::Value;
+
+use crate::{
+    data::HLL,
+    state::datastate::{DataType, FloatType, HLLType, IntType, JsonType, ListType, StringType},
+};
+
+/**
+ * Binary encoding of single values shared by snapshots and DUMP/RESTORE:
+ * a type tag followed by the payload, lengths and numbers are little endian.
+ */
+pub const TYPE_INT: u8 = 1;
+pub const TYPE_FLOAT: u8 = 2;
+pub const TYPE_STRING: u8 = 3;
+pub const TYPE_LIST: u8 = 4;
+pub const TYPE_HLL: u8 = 5;
+pub const TYPE_JSON: u8 = 6;
+
+pub fn encode_value(data: &DataType, out: &mut Vec<u8>) {
+    match data {
+        DataType::Int(i) => {
+            out.push(TYPE_INT);
+            out.extend(i.to_bytes());
+        }
+        DataType::Float(f) => {
+            out.push(TYPE_FLOAT);
+            out.extend(f.to_bytes());
+        }
+        DataType::String(s) => {
+            out.push(TYPE_STRING);
+            put_bytes(out, s.get());
+        }
+        DataType::List(l) => {
+            out.push(TYPE_LIST);
+            out.extend((l.get().len() as u32).to_le_bytes());
+            for item in l.get() {
+                put_bytes(out, item.get());
+            }
+        }
+        DataType::HLL(h) => {
+            out.push(TYPE_HLL);
+            out.push(h.get().bucket_bits());
+            out.extend(h.get().registers());
+        }
+        DataType::Json(j) => {
+            out.push(TYPE_JSON);
+            put_bytes(out, &serde_json::to_vec(j.get()).unwrap_or_default());
+        }
+    }
+}
+
+pub fn decode_value(reader: &mut Reader) -> Result<DataType, String> {
+    match reader.u8()? {
+        TYPE_INT => Ok(DataType::Int(IntType::new(i32::from_le_bytes(
+            reader.array()?,
+        )))),
+        TYPE_FLOAT => Ok(DataType::Float(FloatType::new(f32::from_le_bytes(
+            reader.array()?,
+        )))),
+        TYPE_STRING => Ok(DataType::String(StringType::from_bytes(
+            reader.sized()?.to_vec(),
+        ))),
+        TYPE_LIST => {
+            let count = reader.u32()? as usize;
+            // the count is not trusted for the allocation, every item needs at least 4 bytes
+            let mut items = Vec::with_capacity(count.min(reader.remaining() / 4));
+            for _ in 0..count {
+                items.push(StringType::from_bytes(reader.sized()?.to_vec()));
+            }
+            Ok(DataType::List(ListType::from_items(items)))
+        }
+        TYPE_HLL => {
+            let bits = reader.u8()?;
+            if !(1..=15).contains(&bits) {
+                return Err("Invalid HLL registers".to_owned());
+            }
+            let registers = reader.bytes(1usize << bits)?.to_vec();
+            Ok(DataType::HLL(HLLType::new_from_hll(HLL::from_registers(
+                bits, registers,
+            )?)))
+        }
+        TYPE_JSON => {
+            let value: Value = serde_json::from_slice(reader.sized()?)
+                .map_err(|e| format!("Invalid JSON value: {}", e))?;
+            Ok(DataType::Json(JsonType::new(value)))
+        }
+        t => Err(format!("Unknown value type {}", t)),
+    }
+}
+
+/**
+ * DUMP payload: u16 version, the encoded value, u64 remaining TTL in milliseconds (0 without TTL)
+ * and the crc32 of the previous bytes. The TTL is relative so clocks of the two servers don't
+ * need to agree.
+ */
+pub const DUMP_VERSION: u16 = 1;
+// RESTORE flags byte
+pub const RESTORE_REPLACE: u8 = 1;
+pub const RESTORE_ABSTTL: u8 = 2;
+
+pub fn encode_dump(data: &DataType, ttl: Option<u64>) -> Vec<u8> {
+    let mut out = DUMP_VERSION.to_le_bytes().to_vec();
+    encode_value(data, &mut out);
+    out.extend(ttl.unwrap_or(0).to_le_bytes());
+    out.extend(crc32fast::hash(&out).to_le_bytes());
+    out
+}
+
+pub fn decode_dump(bytes: &[u8]) -> Result<(DataType, Option<u64>), String> {
+    let invalid = || "DUMP payload version or checksum are wrong".to_owned();
+    if bytes.len() < 4 {
+        return Err(invalid());
+    }
+    let (payload, crc) = bytes.split_at(bytes.len() - 4);
+    if crc32fast::hash(payload).to_le_bytes() != crc {
+        return Err(invalid());
+    }
+    let mut reader = Reader::new(payload);
+    if u16::from_le_bytes(reader.array()?) > DUMP_VERSION {
+        return Err(invalid());
+    }
+    let data = decode_value(&mut reader)?;
+    let ttl = reader.u64()?;
+    if reader.remaining() != 0 {
+        return Err(invalid());
+    }
+    Ok((data, (ttl > 0).then_some(ttl)))
+}
+
+// TTL of an already validated payload without decoding the value
+pub fn dump_ttl(bytes: &[u8]) -> Option<u64> {
+    let ttl = bytes
+        .len()
+        .checked_sub(12)
+        .map(|start| &bytes[start..start + 8])?;
+    Some(u64::from_le_bytes(ttl.try_into().unwrap())).filter(|ttl| *ttl > 0)
+}
+
+pub fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend((bytes.len() as u32).to_le_bytes());
+    out.extend(bytes);
+}
+
+// bounds checked cursor over encoded data, running past the end is reported as truncation
+pub struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.remaining() < len {
+            return Err("Truncated data".to_owned());
+        }
+        let bytes = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+    pub fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+    pub fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+    pub fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+    // u32 length prefixed bytes
+    pub fn sized(&mut self) -> Result<&'a [u8], String> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+}
@@ -0,0 +1,157 @@
+use serde_json::{json, Map, Value};
+
+use crate::{
+    commands::commands::CommandType,
+    persistence::codec::{decode_dump, encode_dump},
+    state::datastate::{DataType, ListType},
+};
+
+/**
+ * JSON lines written and loaded by echors-dump, one line per key:
+ * {"db":0,"key":"k","type":"list","value":["a","b"],"ttl":1500}
+ * ttl is the remaining time in milliseconds or null. Strings and list items that are not valid
+ * utf8 are written as hex with "encoding":"hex", HLL values are {"bits":14,"registers":"<hex>"}.
+ */
+const LIST_BATCH: usize = 100;
+
+pub type ImportCommand = (CommandType, Vec<Vec<u8>>);
+
+// line of a DUMP payload, the caller adds db and key
+pub fn export_line(payload: &[u8]) -> Result<Map<String, Value>, String> {
+    let (data, ttl) = decode_dump(payload)?;
+    let mut line = Map::new();
+    let (type_name, value) = match &data {
+        DataType::Int(i) => ("int", json!(i.get())),
+        DataType::Float(f) => ("float", json!(f.get())),
+        DataType::String(s) => match std::str::from_utf8(s.get()) {
+            Ok(s) => ("string", json!(s)),
+            Err(_) => {
+                line.insert("encoding".to_owned(), json!("hex"));
+                ("string", json!(hex(s.get())))
+            }
+        },
+        DataType::List(l) => {
+            let items = l.get();
+            let utf8 = items.iter().all(|i| std::str::from_utf8(i.get()).is_ok());
+            if !utf8 {
+                line.insert("encoding".to_owned(), json!("hex"));
+            }
+            let items: Vec<String> = items
+                .iter()
+                .map(|i| match utf8 {
+                    true => String::from_utf8_lossy(i.get()).into_owned(),
+                    false => hex(i.get()),
+                })
+                .collect();
+            ("list", json!(items))
+        }
+        DataType::HLL(h) => {
+            let hll = h.get();
+            let registers = hex(hll.registers());
+            (
+                "hll",
+                json!({"bits": hll.bucket_bits(), "registers": registers}),
+            )
+        }
+        DataType::Json(j) => ("json", j.get().clone()),
+    };
+    line.insert("type".to_owned(), json!(type_name));
+    line.insert("value".to_owned(), value);
+    line.insert("ttl".to_owned(), ttl.into());
+    Ok(line)
+}
+
+/**
+ * Commands recreating the key of a line in the selected db: the key is deleted, the value is
+ * written and the TTL is applied after it.
+ */
+pub fn import_commands(record: &Value) -> Result<Vec<ImportCommand>, String> {
+    let key = record["key"].as_str().ok_or("Missing key")?.as_bytes();
+    let value = &record["value"];
+    let hex_encoded = record["encoding"].as_str() == Some("hex");
+    let bytes = |v: &Value| -> Result<Vec<u8>, String> {
+        let s = v.as_str().ok_or("Expected a string value")?;
+        match hex_encoded {
+            true => unhex(s),
+            false => Ok(s.as_bytes().to_vec()),
+        }
+    };
+    let mut commands = vec![(CommandType::Delete, vec![key.to_vec()])];
+    match record["type"].as_str().ok_or("Missing type")? {
+        "int" => {
+            let i = value
+                .as_i64()
+                .and_then(|i| i32::try_from(i).ok())
+                .ok_or("Invalid int")?;
+            commands.push((
+                CommandType::SetInt,
+                vec![key.to_vec(), i.to_le_bytes().to_vec()],
+            ));
+        }
+        "float" => {
+            let f = value.as_f64().ok_or("Invalid float")? as f32;
+            commands.push((
+                CommandType::SetFloat,
+                vec![key.to_vec(), f.to_le_bytes().to_vec()],
+            ));
+        }
+        "string" if !hex_encoded => {
+            commands.push((CommandType::SetString, vec![key.to_vec(), bytes(value)?]))
+        }
+        // SETS only takes utf8, APPEND on a missing key stores any bytes
+        "string" => commands.push((CommandType::Append, vec![key.to_vec(), bytes(value)?])),
+        "list" => {
+            let items = value.as_array().ok_or("Invalid list")?;
+            // LPUSH needs an item, an emptied list is restored from its dump
+            if items.is_empty() {
+                let empty = DataType::List(ListType::from_items(Vec::new()));
+                commands.push((
+                    CommandType::Restore,
+                    vec![key.to_vec(), encode_dump(&empty, None)],
+                ));
+            }
+            for chunk in items.chunks(LIST_BATCH) {
+                let mut args = vec![key.to_vec()];
+                for item in chunk {
+                    args.push(bytes(item)?);
+                }
+                commands.push((CommandType::ListPush, args));
+            }
+        }
+        "hll" => {
+            let bits = value["bits"].as_u64().ok_or("Invalid HLL bits")? as u8;
+            let mut registers = vec![bits];
+            registers.extend(unhex(value["registers"].as_str().ok_or("Invalid HLL")?)?);
+            commands.push((CommandType::HLLLoad, vec![key.to_vec(), registers]));
+        }
+        "json" => {
+            let json = serde_json::to_vec(value).map_err(|e| e.to_string())?;
+            commands.push((
+                CommandType::JsonSet,
+                vec![key.to_vec(), b"$".to_vec(), json],
+            ));
+        }
+        t => return Err(format!("Unknown type {}", t)),
+    }
+    if let Some(ttl) = record["ttl"].as_u64() {
+        commands.push((
+            CommandType::PExpire,
+            vec![key.to_vec(), ttl.to_le_bytes().to_vec()],
+        ));
+    }
+    Ok(commands)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(s: &str) -> Result<Vec<u8>, String> {
+    if s.len() % 2 == 1 || !s.is_ascii() {
+        return Err("Invalid hex value".to_owned());
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| "Invalid hex value".to_owned()))
+        .collect()
+}
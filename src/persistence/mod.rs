@@ -0,0 +1,4 @@
+pub mod aof;
+pub mod codec;
+pub mod jsonlines;
+pub mod snapshot;
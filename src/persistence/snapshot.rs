@@ -0,0 +1,254 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Error, ErrorKind, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use log::{info, warn};
+
+use crate::state::{
+    datastore::DataStore,
+    expires::{current_millis, ExpireParameter},
+};
+
+use super::codec::{decode_value, encode_value, put_bytes, Reader};
+
+/**
+ * Snapshot file layout: magic, u16 version, then for every non empty database OP_DB with its
+ * u32 index followed by OP_ENTRY records ([u64 deadline, 0 without TTL][key][value]),
+ * closed by OP_EOF and the crc32 of all the previous bytes.
+ */
+const MAGIC: &[u8; 8] = b"ECHORSDB";
+const VERSION: u16 = 1;
+const OP_DB: u8 = 0xFE;
+const OP_ENTRY: u8 = 0xFD;
+const OP_EOF: u8 = 0xFF;
+
+#[derive(Debug)]
+pub struct Snapshotter {
+    path: PathBuf,
+    in_progress: AtomicBool,
+    // unix seconds of the last successful save
+    last_save: AtomicU64,
+}
+
+impl Snapshotter {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            in_progress: AtomicBool::new(false),
+            last_save: AtomicU64::new(current_millis() / 1000),
+        }
+    }
+    pub fn last_save(&self) -> u64 {
+        self.last_save.load(Ordering::Relaxed)
+    }
+    pub fn in_progress(&self) -> bool {
+        self.in_progress.load(Ordering::Relaxed)
+    }
+    // only one save runs at a time, whether started by SAVE, BGSAVE or the save rules
+    fn begin(&self) -> Result<(), String> {
+        self.in_progress
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed)
+            .map(|_| ())
+            .map_err(|_| "A save is already in progress".to_owned())
+    }
+    pub fn save(&self, store: &DataStore) -> Result<(), String> {
+        self.begin()?;
+        let result = self.write(store);
+        self.in_progress.store(false, Ordering::Release);
+        result
+    }
+    pub fn save_in_background(self: &Arc<Self>, store: &Arc<DataStore>) -> Result<(), String> {
+        self.begin()?;
+        let (snapshotter, store) = (self.clone(), store.clone());
+        std::thread::spawn(move || {
+            if let Err(e) = snapshotter.write(&store) {
+                warn!("Background save failed: {}", e);
+            }
+            snapshotter.in_progress.store(false, Ordering::Release);
+        });
+        Ok(())
+    }
+    // written to a temporary file first so a crash never leaves a partial snapshot in place
+    fn write(&self, store: &DataStore) -> Result<(), String> {
+        let start = Instant::now();
+        let changes = store.changes();
+        let tmp_path = self.path.with_extension("tmp");
+        let writer = loop {
+            let file = File::create(&tmp_path).map_err(|e| e.to_string())?;
+            let mut writer = CrcWriter::new(BufWriter::new(file));
+            match write_snapshot(store, &mut writer) {
+                Err(e) if e.kind() == ErrorKind::Interrupted => info!("{}, restarting", e),
+                result => break result.map(|_| writer).map_err(|e| e.to_string())?,
+            }
+        };
+        let file = writer
+            .finish()
+            .and_then(|w| w.into_inner().map_err(|e| e.into_error()))
+            .map_err(|e| e.to_string())?;
+        file.sync_all().map_err(|e| e.to_string())?;
+        fs::rename(&tmp_path, &self.path).map_err(|e| e.to_string())?;
+        store.forget_changes(changes);
+        self.last_save
+            .store(current_millis() / 1000, Ordering::Relaxed);
+        info!(
+            "Snapshot saved to {:?} in {}ms",
+            self.path,
+            start.elapsed().as_millis()
+        );
+        Ok(())
+    }
+    // a missing file means an empty dataset, a damaged one is an error
+    pub fn load(&self, store: &DataStore) -> Result<usize, String> {
+        let bytes = match fs::read(&self.path) {
+            Ok(b) => b,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.to_string()),
+        };
+        load_snapshot(&bytes, store)
+    }
+}
+
+pub fn write_snapshot(store: &DataStore, out: &mut impl Write) -> std::io::Result<()> {
+    out.write_all(MAGIC)?;
+    out.write_all(&VERSION.to_le_bytes())?;
+    let mut buf: Vec<u8> = Vec::new();
+    for (index, db) in store.dbs().iter().enumerate() {
+        let (shard_count, generation) = {
+            let state = db.read();
+            if state.is_empty() {
+                continue;
+            }
+            (state.shard_count(), state.generation())
+        };
+        out.write_all(&[OP_DB])?;
+        out.write_all(&(index as u32).to_le_bytes())?;
+        for shard in 0..shard_count {
+            // the database lock is only held while copying the shard, a writer queued behind
+            // it would otherwise stop every command on the database until the write is done.
+            // A SWAPDB or flush in between would mix two datasets, the save starts over
+            let entries = {
+                let state = db.read();
+                if state.generation() != generation {
+                    return Err(Error::new(
+                        ErrorKind::Interrupted,
+                        "database swapped or flushed during the save",
+                    ));
+                }
+                state.copy_shard(shard)
+            };
+            for (key, deadline, data) in entries {
+                buf.clear();
+                buf.push(OP_ENTRY);
+                buf.extend(deadline.unwrap_or(0).to_le_bytes());
+                put_bytes(&mut buf, key.as_bytes());
+                encode_value(&data, &mut buf);
+                out.write_all(&buf)?;
+            }
+        }
+    }
+    out.write_all(&[OP_EOF])
+}
+
+// whole snapshot with its checksum in memory, sent to replicas doing a full sync
+pub fn snapshot_bytes(store: &DataStore) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::new();
+    while let Err(e) = write_snapshot(store, &mut bytes) {
+        // writing to memory only fails when the save has to start over
+        assert_eq!(e.kind(), ErrorKind::Interrupted);
+        bytes.clear();
+    }
+    bytes.extend(crc32fast::hash(&bytes).to_le_bytes());
+    bytes
+}
+
+// returns the number of keys loaded, entries whose deadline passed are skipped
+pub fn load_snapshot(bytes: &[u8], store: &DataStore) -> Result<usize, String> {
+    if bytes.len() < MAGIC.len() + 2 + 1 + 4 || &bytes[..MAGIC.len()] != MAGIC {
+        return Err("Not a snapshot file".to_owned());
+    }
+    let (content, crc) = bytes.split_at(bytes.len() - 4);
+    if crc32fast::hash(content).to_le_bytes() != crc {
+        return Err("Snapshot checksum mismatch".to_owned());
+    }
+    let mut reader = Reader::new(&content[MAGIC.len()..]);
+    let version = u16::from_le_bytes(reader.array()?);
+    if version != VERSION {
+        return Err(format!("Unsupported snapshot version {}", version));
+    }
+    let now = current_millis();
+    let mut db: Option<usize> = None;
+    let mut loaded = 0;
+    loop {
+        match reader.u8()? {
+            OP_DB => {
+                let index = reader.u32()? as usize;
+                if index >= store.dbs().len() {
+                    return Err(format!(
+                        "Snapshot contains database {} but only {} are configured",
+                        index,
+                        store.dbs().len()
+                    ));
+                }
+                db = Some(index);
+            }
+            OP_ENTRY => {
+                let index = db.ok_or("Snapshot entry outside of a database")?;
+                let deadline = reader.u64()?;
+                let key = std::str::from_utf8(reader.sized()?)
+                    .map_err(|_| "Invalid utf8 key in snapshot")?;
+                let data = decode_value(&mut reader)?;
+                if deadline != 0 && deadline <= now {
+                    continue;
+                }
+                let expire = match deadline {
+                    0 => ExpireParameter::None,
+                    d => ExpireParameter::EXPIREAT(d),
+                };
+                let _ = store.db(index).read().set(key, data, expire);
+                loaded += 1;
+            }
+            OP_EOF => break,
+            op => return Err(format!("Unknown snapshot record {:#x}", op)),
+        }
+    }
+    Ok(loaded)
+}
+
+struct CrcWriter<W: Write> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+}
+
+impl<W: Write> CrcWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+        }
+    }
+    // appends the checksum and hands back the inner writer
+    fn finish(mut self) -> std::io::Result<W> {
+        let crc = self.hasher.clone().finalize();
+        self.inner.write_all(&crc.to_le_bytes())?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for CrcWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
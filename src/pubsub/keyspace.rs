@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use log::warn;
+
+use super::registry::PubSub;
+
+// groups of events selected by the letters of notify_keyspace_events
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventClass {
+    // del, expire, persist, rename_from, rename_to, copy_to, move_from, restore
+    Generic,
+    // set, incrby, incrbyfloat, append, setrange on strings and numbers
+    String,
+    List,
+    HyperLogLog,
+    Json,
+    Expired,
+    Evicted,
+}
+
+impl EventClass {
+    fn flag(&self) -> char {
+        match self {
+            EventClass::Generic => 'g',
+            EventClass::String => '$',
+            EventClass::List => 'l',
+            EventClass::HyperLogLog => 'h',
+            EventClass::Json => 'j',
+            EventClass::Expired => 'x',
+            EventClass::Evicted => 'e',
+        }
+    }
+}
+
+const ALL_CLASSES: &str = "g$lhjxe";
+
+/**
+ * Publishes changes to keys, when enabled by notify_keyspace_events. K sends the event name
+ * on __keyspace@<db>__:<key>, E sends the key on __keyevent@<db>__:<event>, the other letters
+ * select event classes as in EventClass::flag and A stands for all of them.
+ */
+#[derive(Debug)]
+pub struct KeyspaceEvents {
+    pubsub: Arc<PubSub>,
+    keyspace: bool,
+    keyevent: bool,
+    classes: String,
+}
+
+impl KeyspaceEvents {
+    pub fn new(pubsub: Arc<PubSub>, flags: &str) -> Self {
+        let mut classes = String::new();
+        for flag in flags.chars() {
+            match flag {
+                'K' | 'E' => {}
+                'A' => classes.push_str(ALL_CLASSES),
+                flag if ALL_CLASSES.contains(flag) => classes.push(flag),
+                flag => warn!("Unknown keyspace event class {}", flag),
+            }
+        }
+        Self {
+            pubsub,
+            keyspace: flags.contains('K'),
+            keyevent: flags.contains('E'),
+            classes,
+        }
+    }
+    pub fn enabled(&self, class: EventClass) -> bool {
+        (self.keyspace || self.keyevent) && self.classes.contains(class.flag())
+    }
+    // any event would be published, lets callers skip collecting them
+    pub fn active(&self) -> bool {
+        (self.keyspace || self.keyevent) && !self.classes.is_empty()
+    }
+    pub fn notify(&self, class: EventClass, event: &str, db: usize, key: &[u8]) {
+        if !self.enabled(class) {
+            return;
+        }
+        if self.keyspace {
+            let mut channel = format!("__keyspace@{}__:", db).into_bytes();
+            channel.extend(key);
+            self.pubsub.publish(&channel, event.as_bytes());
+        }
+        if self.keyevent {
+            let channel = format!("__keyevent@{}__:{}", db, event);
+            self.pubsub.publish(channel.as_bytes(), key);
+        }
+    }
+}
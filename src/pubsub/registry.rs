@@ -0,0 +1,221 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use parking_lot::RwLock;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::{
+    data::glob_match,
+    state::datastate::{srlz_array, StringType},
+    CommandResult,
+};
+
+static NEXT_SUBSCRIPTION_ID: AtomicU64 = AtomicU64::new(1);
+
+// sent to a subscribed connection, which writes it between the replies to its commands
+#[derive(Debug)]
+pub enum Push {
+    Message(Arc<[u8]>),
+    // the connection fell behind by more than the output buffer limit
+    Disconnect,
+}
+
+// sending side of a subscribed connection, shared by all its channels and patterns
+#[derive(Debug)]
+struct Mailbox {
+    sender: UnboundedSender<Push>,
+    // bytes sent and not written to the socket yet
+    pending: AtomicUsize,
+    overflowed: AtomicBool,
+}
+
+impl Mailbox {
+    fn deliver(&self, message: &Arc<[u8]>, limit: usize) {
+        if self.overflowed.load(Ordering::Relaxed) {
+            return;
+        }
+        let pending = self.pending.fetch_add(message.len(), Ordering::Relaxed) + message.len();
+        if limit > 0 && pending > limit {
+            if !self.overflowed.swap(true, Ordering::Relaxed) {
+                let _ = self.sender.send(Push::Disconnect);
+            }
+            return;
+        }
+        let _ = self.sender.send(Push::Message(message.clone()));
+    }
+}
+
+/**
+ * Subscriptions of one connection, kept in its ConnectionState while it is subscribed to at
+ * least one channel or pattern.
+ */
+#[derive(Debug)]
+pub struct Subscription {
+    id: u64,
+    mailbox: Arc<Mailbox>,
+    receiver: UnboundedReceiver<Push>,
+    pub channels: HashSet<Vec<u8>>,
+    pub patterns: HashSet<Vec<u8>>,
+}
+
+impl Subscription {
+    pub fn count(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+    // next push to write, None once the connection has to be closed
+    pub async fn next_push(&mut self) -> Option<Arc<[u8]>> {
+        match self.receiver.recv().await? {
+            Push::Message(message) => {
+                self.mailbox
+                    .pending
+                    .fetch_sub(message.len(), Ordering::Relaxed);
+                Some(message)
+            }
+            Push::Disconnect => None,
+        }
+    }
+}
+
+type Subscribers = HashMap<Vec<u8>, HashMap<u64, Arc<Mailbox>>>;
+
+/**
+ * Channels and patterns of all connections. Messages are pushed to the connections through
+ * unbounded channels, the output buffer limit disconnects subscribers that do not read them.
+ */
+#[derive(Debug)]
+pub struct PubSub {
+    output_buffer_limit: usize,
+    channels: RwLock<Subscribers>,
+    patterns: RwLock<Subscribers>,
+}
+
+impl PubSub {
+    pub fn new(output_buffer_limit: usize) -> Self {
+        Self {
+            output_buffer_limit,
+            channels: RwLock::new(HashMap::new()),
+            patterns: RwLock::new(HashMap::new()),
+        }
+    }
+    pub fn subscription(&self) -> Subscription {
+        let (sender, receiver) = unbounded_channel();
+        Subscription {
+            id: NEXT_SUBSCRIPTION_ID.fetch_add(1, Ordering::Relaxed),
+            mailbox: Arc::new(Mailbox {
+                sender,
+                pending: AtomicUsize::new(0),
+                overflowed: AtomicBool::new(false),
+            }),
+            receiver,
+            channels: HashSet::new(),
+            patterns: HashSet::new(),
+        }
+    }
+    // pattern tells whether the name is a glob pattern or a channel
+    pub fn subscribe(&self, subscription: &mut Subscription, name: &[u8], pattern: bool) {
+        let (id, mailbox) = (subscription.id, subscription.mailbox.clone());
+        let (registry, names) = self.registry(subscription, pattern);
+        if names.insert(name.to_vec()) {
+            registry
+                .write()
+                .entry(name.to_vec())
+                .or_default()
+                .insert(id, mailbox);
+        }
+    }
+    pub fn unsubscribe(&self, subscription: &mut Subscription, name: &[u8], pattern: bool) {
+        let id = subscription.id;
+        let (registry, names) = self.registry(subscription, pattern);
+        if names.remove(name) {
+            let mut registry = registry.write();
+            if let Some(subscribers) = registry.get_mut(name) {
+                subscribers.remove(&id);
+                if subscribers.is_empty() {
+                    registry.remove(name);
+                }
+            }
+        }
+    }
+    // drops every subscription of a connection, when it closes
+    pub fn remove(&self, mut subscription: Subscription) {
+        for pattern in [false, true] {
+            let (_, names) = self.registry(&mut subscription, pattern);
+            let names: Vec<Vec<u8>> = names.iter().cloned().collect();
+            for name in names {
+                self.unsubscribe(&mut subscription, &name, pattern);
+            }
+        }
+    }
+    fn registry<'a>(
+        &'a self,
+        subscription: &'a mut Subscription,
+        pattern: bool,
+    ) -> (&'a RwLock<Subscribers>, &'a mut HashSet<Vec<u8>>) {
+        match pattern {
+            true => (&self.patterns, &mut subscription.patterns),
+            false => (&self.channels, &mut subscription.channels),
+        }
+    }
+
+    /**
+     * Pushes the message to the subscribers of the channel as ["message", channel, message]
+     * and to those of matching patterns as ["pmessage", pattern, channel, message]. Returns
+     * the number of deliveries.
+     */
+    pub fn publish(&self, channel: &[u8], message: &[u8]) -> u64 {
+        let string = |bytes: &[u8]| StringType::from_bytes(bytes.to_vec()).serialize();
+        let mut delivered = 0;
+        if let Some(subscribers) = self.channels.read().get(channel) {
+            let push: Arc<[u8]> =
+                srlz_push(vec![string(b"message"), string(channel), string(message)]).into();
+            for mailbox in subscribers.values() {
+                mailbox.deliver(&push, self.output_buffer_limit);
+            }
+            delivered += subscribers.len() as u64;
+        }
+        for (pattern, subscribers) in self.patterns.read().iter() {
+            if !glob_match(pattern, channel) {
+                continue;
+            }
+            let push: Arc<[u8]> = srlz_push(vec![
+                string(b"pmessage"),
+                string(pattern),
+                string(channel),
+                string(message),
+            ])
+            .into();
+            for mailbox in subscribers.values() {
+                mailbox.deliver(&push, self.output_buffer_limit);
+            }
+            delivered += subscribers.len() as u64;
+        }
+        delivered
+    }
+    // channels with at least one subscriber, all of them or those matching the pattern
+    pub fn active_channels(&self, pattern: Option<&[u8]>) -> Vec<Vec<u8>> {
+        let mut channels: Vec<Vec<u8>> = self
+            .channels
+            .read()
+            .keys()
+            .filter(|channel| pattern.is_none_or(|pattern| glob_match(pattern, channel)))
+            .cloned()
+            .collect();
+        channels.sort();
+        channels
+    }
+    // subscribers of the channel itself, pattern subscriptions are not counted
+    pub fn subscriber_count(&self, channel: &[u8]) -> usize {
+        self.channels.read().get(channel).map_or(0, HashMap::len)
+    }
+}
+
+fn srlz_push(items: Vec<Vec<u8>>) -> Vec<u8> {
+    let mut push = vec![CommandResult::PUSH as u8];
+    push.extend(srlz_array(items));
+    push
+}
@@ -0,0 +1,275 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use parking_lot::{Mutex, MutexGuard, RwLock, RwLockReadGuard};
+use tokio::{sync::watch, task::JoinHandle};
+
+use crate::{
+    commands::commands::Command,
+    persistence::{
+        aof::{delete_command, propagation_records},
+        snapshot::{load_snapshot, snapshot_bytes},
+    },
+    state::{datastore::DataStore, serverstate::ServerState},
+};
+
+use super::replica;
+
+/**
+ * Replication state of a server, primary and replica sides.
+ * Writes are appended to the backlog in the AOF record format, replicas read it from the offset
+ * they reached. A replica that reconnects with the same history id and an offset still in the
+ * backlog continues from there, any other replica gets a snapshot first.
+ */
+#[derive(Debug)]
+pub struct Replication {
+    backlog_size: usize,
+    // false until the first replica attaches, writes skip the backlog until then
+    feeding: RwLock<bool>,
+    backlog: Mutex<Backlog>,
+    // end offset of the backlog, feeds of the replicas wait for it to move
+    offset_tx: watch::Sender<u64>,
+    connected_replicas: AtomicUsize,
+    link: Mutex<Option<ReplicaLink>>,
+}
+
+#[derive(Debug)]
+struct Backlog {
+    // history id, a new one is generated when the dataset is replaced by a full sync
+    id: String,
+    buf: VecDeque<u8>,
+    // offset of the first byte kept in buf
+    start: u64,
+    db: Option<usize>,
+}
+
+impl Backlog {
+    fn end(&self) -> u64 {
+        self.start + self.buf.len() as u64
+    }
+}
+
+#[derive(Debug)]
+struct ReplicaLink {
+    primary: String,
+    task: JoinHandle<()>,
+    state: Arc<LinkState>,
+}
+
+// position of a replica in the history of its primary, kept across reconnections
+#[derive(Debug)]
+pub struct LinkState {
+    pub up: AtomicBool,
+    pub primary_id: Mutex<String>,
+    pub primary_offset: AtomicU64,
+    // database selected by the replicated stream
+    pub db: AtomicUsize,
+}
+
+pub enum Attach {
+    Continue {
+        id: String,
+        offset: u64,
+    },
+    Full {
+        id: String,
+        offset: u64,
+        snapshot: Vec<u8>,
+    },
+}
+
+/**
+ * Held while a write command executes so that the backlog order is the execution order.
+ */
+pub struct FeedGuard<'a> {
+    replication: &'a Replication,
+    _gate: RwLockReadGuard<'a, bool>,
+    backlog: Option<MutexGuard<'a, Backlog>>,
+}
+
+impl FeedGuard<'_> {
+    // deadline is the expiry of the key once the command ran, see propagation_form
+    pub fn append(&mut self, db: usize, cmd: &Command, deadline: Option<u64>) {
+        let backlog = match self.backlog.as_mut() {
+            Some(b) => b,
+            None => return,
+        };
+        let records = propagation_records(&mut backlog.db, db, cmd, deadline);
+        backlog.buf.extend(records);
+        let excess = backlog
+            .buf
+            .len()
+            .saturating_sub(self.replication.backlog_size);
+        if excess > 0 {
+            backlog.buf.drain(..excess);
+            backlog.start += excess as u64;
+        }
+        self.replication.offset_tx.send_replace(backlog.end());
+    }
+    pub fn append_evicted(&mut self, evicted: &[(usize, String)]) {
+        for (db, key) in evicted {
+            self.append(*db, &delete_command(key), None);
+        }
+    }
+}
+
+impl Replication {
+    pub fn new(backlog_size: usize) -> Self {
+        Self {
+            backlog_size: backlog_size.max(1),
+            feeding: RwLock::new(false),
+            backlog: Mutex::new(Backlog {
+                id: new_id(),
+                buf: VecDeque::new(),
+                start: 0,
+                db: None,
+            }),
+            offset_tx: watch::channel(0).0,
+            connected_replicas: AtomicUsize::new(0),
+            link: Mutex::new(None),
+        }
+    }
+    pub fn feed(&self) -> FeedGuard<'_> {
+        let gate = self.feeding.read();
+        let backlog = match *gate {
+            true => Some(self.backlog.lock()),
+            false => None,
+        };
+        FeedGuard {
+            replication: self,
+            _gate: gate,
+            backlog,
+        }
+    }
+    /**
+     * Decides between partial and full resync for a replica at (id, offset). The snapshot of a
+     * full resync is taken with the backlog locked, writes are paused until it is in memory.
+     */
+    pub fn attach(&self, store: &DataStore, id: &str, offset: u64) -> Attach {
+        // waits for the writes that skipped the backlog, every later one goes through it
+        *self.feeding.write() = true;
+        let mut backlog = self.backlog.lock();
+        if id == backlog.id && (backlog.start..=backlog.end()).contains(&offset) {
+            return Attach::Continue {
+                id: backlog.id.clone(),
+                offset,
+            };
+        }
+        let snapshot = snapshot_bytes(store);
+        // the new replica does not know the selected database
+        backlog.db = None;
+        Attach::Full {
+            id: backlog.id.clone(),
+            offset: backlog.end(),
+            snapshot,
+        }
+    }
+    // bytes from offset, None when they left the backlog or belong to another history
+    pub fn read(&self, id: &str, offset: u64, max: usize) -> Option<Vec<u8>> {
+        let backlog = self.backlog.lock();
+        if id != backlog.id || offset < backlog.start || offset > backlog.end() {
+            return None;
+        }
+        let from = (offset - backlog.start) as usize;
+        let to = backlog.buf.len().min(from + max);
+        Some(backlog.buf.range(from..to).copied().collect())
+    }
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.offset_tx.subscribe()
+    }
+    /**
+     * Replaces the dataset with the snapshot of the primary. The history of this server
+     * restarts with a new id so its own replicas resync as well.
+     */
+    pub fn replace_dataset(&self, store: &DataStore, snapshot: &[u8]) -> Result<usize, String> {
+        let _gate = self.feeding.write();
+        let mut backlog = self.backlog.lock();
+        backlog.id = new_id();
+        backlog.start = backlog.end();
+        backlog.buf.clear();
+        backlog.db = None;
+        store.flush_all();
+        load_snapshot(snapshot, store)
+    }
+    pub fn replica_connected(&self) {
+        self.connected_replicas.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn replica_disconnected(&self) {
+        self.connected_replicas.fetch_sub(1, Ordering::Relaxed);
+    }
+    pub fn is_replica(&self) -> bool {
+        self.link.lock().is_some()
+    }
+    /**
+     * Starts replicating from primary ("host:port"), or stops replicating with None.
+     * The dataset is kept when replication stops.
+     */
+    pub fn set_primary(
+        &self,
+        primary: Option<String>,
+        store: &Arc<DataStore>,
+        server_state: &Arc<RwLock<ServerState>>,
+    ) {
+        let mut link = self.link.lock();
+        if let Some(old) = link.take() {
+            old.task.abort();
+        }
+        if let Some(primary) = primary {
+            let state = Arc::new(LinkState {
+                up: AtomicBool::new(false),
+                primary_id: Mutex::new("?".to_owned()),
+                primary_offset: AtomicU64::new(0),
+                db: AtomicUsize::new(0),
+            });
+            let task = tokio::spawn(replica::run(
+                primary.clone(),
+                store.clone(),
+                server_state.clone(),
+                state.clone(),
+            ));
+            *link = Some(ReplicaLink {
+                primary,
+                task,
+                state,
+            });
+        }
+    }
+    pub fn info(&self) -> String {
+        let (id, offset, start) = {
+            let backlog = self.backlog.lock();
+            (backlog.id.clone(), backlog.end(), backlog.start)
+        };
+        let mut info = format!(
+            "\nrole: {}\nreplication_id: {}\nreplication_offset: {}\nbacklog_first_offset: {}\nconnected_replicas: {}",
+            match self.is_replica() {
+                true => "replica",
+                false => "primary",
+            },
+            id,
+            offset,
+            start,
+            self.connected_replicas.load(Ordering::Relaxed)
+        );
+        if let Some(link) = self.link.lock().as_ref() {
+            info.push_str(&format!(
+                "\nprimary: {}\nprimary_link_status: {}\nprimary_offset: {}",
+                link.primary,
+                match link.state.up.load(Ordering::Relaxed) {
+                    true => "up",
+                    false => "down",
+                },
+                link.state.primary_offset.load(Ordering::Relaxed)
+            ));
+        }
+        info
+    }
+}
+
+fn new_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
@@ -0,0 +1,3 @@
+pub mod backlog;
+pub mod primary;
+pub mod replica;
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use log::info;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::{
+    state::{datastate::StringType, datastore::DataStore},
+    CommandResult,
+};
+
+use super::backlog::{Attach, Replication};
+
+// bytes of backlog sent per write to a replica
+const FEED_CHUNK: usize = 64 * 1024;
+
+/**
+ * Serves a connection that sent PSYNC until it closes. The reply is a string,
+ * "FULLRESYNC <id> <offset>" followed by the u64 length of a snapshot and the snapshot,
+ * or "CONTINUE <id>", then the backlog records are streamed as they are written.
+ * Returns why the connection ended.
+ */
+pub async fn serve_replica(
+    socket: &mut TcpStream,
+    store: Arc<DataStore>,
+    replication: Arc<Replication>,
+    id: String,
+    offset: u64,
+) -> String {
+    // subscribed before the position is taken so no write is missed
+    let mut offsets = replication.subscribe();
+    let attach = {
+        let replication = replication.clone();
+        tokio::task::spawn_blocking(move || replication.attach(&store, &id, offset)).await
+    };
+    let (header, snapshot, id, mut position) = match attach {
+        Ok(Attach::Continue { id, offset }) => (format!("CONTINUE {}", id), None, id, offset),
+        Ok(Attach::Full {
+            id,
+            offset,
+            snapshot,
+        }) => (
+            format!("FULLRESYNC {} {}", id, offset),
+            Some(snapshot),
+            id,
+            offset,
+        ),
+        Err(e) => return format!("replica sync failed: {}", e),
+    };
+    info!("Replica sync: {}", header);
+    let mut reply: Vec<u8> = vec![CommandResult::OK as u8];
+    reply.extend(StringType::new(header).serialize());
+    if let Some(snapshot) = snapshot {
+        reply.extend((snapshot.len() as u64).to_le_bytes());
+        reply.extend(snapshot);
+    }
+    if let Err(e) = socket.write_all(&reply).await {
+        return format!("failed to write to replica; err = {:?}", e);
+    }
+    replication.replica_connected();
+    let mut probe = [0u8; 64];
+    let result = loop {
+        match replication.read(&id, position, FEED_CHUNK) {
+            None => break "replica fell behind the backlog".to_owned(),
+            Some(chunk) if !chunk.is_empty() => {
+                if let Err(e) = socket.write_all(&chunk).await {
+                    break format!("failed to write to replica; err = {:?}", e);
+                }
+                position += chunk.len() as u64;
+                continue;
+            }
+            Some(_) => {}
+        }
+        // replicas send nothing, reading only notices when they go away
+        tokio::select! {
+            changed = offsets.changed() => if changed.is_err() {
+                break "replication stopped".to_owned();
+            },
+            read = socket.read(&mut probe) => if !matches!(read, Ok(n) if n > 0) {
+                break "replica disconnected".to_owned();
+            },
+        }
+    };
+    replication.replica_disconnected();
+    result
+}
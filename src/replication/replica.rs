@@ -0,0 +1,125 @@
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use log::{info, warn};
+use parking_lot::RwLock;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::{
+    commands::{
+        commands::{Command, CommandType},
+        parser::Parser,
+    },
+    persistence::aof::encode,
+    state::{connectionstate::ConnectionState, datastore::DataStore, serverstate::ServerState},
+};
+
+use super::backlog::LinkState;
+
+// delay before reconnecting to the primary after the link broke
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+// replication task of a replica, runs until REPLICAOF changes the primary
+pub async fn run(
+    primary: String,
+    store: Arc<DataStore>,
+    server_state: Arc<RwLock<ServerState>>,
+    link: Arc<LinkState>,
+) {
+    loop {
+        if let Err(e) = sync(&primary, &store, &server_state, &link).await {
+            warn!("Replication from {} interrupted: {}", primary, e);
+        }
+        link.up.store(false, Ordering::Relaxed);
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+/**
+ * Asks the primary to continue from the position reached so far, loads its snapshot when it
+ * answers with a full resync, then applies the streamed writes.
+ */
+async fn sync(
+    primary: &str,
+    store: &Arc<DataStore>,
+    server_state: &Arc<RwLock<ServerState>>,
+    link: &LinkState,
+) -> Result<(), String> {
+    let io_err = |e: std::io::Error| e.to_string();
+    let mut socket = TcpStream::connect(primary).await.map_err(io_err)?;
+    let primary_id = link.primary_id.lock().clone();
+    let offset = link.primary_offset.load(Ordering::Relaxed).to_le_bytes();
+    let psync = encode(CommandType::Psync, &[primary_id.as_bytes(), &offset]);
+    socket
+        .write_all(&(psync.len() as u32).to_le_bytes())
+        .await
+        .map_err(io_err)?;
+    socket.write_all(&psync).await.map_err(io_err)?;
+
+    let (status, data_type) = (socket.read_u8().await, socket.read_u8().await);
+    let mut header = vec![0; socket.read_u32_le().await.map_err(io_err)? as usize];
+    socket.read_exact(&mut header).await.map_err(io_err)?;
+    let header = String::from_utf8_lossy(&header).into_owned();
+    if !matches!((status, data_type), (Ok(1), Ok(3))) {
+        return Err(format!("primary refused PSYNC: {}", header));
+    }
+    let words: Vec<&str> = header.split(' ').collect();
+    match words.as_slice() {
+        ["FULLRESYNC", id, offset] => {
+            let offset: u64 = offset.parse().map_err(|_| "Invalid FULLRESYNC offset")?;
+            let mut snapshot = vec![0; socket.read_u64_le().await.map_err(io_err)? as usize];
+            socket.read_exact(&mut snapshot).await.map_err(io_err)?;
+            let replication = server_state.read().replication.clone();
+            let loaded = {
+                let store = store.clone();
+                tokio::task::spawn_blocking(move || replication.replace_dataset(&store, &snapshot))
+                    .await
+                    .map_err(|e| e.to_string())??
+            };
+            info!("Full resync from {}, {} keys loaded", primary, loaded);
+            *link.primary_id.lock() = id.to_string();
+            link.primary_offset.store(offset, Ordering::Relaxed);
+            link.db.store(0, Ordering::Relaxed);
+            // the loaded dataset is not in the log, it is rewritten from memory
+            let aof = server_state.read().aof.clone();
+            if let Some(aof) = aof {
+                if let Err(e) = aof.rewrite_in_background(store) {
+                    warn!("Could not rewrite the AOF after a full resync: {}", e);
+                }
+            }
+        }
+        ["CONTINUE", _] => info!(
+            "Partial resync from {} at offset {}",
+            primary,
+            u64::from_le_bytes(offset)
+        ),
+        _ => return Err(format!("Unexpected PSYNC reply: {}", header)),
+    }
+    link.up.store(true, Ordering::Relaxed);
+
+    let mut conn = ConnectionState {
+        db: link.db.load(Ordering::Relaxed),
+        replicated: true,
+        ..Default::default()
+    };
+    loop {
+        let len = socket.read_u32_le().await.map_err(io_err)? as usize;
+        let mut payload = vec![0; len];
+        socket.read_exact(&mut payload).await.map_err(io_err)?;
+        let cmd: Command = Parser::parse(&payload).map_err(|_| "Invalid replicated command")?;
+        // the primary only sends writes that succeeded there, a failure means the datasets
+        // differ and only a full resync brings them back together
+        if let Err(e) = cmd.apply(store, &mut conn, server_state) {
+            *link.primary_id.lock() = "?".to_owned();
+            return Err(format!("replicated {:?} failed: {}", cmd.command_type, e));
+        }
+        link.db.store(conn.db, Ordering::Relaxed);
+        link.primary_offset
+            .fetch_add(4 + len as u64, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,240 @@
+use std::{cell::RefCell, sync::Arc, time::Instant};
+
+use mlua::{HookTriggers, Lua, LuaOptions, StdLib, Table, Value, Variadic};
+use parking_lot::RwLock;
+
+use crate::{
+    commands::commands::{Command, CommandType},
+    state::{
+        connectionstate::ConnectionState,
+        datastate::{srlz_array, srlz_nil, DataTypeByte, StringType},
+        datastore::DataStore,
+        serverstate::ServerState,
+    },
+};
+
+use super::scripts::Scripts;
+
+// VM instructions between two checks of the time limit and of SCRIPT KILL
+const HOOK_INSTRUCTIONS: u32 = 10_000;
+
+/**
+ * Runs a script in a fresh VM without io, os or debug libraries. KEYS and ARGV hold the
+ * arguments as strings, echors.call(name, ...) runs a command by its CommandType name and
+ * raises its errors while echors.pcall returns them as {err = message}. Command arguments are
+ * sent as is, numbers are encoded with string.pack like the native protocol expects them.
+ * The caller holds the transaction lock and the RunningScript guard of the script.
+ */
+pub fn run_script(
+    source: &str,
+    keys: &[&[u8]],
+    args: &[&[u8]],
+    store: &Arc<DataStore>,
+    conn: &ConnectionState,
+    server_state_rwl: &Arc<RwLock<ServerState>>,
+    scripts: &Arc<Scripts>,
+) -> Result<Option<Vec<u8>>, String> {
+    let libs = StdLib::TABLE | StdLib::STRING | StdLib::MATH | StdLib::UTF8;
+    let lua = Lua::new_with(libs, LuaOptions::default()).map_err(script_error)?;
+    let started = Instant::now();
+    {
+        let (store, scripts) = (store.clone(), scripts.clone());
+        let triggers = HookTriggers::new().every_nth_instruction(HOOK_INSTRUCTIONS);
+        lua.set_hook(triggers, move |_, _| {
+            if started.elapsed() > scripts.time_limit() {
+                store.set_script_busy(true);
+            }
+            match scripts.kill_requested() {
+                true => Err(mlua::Error::runtime(
+                    "Script killed by user with SCRIPT KILL",
+                )),
+                false => Ok(()),
+            }
+        });
+    }
+    // the script selects databases for itself, the caller stays on its own
+    let script_conn = RefCell::new(ConnectionState {
+        db: conn.db,
+        replicated: conn.replicated,
+        ..Default::default()
+    });
+    let call = |args: Variadic<mlua::String>| {
+        let name = args
+            .first()
+            .ok_or_else(|| mlua::Error::runtime("Please specify at least one argument"))?;
+        let name = name.to_str()?;
+        let command_type = CommandType::from_name(name)
+            .ok_or_else(|| mlua::Error::runtime(format!("Unknown command {}", name)))?;
+        if !command_type.allowed_in_scripts() {
+            return Err(mlua::Error::runtime(format!(
+                "{} is not allowed from scripts",
+                name
+            )));
+        }
+        if command_type.is_write() {
+            scripts.record_write();
+        }
+        let cmd = Command {
+            command_type,
+            arguments: args[1..].iter().map(|a| a.as_bytes()).collect(),
+        };
+        Ok(cmd.run(store, &mut script_conn.borrow_mut(), server_state_rwl))
+    };
+    let result = lua.scope(|scope| {
+        let echors = lua.create_table()?;
+        echors.set(
+            "call",
+            scope.create_function(|lua, args| match call(args)? {
+                Ok(reply) => to_lua(lua, reply.as_deref()),
+                Err(e) => Err(mlua::Error::runtime(e)),
+            })?,
+        )?;
+        echors.set(
+            "pcall",
+            scope.create_function(|lua, args| match call(args)? {
+                Ok(reply) => to_lua(lua, reply.as_deref()),
+                Err(e) => Ok(Value::Table(lua.create_table_from([("err", e)])?)),
+            })?,
+        )?;
+        let globals = lua.globals();
+        globals.set("echors", echors)?;
+        let to_strings = |items: &[&[u8]]| {
+            let strings = items
+                .iter()
+                .map(|item| lua.create_string(item))
+                .collect::<mlua::Result<Vec<_>>>()?;
+            lua.create_sequence_from(strings)
+        };
+        globals.set("KEYS", to_strings(keys)?)?;
+        globals.set("ARGV", to_strings(args)?)?;
+        let value: Value = lua.load(source).set_name("script").eval()?;
+        Ok(from_lua(&value))
+    });
+    result.map_err(script_error)?
+}
+
+// the message of the error raised by the script or by a command it called
+fn script_error(e: mlua::Error) -> String {
+    match e {
+        mlua::Error::RuntimeError(message) => message,
+        mlua::Error::SyntaxError { message, .. } => message,
+        mlua::Error::CallbackError { cause, .. } => script_error((*cause).clone()),
+        other => other.to_string(),
+    }
+}
+
+/**
+ * Converts a typed reply to a Lua value: numbers, strings, tables for lists and arrays and
+ * false for nil so it can be stored in tables. A command without value gives "OK".
+ */
+fn to_lua<'lua>(lua: &'lua Lua, reply: Option<&[u8]>) -> mlua::Result<Value<'lua>> {
+    let Some(reply) = reply else {
+        return Ok(Value::String(lua.create_string("OK")?));
+    };
+    let malformed = || mlua::Error::runtime("Malformed command reply");
+    let take = |bytes: &[u8], from: usize, len: usize| {
+        bytes
+            .get(from..from + len)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(malformed)
+    };
+    let u32_at = |bytes: &[u8], from: usize| -> mlua::Result<usize> {
+        let b: [u8; 4] = take(bytes, from, 4)?.try_into().unwrap();
+        Ok(u32::from_le_bytes(b) as usize)
+    };
+    let Some((type_byte, _)) = reply.split_first() else {
+        return Err(malformed());
+    };
+    let value = match *type_byte {
+        t if t == DataTypeByte::Integer as u8 => {
+            let b: [u8; 4] = take(reply, 1, 4)?.try_into().unwrap();
+            Value::Integer(i32::from_le_bytes(b) as i64)
+        }
+        t if t == DataTypeByte::Float as u8 => {
+            let b: [u8; 4] = take(reply, 1, 4)?.try_into().unwrap();
+            Value::Number(f32::from_le_bytes(b) as f64)
+        }
+        t if t == DataTypeByte::String as u8 => {
+            let len = u32_at(reply, 1)?;
+            Value::String(lua.create_string(take(reply, 5, len)?)?)
+        }
+        t if t == DataTypeByte::StrList as u8 => {
+            let mut items = Vec::new();
+            let mut pos = 1;
+            while pos < reply.len() {
+                let len = u32_at(reply, pos + 1)?;
+                items.push(lua.create_string(take(reply, pos + 5, len)?)?);
+                pos += 5 + len;
+            }
+            Value::Table(lua.create_sequence_from(items)?)
+        }
+        t if t == DataTypeByte::Long as u8 => {
+            let b: [u8; 8] = take(reply, 1, 8)?.try_into().unwrap();
+            Value::Integer(u64::from_le_bytes(b) as i64)
+        }
+        t if t == DataTypeByte::Nil as u8 => Value::Boolean(false),
+        t if t == DataTypeByte::Array as u8 => {
+            let count = u32_at(reply, 1)?;
+            let table = lua.create_table_with_capacity(count, 0)?;
+            let mut pos = 5;
+            for _ in 0..count {
+                let len = u32_at(reply, pos)?;
+                table.raw_push(to_lua(lua, Some(&take(reply, pos + 4, len)?))?)?;
+                pos += 4 + len;
+            }
+            Value::Table(table)
+        }
+        // INFO replies plain text
+        _ => Value::String(lua.create_string(reply)?),
+    };
+    Ok(value)
+}
+
+/**
+ * Reply of a script: nil and false give nil, true 1, integers a long and other numbers a
+ * float. Tables are arrays up to their first nil, {err = message} is an error reply and
+ * {ok = message} a string.
+ */
+fn from_lua(value: &Value) -> Result<Option<Vec<u8>>, String> {
+    if let Value::Table(table) = value {
+        if let Ok(Value::String(e)) = table.raw_get::<_, Value>("err") {
+            return Err(e.to_string_lossy().into_owned());
+        }
+    }
+    Ok(Some(srlz_lua(value)))
+}
+
+fn srlz_lua(value: &Value) -> Vec<u8> {
+    let long = |n: i64| {
+        let mut srlz = vec![DataTypeByte::Long as u8];
+        srlz.extend(n.to_le_bytes());
+        srlz
+    };
+    match value {
+        Value::Boolean(true) => long(1),
+        Value::Integer(n) => long(*n),
+        Value::Number(n) => {
+            let mut srlz = vec![DataTypeByte::Float as u8];
+            srlz.extend((*n as f32).to_le_bytes());
+            srlz
+        }
+        Value::String(s) => StringType::from_bytes(s.as_bytes().to_vec()).serialize(),
+        Value::Table(table) => srlz_table(table),
+        _ => srlz_nil(),
+    }
+}
+
+fn srlz_table(table: &Table) -> Vec<u8> {
+    for field in ["ok", "err"] {
+        if let Ok(Value::String(s)) = table.raw_get::<_, Value>(field) {
+            return StringType::from_bytes(s.as_bytes().to_vec()).serialize();
+        }
+    }
+    let items = table
+        .clone()
+        .sequence_values::<Value>()
+        .map_while(Result::ok)
+        .map(|item| srlz_lua(&item))
+        .collect();
+    srlz_array(items)
+}
@@ -0,0 +1,2 @@
+pub mod lua;
+pub mod scripts;
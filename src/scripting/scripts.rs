@@ -0,0 +1,95 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+
+use crate::state::datastore::DataStore;
+
+/**
+ * Scripts known by their SHA1, loaded by EVAL and SCRIPT LOAD, and the state of the script
+ * running now. Only one script runs at a time since they hold the transaction lock.
+ */
+#[derive(Debug)]
+pub struct Scripts {
+    time_limit: Duration,
+    sources: Mutex<HashMap<String, Arc<str>>>,
+    running: AtomicBool,
+    // a script that wrote can not be killed, the dataset would keep half of its changes
+    wrote: AtomicBool,
+    kill: AtomicBool,
+}
+
+impl Scripts {
+    pub fn new(time_limit: Duration) -> Self {
+        Self {
+            time_limit,
+            sources: Mutex::new(HashMap::new()),
+            running: AtomicBool::new(false),
+            wrote: AtomicBool::new(false),
+            kill: AtomicBool::new(false),
+        }
+    }
+    pub fn time_limit(&self) -> Duration {
+        self.time_limit
+    }
+    // caches the script, returns its SHA1 as lowercase hex
+    pub fn load(&self, source: &str) -> String {
+        let sha = sha1_smol::Sha1::from(source).digest().to_string();
+        self.sources
+            .lock()
+            .entry(sha.clone())
+            .or_insert_with(|| source.into());
+        sha
+    }
+    pub fn get(&self, sha: &str) -> Option<Arc<str>> {
+        self.sources.lock().get(&sha.to_ascii_lowercase()).cloned()
+    }
+    // the script ends when the guard is dropped, also when it unwinds from a panic
+    pub fn begin<'a>(&'a self, store: &'a DataStore) -> RunningScript<'a> {
+        self.wrote.store(false, Ordering::Relaxed);
+        self.kill.store(false, Ordering::Relaxed);
+        self.running.store(true, Ordering::Relaxed);
+        RunningScript {
+            scripts: self,
+            store,
+        }
+    }
+    pub fn record_write(&self) {
+        self.wrote.store(true, Ordering::Relaxed);
+    }
+    pub fn kill_requested(&self) -> bool {
+        self.kill.load(Ordering::Relaxed)
+    }
+    pub fn request_kill(&self) -> Result<(), String> {
+        if !self.running.load(Ordering::Relaxed) {
+            return Err("NOTBUSY No scripts in execution right now".to_owned());
+        }
+        if self.wrote.load(Ordering::Relaxed) {
+            return Err(
+                "UNKILLABLE The script already wrote to the dataset, it can only run to completion"
+                    .to_owned(),
+            );
+        }
+        self.kill.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+// clears the running state and the BUSY flag of the store when the script ends
+pub struct RunningScript<'a> {
+    scripts: &'a Scripts,
+    store: &'a DataStore,
+}
+impl Drop for RunningScript<'_> {
+    fn drop(&mut self) {
+        self.scripts.running.store(false, Ordering::Relaxed);
+        self.scripts.kill.store(false, Ordering::Relaxed);
+        self.store.set_script_busy(false);
+    }
+}
@@ -0,0 +1,20 @@
+use crate::pubsub::registry::Subscription;
+
+// per connection settings, owned by the socket task
+#[derive(Debug, Default)]
+pub struct ConnectionState {
+    pub db: usize,
+    // commands received from the primary or replayed from the AOF, they passed the read only,
+    // memory and cluster checks when they first ran
+    pub replicated: bool,
+    // set by ASKING, lets the next command use a slot being imported
+    pub asking: bool,
+    // payloads of the commands received after MULTI, None outside of a transaction
+    pub queued: Option<Vec<Vec<u8>>>,
+    // a command could not be queued, EXEC refuses the transaction
+    pub queue_failed: bool,
+    // (db, key, version) recorded by WATCH, the version is None for a missing key
+    pub watched: Vec<(usize, String, Option<u64>)>,
+    // channels and patterns while subscribed, published messages are pushed to the connection
+    pub subscription: Option<Subscription>,
+}
@@ -1,17 +1,31 @@
-use crate::data::HLL;
+use crate::data::{json_mem_usage, JsonPath, HLL};
 use dashmap::{
-    mapref::one::{Ref, RefMut},
-    DashMap,
+    mapref::{
+        entry::Entry,
+        one::{Ref, RefMut},
+    },
+    DashMap, RwLockWriteGuard, SharedValue,
 };
 use log::debug;
+use parking_lot::Mutex;
+use serde_json::Value;
 use std::{
-    cmp, mem,
-    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    cmp,
+    collections::{
+        hash_map::{DefaultHasher, RandomState},
+        BTreeMap, BinaryHeap,
+    },
+    hash::{Hash, Hasher},
+    mem::size_of,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering},
     time::{SystemTime, UNIX_EPOCH},
 };
-use string_builder::ToBytes;
 
-use super::expires::{ExpireParameter, NO_EXPIRE};
+use super::{
+    eviction::{lfu_decay, lfu_increment, lru_clock, EvictionPolicy, LFU_INIT},
+    expires::{current_millis, ExpireCondition, ExpireParameter, ExpiryIndex, NO_EXPIRE},
+};
 
 #[repr(u8)]
 pub enum DataTypeByte {
@@ -21,7 +35,31 @@ pub enum DataTypeByte {
     StrList = 4,
     Map = 5,
     Long = 6,
+    Nil = 7,
+    Array = 8,
+}
+pub fn srlz_nil() -> Vec<u8> {
+    vec![DataTypeByte::Nil as u8]
+}
+// element count followed by every serialized element prefixed by its length
+pub fn srlz_array(items: Vec<Vec<u8>>) -> Vec<u8> {
+    let mut result: Vec<u8> = Vec::new();
+    result.push(DataTypeByte::Array as u8);
+    result.extend((items.len() as u32).to_le_bytes());
+    for item in items {
+        result.extend((item.len() as u32).to_le_bytes());
+        result.extend(item);
+    }
+    result
+}
+pub fn srlz_str_list(items: Vec<String>) -> Vec<u8> {
+    let mut result: Vec<u8> = vec![DataTypeByte::StrList as u8];
+    for item in items {
+        result.extend(StringType::new(item).serialize());
+    }
+    result
 }
+#[derive(Clone)]
 pub struct Data<T> {
     data: T,
 }
@@ -31,6 +69,7 @@ pub type ListType = Data<Vec<StringType>>;
 pub type IntType = Data<i32>;
 pub type FloatType = Data<f32>;
 pub type HLLType = Data<HLL>;
+pub type JsonType = Data<Value>;
 
 impl IntType {
     pub fn new(num: i32) -> Self {
@@ -83,6 +122,9 @@ impl ListType {
         }
         Self { data: result }
     }
+    pub fn from_items(items: Vec<StringType>) -> Self {
+        Self { data: items }
+    }
     pub fn srlz_range(&mut self, end: usize) -> Vec<u8> {
         return self.srlz_extract_range_with_start(0, end);
     }
@@ -108,6 +150,7 @@ impl ListType {
         }
         return result;
     }
+    #[allow(clippy::result_unit_err)]
     pub fn push(&mut self, data: StringType) -> Result<(), ()> {
         self.data.push(data);
         return Ok(());
@@ -150,18 +193,73 @@ impl ListType {
     }
 }
 impl StringType {
+    const MAX_LEN: usize = 512 * 1024 * 1024;
     pub fn new(str: String) -> Self {
-        let mut asvecdata: Vec<u8> = Vec::new();
-        let data_type = DataTypeByte::String as u8;
-        asvecdata.push(data_type);
-        let sz = str.len() as u32;
-        asvecdata.extend(sz.to_le_bytes());
-        asvecdata.extend(str.to_bytes().iter());
-        Self { data: asvecdata }
+        Self {
+            data: str.into_bytes(),
+        }
+    }
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self { data: bytes }
     }
 
-    pub fn serialize(&self) -> &Vec<u8> {
-        return &self.data;
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result: Vec<u8> = Vec::with_capacity(self.data.len() + 5);
+        result.push(DataTypeByte::String as u8);
+        result.extend((self.data.len() as u32).to_le_bytes());
+        result.extend(&self.data);
+        result
+    }
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+    pub fn append(&mut self, bytes: &[u8]) -> Result<usize, String> {
+        if self.data.len() + bytes.len() > Self::MAX_LEN {
+            return Err("String exceeds maximum allowed size".to_owned());
+        }
+        self.data.extend_from_slice(bytes);
+        Ok(self.data.len())
+    }
+    // start and end are inclusive, negative values are offsets from the end of the string
+    pub fn srlz_substr(&self, start: i64, end: i64) -> Vec<u8> {
+        let len = self.data.len() as i64;
+        let start = if start < 0 {
+            cmp::max(len + start, 0)
+        } else {
+            start
+        };
+        let end = if end < 0 {
+            len + end
+        } else {
+            cmp::min(end, len - 1)
+        };
+        if start > end || len == 0 {
+            return StringType::from_bytes(Vec::new()).serialize();
+        }
+        StringType::from_bytes(self.data[start as usize..=end as usize].to_vec()).serialize()
+    }
+    // overwrites starting at offset, padding with zero bytes if the string is shorter
+    pub fn set_range(&mut self, offset: usize, bytes: &[u8]) -> Result<usize, String> {
+        let end = offset + bytes.len();
+        if end > Self::MAX_LEN {
+            return Err("String exceeds maximum allowed size".to_owned());
+        }
+        if bytes.is_empty() {
+            return Ok(self.data.len());
+        }
+        if self.data.len() < end {
+            self.data.resize(end, 0);
+        }
+        self.data[offset..end].copy_from_slice(bytes);
+        Ok(self.data.len())
+    }
+}
+impl Default for HLLType {
+    fn default() -> Self {
+        Self::new()
     }
 }
 impl HLLType {
@@ -184,6 +282,15 @@ impl HLLType {
         return result;
     }
 }
+impl JsonType {
+    pub fn new(value: Value) -> Self {
+        Self { data: value }
+    }
+    pub fn srlz_path(&self, path: &JsonPath) -> Result<Vec<u8>, String> {
+        let value = path.get(&self.data).ok_or("Path not found")?;
+        Ok(StringType::new(value.to_string()).serialize())
+    }
+}
 impl<T> Data<T> {
     pub fn get(&self) -> &T {
         return &self.data;
@@ -193,39 +300,259 @@ impl<T> Data<T> {
     }
 }
 
+const MEM_SAMPLES: usize = 64;
+// versions are unique in the process, a value recreated under the same key never gets an old one
+static NEXT_VERSION: AtomicU64 = AtomicU64::new(1);
+fn next_version() -> u64 {
+    NEXT_VERSION.fetch_add(1, Ordering::Relaxed)
+}
+// sums f over the items, extrapolating from evenly spaced samples on long slices
+pub fn sampled_sum<T>(items: &[T], f: impl Fn(&T) -> usize) -> usize {
+    if items.len() <= MEM_SAMPLES {
+        return items.iter().map(f).sum();
+    }
+    let step = items.len() / MEM_SAMPLES;
+    let sampled: usize = items.iter().step_by(step).take(MEM_SAMPLES).map(f).sum();
+    sampled * items.len() / MEM_SAMPLES
+}
+#[derive(Clone)]
 pub enum DataType {
     Int(IntType),
     Float(FloatType),
     String(StringType),
     List(ListType),
     HLL(HLLType),
+    Json(JsonType),
+}
+impl DataType {
+    // serialization of the types that can be returned as a single value by GET
+    pub fn srlz_simple(&self) -> Result<Vec<u8>, String> {
+        match self {
+            DataType::String(v) => Ok(v.serialize()),
+            DataType::Int(v) => Ok(v.serialize().to_vec()),
+            DataType::Float(v) => Ok(v.serialize().to_vec()),
+            _ => Err("Data type is not simple".to_owned()),
+        }
+    }
+    // inverse of srlz_simple, used by commands receiving typed values
+    pub fn deserialize_simple(bytes: &[u8]) -> Result<DataType, String> {
+        match bytes {
+            [t, a, b, c, d] if *t == DataTypeByte::Integer as u8 => {
+                Ok(DataType::Int(IntType::new(i32::from_le_bytes([
+                    *a, *b, *c, *d,
+                ]))))
+            }
+            [t, a, b, c, d] if *t == DataTypeByte::Float as u8 => {
+                Ok(DataType::Float(FloatType::new(f32::from_le_bytes([
+                    *a, *b, *c, *d,
+                ]))))
+            }
+            [t, a, b, c, d, rest @ ..] if *t == DataTypeByte::String as u8 => {
+                if u32::from_le_bytes([*a, *b, *c, *d]) as usize != rest.len() {
+                    return Err("Invalid string value length".to_owned());
+                }
+                Ok(DataType::String(StringType::from_bytes(rest.to_vec())))
+            }
+            _ => Err("Invalid typed value".to_owned()),
+        }
+    }
+    // heap bytes owned by the value, large lists are sampled
+    pub fn mem_usage(&self) -> usize {
+        match self {
+            DataType::Int(_) | DataType::Float(_) => 0,
+            DataType::String(s) => s.get().capacity(),
+            DataType::List(l) => {
+                let items = l.get();
+                items.capacity() * size_of::<StringType>()
+                    + sampled_sum(items, |item| item.get().capacity())
+            }
+            DataType::HLL(h) => h.get().mem_usage(),
+            DataType::Json(j) => json_mem_usage(j.get()),
+        }
+    }
+    // internal representation reported by OBJECT ENCODING
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            DataType::Int(_) => "int",
+            DataType::Float(_) => "float",
+            DataType::String(_) => "raw",
+            // a single contiguous vector of elements, never chunked
+            DataType::List(_) => "vector",
+            // registers are always allocated in full, there is no sparse form
+            DataType::HLL(_) => "dense",
+            DataType::Json(_) => "tree",
+        }
+    }
+    // bytes of the value in its reply encoding
+    pub fn serialized_len(&self) -> usize {
+        match self {
+            DataType::Int(_) | DataType::Float(_) => 5,
+            DataType::String(s) => s.len() + 5,
+            DataType::List(l) => 1 + l.get().iter().map(|e| e.len() + 5).sum::<usize>(),
+            DataType::HLL(h) => h.get().mem_usage(),
+            DataType::Json(j) => serde_json::to_vec(j.get()).map_or(0, |v| v.len()),
+        }
+    }
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            DataType::Int(_) => "int",
+            DataType::Float(_) => "float",
+            DataType::String(_) => "string",
+            DataType::List(_) => "list",
+            DataType::HLL(_) => "hll",
+            DataType::Json(_) => "json",
+        }
+    }
+    pub fn is_simple(&self) -> bool {
+        matches!(
+            self,
+            DataType::String(_) | DataType::Int(_) | DataType::Float(_)
+        )
+    }
+}
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SetCondition {
+    Always,
+    NotExists,
+    Exists,
+}
+pub struct SetFlags {
+    pub condition: SetCondition,
+    pub get: bool,
+}
+impl Default for SetFlags {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl SetFlags {
+    pub const NX: u8 = 1;
+    pub const XX: u8 = 2;
+    pub const GET: u8 = 4;
+    pub fn new() -> Self {
+        Self {
+            condition: SetCondition::Always,
+            get: false,
+        }
+    }
+    // single byte bitmask of NX | XX | GET, an empty argument means no flags
+    pub fn from(data_ref: &[u8]) -> Result<Self, String> {
+        let flags = match data_ref {
+            [] => 0,
+            [f] => *f,
+            _ => return Err("Invalid set flags".to_owned()),
+        };
+        if flags & !(Self::NX | Self::XX | Self::GET) != 0 {
+            return Err("Unknown set flags".to_owned());
+        }
+        let condition = match (flags & Self::NX != 0, flags & Self::XX != 0) {
+            (true, true) => return Err("NX and XX flags are mutually exclusive".to_owned()),
+            (true, false) => SetCondition::NotExists,
+            (false, true) => SetCondition::Exists,
+            (false, false) => SetCondition::Always,
+        };
+        Ok(Self {
+            condition,
+            get: flags & Self::GET != 0,
+        })
+    }
+}
+pub struct SetOutcome {
+    pub applied: bool,
+    pub previous: Option<Vec<u8>>,
+}
+impl SetOutcome {
+    // None means plain OK, a skipped write replies nil, GET replies the previous value or nil
+    pub fn srlz(self, flags: &SetFlags) -> Option<Vec<u8>> {
+        if flags.get {
+            Some(self.previous.unwrap_or_else(srlz_nil))
+        } else if self.applied {
+            None
+        } else {
+            Some(srlz_nil())
+        }
+    }
 }
+type Shard = hashbrown::HashMap<String, SharedValue<DataWrapper>, RandomState>;
 pub struct DataState {
     pub data: DashMap<String, DataWrapper>,
     removed_count: AtomicU32,
-    last_expired_cleanup: AtomicU64,
+    expires: ExpiryIndex,
+    used_memory: AtomicUsize,
+    // keys found expired on access, reported by the next maintenance_work
+    lazily_expired: Mutex<Vec<String>>,
+    // unique per emptied state, tells readers across lock releases a flush or swap happened
+    generation: u64,
+}
+impl Default for DataState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl DataState {
-    const MIN_TIME_BETWEEN_CLEANING: u64 = 100000;
+    const SCAN_SHARD_SHIFT: u32 = 48;
+    const SCAN_POSITION_MASK: u64 = (1 << Self::SCAN_SHARD_SHIFT) - 1;
     pub fn new() -> Self {
         Self {
             data: DashMap::new(),
             removed_count: AtomicU32::new(0),
-            last_expired_cleanup: AtomicU64::new(0),
+            expires: ExpiryIndex::new(),
+            used_memory: AtomicUsize::new(0),
+            lazily_expired: Mutex::new(Vec::new()),
+            generation: next_version(),
         }
     }
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+    // approximate bytes held by keys and values, see DataWrapper::entry_size
+    pub fn used_memory(&self) -> usize {
+        self.used_memory.load(Ordering::Relaxed)
+    }
+    // adds the entry count and accounted bytes of every type to totals
+    pub fn memory_by_type(&self, totals: &mut BTreeMap<&'static str, (usize, usize)>) {
+        for entry in self.data.iter() {
+            let total = totals.entry(entry.get_data().type_name()).or_insert((0, 0));
+            total.0 += 1;
+            total.1 += entry.size;
+        }
+    }
+    // records the size of a value about to be stored under key
+    fn account(&self, key: &str, wrapper: &mut DataWrapper) {
+        wrapper.size = DataWrapper::entry_size(key, &wrapper.data);
+        self.used_memory.fetch_add(wrapper.size, Ordering::Relaxed);
+    }
+    fn forget(&self, old: &DataWrapper) {
+        self.used_memory.fetch_sub(old.size, Ordering::Relaxed);
+    }
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
     pub fn remove(&self, key: &str) {
         let old = self.data.remove(key);
-        if let Some(_) = old {
+        if let Some((_, old)) = old {
+            self.forget(&old);
             self.removed_count.fetch_add(1, Ordering::Relaxed);
         }
     }
 
+    fn remove_expired(&self, key: &str) {
+        if let Some((_, old)) = self.data.remove(key) {
+            self.forget(&old);
+            self.removed_count.fetch_add(1, Ordering::Relaxed);
+            self.lazily_expired.lock().push(key.to_owned());
+        }
+    }
+
     pub fn remove_all(&self, keys: Vec<&str>) {
         let mut removed: u32 = 0;
         for key in keys {
             let old = self.data.remove(key);
-            if let Some(_) = old {
+            if let Some((_, old)) = old {
+                self.forget(&old);
                 removed += 1;
             }
         }
@@ -238,7 +565,8 @@ impl DataState {
         let mut removed: u32 = 0;
         for key in keys {
             let old = self.data.remove(&key);
-            if let Some(_) = old {
+            if let Some((_, old)) = old {
+                self.forget(&old);
                 removed += 1;
             }
         }
@@ -247,35 +575,360 @@ impl DataState {
             self.removed_count.fetch_add(removed, Ordering::Relaxed);
         }
     }
-    pub fn maintenance_work(&self) {
+    // removes the key only if the predicate holds, expired values are dropped and not returned
+    pub fn take_if(
+        &self,
+        key: &str,
+        predicate: impl FnOnce(&DataWrapper) -> bool,
+    ) -> Option<DataWrapper> {
+        let (_, old) = self.data.remove_if(key, |_, v| predicate(v))?;
+        self.forget(&old);
+        self.removed_count.fetch_add(1, Ordering::Relaxed);
+        (!old.is_expired(current_millis())).then_some(old)
+    }
+    /**
+     * Reclaims at most max_expired keys whose deadline passed, the rest is left for the next
+     * tick. Returns the expired keys, with those removed on access since the last call.
+     */
+    pub fn maintenance_work(&self, max_expired: usize) -> Vec<String> {
         let current_removed = self.removed_count.load(Ordering::SeqCst);
         if current_removed as f32 / self.data.len() as f32 > 0.1f32 || current_removed > 50000 {
             debug!("Hashmap shrinking...");
             self.data.shrink_to_fit();
             self.removed_count.store(0, Ordering::SeqCst);
         }
-        let last_expired_cleanup = self.last_expired_cleanup.load(Ordering::SeqCst);
-        let current_ts = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
-        if (current_ts - last_expired_cleanup) > Self::MIN_TIME_BETWEEN_CLEANING
-            || last_expired_cleanup == 0
-        {
-            let mut rm_keys: Vec<String> = Vec::new();
-            self.data.iter().for_each(|kv| {
-                let exp = kv.get_expire();
-                if let Some(ex) = exp {
-                    if ex.load(Ordering::Relaxed) < current_ts {
-                        rm_keys.push(kv.key().clone());
-                    }
-                }
+        let current_ts = current_millis();
+        let mut removed: u32 = 0;
+        let mut expired_keys = std::mem::take(&mut *self.lazily_expired.lock());
+        for (deadline, key) in self.expires.pop_due(current_ts, max_expired) {
+            let expired = self.data.remove_if(&key, |_, v| {
+                v.get_expire().map(|e| e.load(Ordering::Relaxed)) == Some(deadline)
             });
-            if rm_keys.len() > 0 {
-                self.remove_all_string(rm_keys);
+            if let Some((_, old)) = expired {
+                self.forget(&old);
+                removed += 1;
+                expired_keys.push(key);
             }
-            self.last_expired_cleanup
-                .store(current_ts, Ordering::SeqCst);
+        }
+        if removed > 0 {
+            debug!("Expired {} keys", removed);
+            self.removed_count.fetch_add(removed, Ordering::Relaxed);
+        }
+        // mostly stale entries left behind by TTL updates and deletes, rebuild from live keys
+        if self.expires.len() > self.data.len() * 2 + 1024 {
+            let entries = self
+                .data
+                .iter()
+                .filter_map(|kv| {
+                    kv.get_expire()
+                        .map(|e| (e.load(Ordering::Relaxed), kv.key().clone()))
+                })
+                .collect();
+            self.expires.rebuild(entries);
+        }
+        expired_keys
+    }
+    /**
+     * Cursor based iteration: the upper 16 bits of the cursor select the shard and the lower 48
+     * bits a position inside it. Positions come from a hash of the key that does not depend on
+     * the table layout, so resizes neither skip nor repeat keys present during the whole scan.
+     * count is the number of keys visited, only the ones passing the filter are returned.
+     */
+    pub fn scan(
+        &self,
+        cursor: u64,
+        count: usize,
+        filter: impl Fn(&str, &DataWrapper) -> bool,
+    ) -> (u64, Vec<String>) {
+        let shards = self.data.shards();
+        let mut shard_idx = (cursor >> Self::SCAN_SHARD_SHIFT) as usize;
+        let mut from = cursor & Self::SCAN_POSITION_MASK;
+        let mut keys: Vec<String> = Vec::new();
+        let mut visited: usize = 0;
+        let now = current_millis();
+        while shard_idx < shards.len() {
+            let need = count - visited;
+            if need == 0 {
+                break;
+            }
+            let shard = shards[shard_idx].read();
+            // the need smallest positions from the cursor on, plus the keys sharing the largest
+            let mut smallest: BinaryHeap<(u64, &String)> = BinaryHeap::with_capacity(need);
+            let mut ties: Vec<(u64, &String)> = Vec::new();
+            let mut remaining: usize = 0;
+            for key in shard.keys() {
+                let pos = scan_position(key);
+                if pos < from {
+                    continue;
+                }
+                remaining += 1;
+                if smallest.len() < need {
+                    smallest.push((pos, key));
+                    continue;
+                }
+                let max = smallest.peek().map_or(0, |(max, _)| *max);
+                if pos == max {
+                    ties.push((pos, key));
+                } else if pos < max {
+                    let evicted = smallest.pop();
+                    smallest.push((pos, key));
+                    let max = smallest.peek().map_or(0, |(max, _)| *max);
+                    ties.retain(|(pos, _)| *pos == max);
+                    ties.extend(evicted.filter(|(pos, _)| *pos == max));
+                }
+            }
+            let mut taken = smallest.into_vec();
+            taken.append(&mut ties);
+            taken.sort_unstable();
+            visited += taken.len();
+            remaining -= taken.len();
+            keys.extend(taken.iter().filter_map(|(_, key)| {
+                let wrapper = shard.get(*key)?.get();
+                (!wrapper.is_expired(now) && filter(key, wrapper)).then(|| (*key).clone())
+            }));
+            if remaining == 0 {
+                shard_idx += 1;
+                from = 0;
+            } else if let Some((last_pos, _)) = taken.last() {
+                from = last_pos + 1;
+            }
+        }
+        if shard_idx >= shards.len() {
+            return (0, keys);
+        }
+        (((shard_idx as u64) << Self::SCAN_SHARD_SHIFT) | from, keys)
+    }
+    /**
+     * Samples up to samples keys starting at a random shard and position and returns the best
+     * one to evict under the policy, along with its score: the higher, the better to evict.
+     */
+    pub fn eviction_candidate(
+        &self,
+        policy: EvictionPolicy,
+        samples: usize,
+    ) -> Option<(u64, String)> {
+        let shards = self.data.shards();
+        let now = lru_clock();
+        let start = fastrand::usize(..shards.len());
+        let mut best: Option<(u64, String)> = None;
+        let mut sampled = 0;
+        for i in 0..shards.len() {
+            let shard = shards[(start + i) % shards.len()].read();
+            if shard.is_empty() {
+                continue;
+            }
+            let skip = fastrand::usize(..shard.len());
+            let candidates = shard
+                .iter()
+                .skip(skip)
+                .chain(shard.iter().take(skip))
+                .filter(|(_, v)| !policy.volatile_only() || v.get().get_expire().is_some())
+                .take(samples - sampled);
+            for (key, value) in candidates {
+                sampled += 1;
+                let score = value.get().eviction_score(policy, now);
+                if best.as_ref().is_none_or(|(b, _)| score > *b) {
+                    best = Some((score, key.clone()));
+                }
+            }
+            if sampled >= samples {
+                break;
+            }
+        }
+        best
+    }
+    // removes a key chosen by eviction_candidate, returns the bytes released
+    pub fn evict(&self, key: &str) -> usize {
+        match self.data.remove(key) {
+            Some((_, old)) => {
+                self.forget(&old);
+                self.removed_count.fetch_add(1, Ordering::Relaxed);
+                old.size
+            }
+            None => 0,
+        }
+    }
+    pub fn shard_count(&self) -> usize {
+        self.data.shards().len()
+    }
+    pub fn shard_of(&self, key: &str) -> usize {
+        self.data.determine_map(key)
+    }
+    // clones the live entries of one shard, the shard is only locked while copying
+    pub fn copy_shard(&self, shard: usize) -> Vec<(String, Option<u64>, DataType)> {
+        let now = current_millis();
+        self.data.shards()[shard]
+            .read()
+            .iter()
+            .filter(|(_, v)| !v.get().is_expired(now))
+            .map(|(k, v)| {
+                let wrapper = v.get();
+                let deadline = wrapper.get_expire().map(|e| e.load(Ordering::Relaxed));
+                (k.clone(), deadline, wrapper.get_data().clone())
+            })
+            .collect()
+    }
+    // starts at a random shard and position, skipping keys that already expired
+    pub fn random_key(&self) -> Option<String> {
+        let shards = self.data.shards();
+        let now = current_millis();
+        let start = fastrand::usize(..shards.len());
+        for i in 0..shards.len() {
+            let shard = shards[(start + i) % shards.len()].read();
+            if shard.is_empty() {
+                continue;
+            }
+            let skip = fastrand::usize(..shard.len());
+            let found = shard
+                .iter()
+                .skip(skip)
+                .chain(shard.iter().take(skip))
+                .find(|(_, v)| !v.get().is_expired(now));
+            if let Some((k, _)) = found {
+                return Some(k.clone());
+            }
+        }
+        None
+    }
+    /**
+     * Write locks the shards owning the keys, always in ascending shard order so concurrent
+     * multi key operations can not deadlock. Also returns, for every key, the index of the guard
+     * holding it.
+     */
+    fn lock_keys(&self, keys: &[&str]) -> (Vec<RwLockWriteGuard<'_, Shard>>, Vec<usize>) {
+        let owners: Vec<usize> = keys.iter().map(|k| self.data.determine_map(*k)).collect();
+        let mut order = owners.clone();
+        order.sort_unstable();
+        order.dedup();
+        let shards = self.data.shards();
+        let guards = order.iter().map(|i| shards[*i].write()).collect();
+        let slots = owners
+            .iter()
+            .map(|o| order.binary_search(o).unwrap())
+            .collect();
+        (guards, slots)
+    }
+    /**
+     * Moves the value and its TTL to a new key in a single step. With nx the rename only happens
+     * if the destination does not exist, returns whether the key was renamed.
+     */
+    pub fn rename(&self, from: &str, to: &str, nx: bool) -> Result<bool, String> {
+        let (mut guards, slots) = self.lock_keys(&[from, to]);
+        let now = current_millis();
+        let live =
+            |shard: &Shard, key: &str| shard.get(key).is_some_and(|v| !v.get().is_expired(now));
+        if !live(&guards[slots[0]], from) {
+            return Err("No such key".to_owned());
+        }
+        if nx && live(&guards[slots[1]], to) {
+            return Ok(false);
+        }
+        if from == to {
+            return Ok(true);
+        }
+        let value = guards[slots[0]].remove(from).unwrap().into_inner();
+        if let Some(e) = value.get_expire() {
+            self.expires.track(to, e.load(Ordering::Relaxed));
+        }
+        let mut value = value;
+        self.forget(&value);
+        self.account(to, &mut value);
+        if let Some(old) = guards[slots[1]].insert(to.to_owned(), SharedValue::new(value)) {
+            self.forget(old.get());
+            self.removed_count.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(true)
+    }
+    // duplicates value and TTL, an existing destination is only overwritten with replace
+    pub fn copy(&self, from: &str, to: &str, replace: bool) -> Result<bool, String> {
+        if from == to {
+            return Err("Source and destination keys are the same".to_owned());
+        }
+        let (mut guards, slots) = self.lock_keys(&[from, to]);
+        let now = current_millis();
+        let mut value = match guards[slots[0]].get(from).map(|v| v.get()) {
+            Some(v) if !v.is_expired(now) => DataWrapper::new(
+                v.get_data().clone(),
+                v.get_expire()
+                    .map(|e| AtomicU64::new(e.load(Ordering::Relaxed))),
+            ),
+            _ => return Ok(false),
+        };
+        let dest_live = guards[slots[1]]
+            .get(to)
+            .is_some_and(|v| !v.get().is_expired(now));
+        if dest_live && !replace {
+            return Ok(false);
+        }
+        if let Some(e) = value.get_expire() {
+            self.expires.track(to, e.load(Ordering::Relaxed));
+        }
+        self.account(to, &mut value);
+        if let Some(old) = guards[slots[1]].insert(to.to_owned(), SharedValue::new(value)) {
+            self.forget(old.get());
+        }
+        Ok(true)
+    }
+    // writes all the pairs only if none of the keys is live, as one step for MSETNX
+    pub fn set_if_none_exist(&self, pairs: Vec<(&str, DataType)>) -> bool {
+        let keys: Vec<&str> = pairs.iter().map(|(key, _)| *key).collect();
+        let (mut guards, slots) = self.lock_keys(&keys);
+        let now = current_millis();
+        let live =
+            |shard: &Shard, key: &str| shard.get(key).is_some_and(|v| !v.get().is_expired(now));
+        if keys
+            .iter()
+            .zip(&slots)
+            .any(|(key, slot)| live(&guards[*slot], key))
+        {
+            return false;
+        }
+        for ((key, value), slot) in pairs.into_iter().zip(slots) {
+            let mut wrapper = DataWrapper::new(value, None);
+            self.account(key, &mut wrapper);
+            if let Some(old) = guards[slot].insert(key.to_owned(), SharedValue::new(wrapper)) {
+                self.forget(old.get());
+            }
+        }
+        true
+    }
+    /**
+     * Moves a live key into dest unless dest already holds it, the shard of the database passed
+     * first by the caller ordering is locked first. Returns whether the key was moved.
+     */
+    pub fn move_key(&self, key: &str, dest: &DataState, self_first: bool) -> bool {
+        let (mut source_guard, mut dest_guard) = match self_first {
+            true => {
+                let source_guard = self.lock_keys(&[key]).0.remove(0);
+                (source_guard, dest.lock_keys(&[key]).0.remove(0))
+            }
+            false => {
+                let dest_guard = dest.lock_keys(&[key]).0.remove(0);
+                (self.lock_keys(&[key]).0.remove(0), dest_guard)
+            }
+        };
+        let now = current_millis();
+        let live = |shard: &Shard| shard.get(key).is_some_and(|v| !v.get().is_expired(now));
+        if !live(&source_guard) || live(&dest_guard) {
+            return false;
+        }
+        let mut value = source_guard.remove(key).unwrap().into_inner();
+        self.forget(&value);
+        self.removed_count.fetch_add(1, Ordering::Relaxed);
+        dest.account(key, &mut value);
+        if let Some(e) = value.get_expire() {
+            dest.expires.track(key, e.load(Ordering::Relaxed));
+        }
+        if let Some(old) = dest_guard.insert(key.to_owned(), SharedValue::new(value)) {
+            dest.forget(old.get());
+        }
+        true
+    }
+    // changes the TTL of a value obtained through get/get_mut and keeps the expiry index in sync
+    pub fn update_expire(&self, key: &str, wrapper: &DataWrapper, expire: Option<u64>) {
+        wrapper.set_expire(expire);
+        if let Some(deadline) = expire {
+            self.expires.track(key, deadline);
         }
     }
     //this will get the value if exists and not expired, it also deletes the value if expired and returns None
@@ -294,18 +947,29 @@ impl DataState {
                     .unwrap()
                     .as_millis() as u64;
                 if e.load(Ordering::Relaxed) > current {
+                    wrapper.touch();
                     return Some(wrapper);
                 }
             } else {
+                wrapper.touch();
                 return Some(wrapper);
             }
         }
         // if key exists but expire check didn't return early
-        self.remove(key);
+        self.remove_expired(key);
         return None;
     }
+    // version of a live key, None when it does not exist
+    pub fn version(&self, key: &str) -> Option<u64> {
+        self.peek(key).map(|wrapper| wrapper.version())
+    }
+    // like get but leaves the access metadata untouched, used by introspection commands
+    pub fn peek(&self, key: &str) -> Option<Ref<'_, String, DataWrapper>> {
+        let wrapper = self.data.get(key)?;
+        (!wrapper.is_expired(current_millis())).then_some(wrapper)
+    }
     //same as above but mut
-    pub fn get_mut(&self, key: &str) -> Option<RefMut<'_, String, DataWrapper>> {
+    pub fn get_mut(&self, key: &str) -> Option<TrackedMut<'_>> {
         {
             let data = self.data.get_mut(key);
             if let None = data {
@@ -319,45 +983,173 @@ impl DataState {
                     .unwrap()
                     .as_millis() as u64;
                 if e.load(Ordering::Relaxed) > current {
-                    return Some(wrapper);
+                    wrapper.touch();
+                    return Some(TrackedMut::new(self, wrapper));
                 }
             } else {
-                return Some(wrapper);
+                wrapper.touch();
+                return Some(TrackedMut::new(self, wrapper));
             }
         }
         // if key exists but expire check didn't return early
-        self.remove(key);
+        self.remove_expired(key);
         return None;
     }
+    #[allow(clippy::result_unit_err)]
     pub fn set(&self, key: &str, value: DataType, expire: ExpireParameter) -> Result<(), ()> {
-        let current_data = self.get_mut(key);
+        self.set_with_flags(key, value, expire, &SetFlags::new())
+            .map(|_| ())
+            .map_err(|_| ())
+    }
+    // condition check, previous value read and write happen under the same entry lock
+    pub fn set_with_flags(
+        &self,
+        key: &str,
+        value: DataType,
+        expire: ExpireParameter,
+        flags: &SetFlags,
+    ) -> Result<SetOutcome, String> {
+        match self.data.entry(key.to_owned()) {
+            Entry::Occupied(mut entry) => {
+                let live = !entry.get().is_expired(current_millis());
+                let previous = match flags.get && live {
+                    true => Some(entry.get().get_data().srlz_simple()?),
+                    false => None,
+                };
+                let applied = match flags.condition {
+                    SetCondition::Always => true,
+                    SetCondition::NotExists => !live,
+                    SetCondition::Exists => live,
+                };
+                if applied {
+                    let new_expire = expire.calc_new_expire(live.then_some(entry.get()));
+                    if let Some(deadline) = new_expire {
+                        self.expires.track(key, deadline);
+                    }
+                    let mut wrapper = DataWrapper::new(value, new_expire.map(AtomicU64::new));
+                    self.account(key, &mut wrapper);
+                    let old = entry.insert(wrapper);
+                    self.forget(&old);
+                } else if !live {
+                    let old = entry.remove();
+                    self.forget(&old);
+                    self.removed_count.fetch_add(1, Ordering::Relaxed);
+                }
+                Ok(SetOutcome { applied, previous })
+            }
+            Entry::Vacant(entry) => {
+                if flags.condition == SetCondition::Exists {
+                    return Ok(SetOutcome {
+                        applied: false,
+                        previous: None,
+                    });
+                }
+                let new_expire = expire.calc_new_expire(None);
+                if let Some(deadline) = new_expire {
+                    self.expires.track(key, deadline);
+                }
+                let mut wrapper = DataWrapper::new(value, new_expire.map(AtomicU64::new));
+                self.account(key, &mut wrapper);
+                entry.insert(wrapper);
+                Ok(SetOutcome {
+                    applied: true,
+                    previous: None,
+                })
+            }
+        }
+    }
 
-        if let Some(mut d) = current_data {
-            let new_expire = expire.calc_new_expire(Some(&*d));
-            let data = DataWrapper::new(
-                value,
-                new_expire.map_or_else(|| None, |e| Some(AtomicU64::new(e))),
-            );
-            let _ = mem::replace(&mut *d, data);
-            Ok(())
-        } else {
-            let new_expire = expire.calc_new_expire(None);
-            let data = DataWrapper::new(
-                value,
-                new_expire.map_or_else(|| None, |e| Some(AtomicU64::new(e))),
-            );
-            self.data.insert(key.to_owned(), data);
-            Ok(())
+    // a deadline already in the past removes the key, returns whether the TTL was changed
+    pub fn expire(&self, key: &str, deadline: u64, condition: ExpireCondition) -> bool {
+        {
+            let wrapper = match self.get(key) {
+                Some(w) => w,
+                None => return false,
+            };
+            let current = wrapper.get_expire().map(|e| e.load(Ordering::Relaxed));
+            if !condition.allows(current, deadline) {
+                return false;
+            }
+            if deadline > current_millis() {
+                self.update_expire(key, &wrapper, Some(deadline));
+                return true;
+            }
+        }
+        self.remove(key);
+        true
+    }
+    pub fn persist(&self, key: &str) -> bool {
+        match self.get(key) {
+            Some(wrapper) if wrapper.get_expire().is_some() => {
+                wrapper.set_expire(None);
+                true
+            }
+            _ => false,
         }
     }
 
     pub fn flush(&mut self) {
         self.data = DashMap::new();
+        self.expires = ExpiryIndex::new();
+        self.used_memory.store(0, Ordering::Relaxed);
+        self.generation = next_version();
+    }
+}
+// stable within the process, independent from the DashMap hasher state and table size
+fn scan_position(key: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish() >> (64 - DataState::SCAN_SHARD_SHIFT)
+}
+/**
+ * Mutable access to a stored value. The memory used by the entry is measured again when the
+ * guard is dropped, so in place updates through get_mut stay accounted.
+ */
+pub struct TrackedMut<'a> {
+    state: &'a DataState,
+    inner: RefMut<'a, String, DataWrapper>,
+}
+impl<'a> TrackedMut<'a> {
+    fn new(state: &'a DataState, inner: RefMut<'a, String, DataWrapper>) -> Self {
+        Self { state, inner }
+    }
+}
+impl<'a> Deref for TrackedMut<'a> {
+    type Target = RefMut<'a, String, DataWrapper>;
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+impl DerefMut for TrackedMut<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+impl Drop for TrackedMut<'_> {
+    fn drop(&mut self) {
+        let (key, wrapper) = self.inner.pair_mut();
+        let size = DataWrapper::entry_size(key, &wrapper.data);
+        if size != wrapper.size {
+            self.state.used_memory.fetch_add(size, Ordering::Relaxed);
+            self.state
+                .used_memory
+                .fetch_sub(wrapper.size, Ordering::Relaxed);
+            wrapper.size = size;
+        }
     }
 }
 pub struct DataWrapper {
     data: DataType,
     expire: AtomicU64,
+    // bytes accounted in DataState::used_memory for this entry
+    size: usize,
+    // lru_clock() of the last access and logarithmic access counter, used for eviction
+    last_access: AtomicU32,
+    frequency: AtomicU8,
+    // unix millis of the creation, kept when the value is renamed or moved
+    created: u64,
+    // changes on every write to the value or its TTL, compared by WATCH
+    version: AtomicU64,
 }
 impl DataWrapper {
     pub fn new(data: DataType, expire: Option<AtomicU64>) -> Self {
@@ -373,14 +1165,72 @@ impl DataWrapper {
         Self {
             data: data,
             expire: exp,
+            size: 0,
+            last_access: AtomicU32::new(lru_clock()),
+            frequency: AtomicU8::new(LFU_INIT),
+            created: current_millis(),
+            version: AtomicU64::new(next_version()),
+        }
+    }
+    pub fn created(&self) -> u64 {
+        self.created
+    }
+    // table overhead is a rough estimate of the hash table slot and control bytes
+    pub fn entry_size(key: &str, data: &DataType) -> usize {
+        const TABLE_OVERHEAD: usize = 8;
+        size_of::<String>()
+            + key.len()
+            + size_of::<DataWrapper>()
+            + TABLE_OVERHEAD
+            + data.mem_usage()
+    }
+    pub fn touch(&self) {
+        let now = lru_clock();
+        let last = self.last_access.swap(now, Ordering::Relaxed);
+        let decayed = lfu_decay(self.frequency.load(Ordering::Relaxed), last, now);
+        self.frequency
+            .store(lfu_increment(decayed), Ordering::Relaxed);
+    }
+    pub fn idle_seconds(&self, now: u32) -> u32 {
+        now.saturating_sub(self.last_access.load(Ordering::Relaxed))
+    }
+    pub fn frequency(&self, now: u32) -> u8 {
+        lfu_decay(
+            self.frequency.load(Ordering::Relaxed),
+            self.last_access.load(Ordering::Relaxed),
+            now,
+        )
+    }
+    fn eviction_score(&self, policy: EvictionPolicy, now: u32) -> u64 {
+        match policy {
+            EvictionPolicy::AllKeysLfu => (u8::MAX - self.frequency(now)) as u64,
+            // the sooner the deadline the better
+            EvictionPolicy::VolatileTtl => self
+                .get_expire()
+                .map_or(0, |e| u64::MAX - e.load(Ordering::Relaxed)),
+            _ => self.idle_seconds(now) as u64,
         }
     }
     pub fn get_data_mut(&mut self) -> &mut DataType {
+        *self.version.get_mut() = next_version();
         return &mut self.data;
     }
     pub fn get_data(&self) -> &DataType {
         return &self.data;
     }
+    pub fn set_expire(&self, expire: Option<u64>) {
+        self.expire.store(expire.unwrap_or(0), Ordering::Relaxed);
+        self.version.store(next_version(), Ordering::Relaxed);
+    }
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Relaxed)
+    }
+    pub fn is_expired(&self, now: u64) -> bool {
+        match self.get_expire() {
+            Some(e) => e.load(Ordering::Relaxed) <= now,
+            None => false,
+        }
+    }
     pub fn get_expire(&self) -> Option<&AtomicU64> {
         if self.expire.load(Ordering::Relaxed) == 0 {
             None
@@ -0,0 +1,183 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::{
+    datastate::DataState,
+    eviction::{EvictionPolicy, MemoryLimit},
+};
+
+pub const BUSY: &str = "BUSY A script is running past the time limit, use SCRIPT KILL";
+const BUSY_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/**
+ * The logical databases of the server, selected per connection by index.
+ * Their number is fixed at startup, SWAPDB exchanges the contents and not the slots
+ * so connections keep pointing to the same index.
+ */
+pub struct DataStore {
+    dbs: Vec<Arc<RwLock<DataState>>>,
+    memory_limit: MemoryLimit,
+    evicted_keys: AtomicU64,
+    // write commands since the last snapshot
+    changes: AtomicU64,
+    // held shared by every command and exclusively by EXEC and scripts, so they run alone
+    transactions: RwLock<()>,
+    // a script holds the lock for longer than the time limit, waiting commands give up
+    script_busy: AtomicBool,
+}
+
+impl DataStore {
+    pub fn new(count: usize, memory_limit: MemoryLimit) -> Self {
+        Self {
+            dbs: (0..count.max(1))
+                .map(|_| Arc::new(RwLock::new(DataState::new())))
+                .collect(),
+            memory_limit,
+            evicted_keys: AtomicU64::new(0),
+            changes: AtomicU64::new(0),
+            transactions: RwLock::new(()),
+            script_busy: AtomicBool::new(false),
+        }
+    }
+    pub fn command_guard(&self) -> Result<RwLockReadGuard<'_, ()>, String> {
+        self.wait_unless_busy(|| self.transactions.try_read_for(BUSY_POLL_INTERVAL))
+    }
+    pub fn transaction_guard(&self) -> Result<RwLockWriteGuard<'_, ()>, String> {
+        self.wait_unless_busy(|| self.transactions.try_write_for(BUSY_POLL_INTERVAL))
+    }
+    // replicated writes wait for a script however long it runs, failing would lose them
+    pub fn replication_guard(&self) -> RwLockReadGuard<'_, ()> {
+        self.transactions.read()
+    }
+    fn wait_unless_busy<G>(&self, mut try_lock: impl FnMut() -> Option<G>) -> Result<G, String> {
+        loop {
+            if let Some(guard) = try_lock() {
+                return Ok(guard);
+            }
+            if self.script_busy.load(Ordering::Relaxed) {
+                return Err(BUSY.to_owned());
+            }
+        }
+    }
+    pub fn set_script_busy(&self, busy: bool) {
+        self.script_busy.store(busy, Ordering::Relaxed);
+    }
+    pub fn record_change(&self) {
+        self.changes.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn changes(&self) -> u64 {
+        self.changes.load(Ordering::Relaxed)
+    }
+    // writes that happened while a snapshot was being taken still count for the next one
+    pub fn forget_changes(&self, saved: u64) {
+        self.changes.fetch_sub(saved, Ordering::Relaxed);
+    }
+    pub fn memory_limit(&self) -> &MemoryLimit {
+        &self.memory_limit
+    }
+    pub fn used_memory(&self) -> usize {
+        self.dbs.iter().map(|db| db.read().used_memory()).sum()
+    }
+    pub fn evicted_keys(&self) -> u64 {
+        self.evicted_keys.load(Ordering::Relaxed)
+    }
+    /**
+     * Called before commands that may allocate. Evicts keys following the policy until the
+     * used memory is below max_memory, fails if the policy forbids it or nothing can be evicted.
+     * Evicted keys are collected with their database so they can be logged.
+     */
+    pub fn reserve_memory(&self, evicted: &mut Vec<(usize, String)>) -> Result<(), String> {
+        let limit = &self.memory_limit;
+        if limit.max_memory == 0 {
+            return Ok(());
+        }
+        let mut used = self.used_memory();
+        while used > limit.max_memory {
+            let victim = match limit.policy {
+                EvictionPolicy::NoEviction => None,
+                _ => self.evict_one(),
+            };
+            match victim {
+                Some(victim) => evicted.push(victim),
+                None => {
+                    return Err("OOM command not allowed when used memory > max_memory".to_owned())
+                }
+            }
+            used = self.used_memory();
+        }
+        Ok(())
+    }
+    // the best candidate among the samples of every database is evicted
+    fn evict_one(&self) -> Option<(usize, String)> {
+        let limit = &self.memory_limit;
+        let best = self
+            .dbs
+            .iter()
+            .enumerate()
+            .filter_map(|(index, db)| {
+                let candidate = db.read().eviction_candidate(limit.policy, limit.samples);
+                candidate.map(|(score, key)| (score, key, index))
+            })
+            .max_by_key(|(score, _, _)| *score);
+        let (_, key, index) = best?;
+        self.dbs[index].read().evict(&key);
+        self.evicted_keys.fetch_add(1, Ordering::Relaxed);
+        Some((index, key))
+    }
+    pub fn db(&self, index: usize) -> &Arc<RwLock<DataState>> {
+        &self.dbs[index]
+    }
+    pub fn dbs(&self) -> &[Arc<RwLock<DataState>>] {
+        &self.dbs
+    }
+    // database indexes travel as u32
+    pub fn parse_index(&self, bytes: &[u8]) -> Result<usize, String> {
+        let index_b: [u8; 4] = bytes
+            .try_into()
+            .map_err(|_| "Invalid database index".to_owned())?;
+        let index = u32::from_le_bytes(index_b) as usize;
+        if index >= self.dbs.len() {
+            return Err("Database index is out of range".to_owned());
+        }
+        Ok(index)
+    }
+    pub fn flush_all(&self) {
+        for db in self.dbs.iter() {
+            db.write().flush();
+        }
+    }
+    // both databases are locked in index order, like every other multi database operation
+    pub fn swap(&self, a: usize, b: usize) {
+        if a == b {
+            return;
+        }
+        let (first, second) = (a.min(b), a.max(b));
+        let mut first_lock = self.dbs[first].write();
+        let mut second_lock = self.dbs[second].write();
+        std::mem::swap(&mut *first_lock, &mut *second_lock);
+    }
+    /**
+     * Moves a live key to another database, nothing happens if the destination already holds it.
+     * Returns whether the key was moved.
+     */
+    pub fn move_key(&self, key: &str, from: usize, to: usize) -> Result<bool, String> {
+        if from == to {
+            return Err("Source and destination databases are the same".to_owned());
+        }
+        let (first, second) = (from.min(to), from.max(to));
+        let first_lock = self.dbs[first].read();
+        let second_lock = self.dbs[second].read();
+        let (source, dest) = match from < to {
+            true => (&*first_lock, &*second_lock),
+            false => (&*second_lock, &*first_lock),
+        };
+        Ok(source.move_key(key, dest, from < to))
+    }
+}
@@ -0,0 +1,69 @@
+use serde::Deserialize;
+
+use super::expires::current_millis;
+
+// what to do when a write needs memory above max_memory
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub enum EvictionPolicy {
+    #[serde(rename = "noeviction")]
+    NoEviction,
+    #[serde(rename = "allkeys-lru")]
+    AllKeysLru,
+    #[serde(rename = "allkeys-lfu")]
+    AllKeysLfu,
+    #[serde(rename = "volatile-lru")]
+    VolatileLru,
+    #[serde(rename = "volatile-ttl")]
+    VolatileTtl,
+}
+
+impl EvictionPolicy {
+    // volatile policies only ever evict keys with a TTL
+    pub fn volatile_only(&self) -> bool {
+        matches!(
+            self,
+            EvictionPolicy::VolatileLru | EvictionPolicy::VolatileTtl
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryLimit {
+    // 0 disables the limit
+    pub max_memory: usize,
+    pub policy: EvictionPolicy,
+    // keys sampled per database for every eviction
+    pub samples: usize,
+}
+
+pub const LFU_INIT: u8 = 5;
+const LFU_LOG_FACTOR: u32 = 10;
+// idle minutes after which the counter loses one point
+const LFU_DECAY_MINUTES: u32 = 1;
+
+// seconds resolution is enough for LRU and keeps the access time in 32 bits
+pub fn lru_clock() -> u32 {
+    (current_millis() / 1000) as u32
+}
+
+/**
+ * Logarithmic access counter: the more accesses a key already had the less likely a new one
+ * increments it, so 255 is only reached after about a million accesses.
+ */
+pub fn lfu_increment(counter: u8) -> u8 {
+    if counter == u8::MAX {
+        return counter;
+    }
+    let base = counter.saturating_sub(LFU_INIT) as u32;
+    let p = 1.0 / (base * LFU_LOG_FACTOR + 1) as f64;
+    match fastrand::f64() < p {
+        true => counter + 1,
+        false => counter,
+    }
+}
+
+// keys that stop being accessed lose frequency over time
+pub fn lfu_decay(counter: u8, last_access: u32, now: u32) -> u8 {
+    let periods = now.saturating_sub(last_access) / 60 / LFU_DECAY_MINUTES;
+    counter.saturating_sub(periods.min(u8::MAX as u32) as u8)
+}
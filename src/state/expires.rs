@@ -1,49 +1,171 @@
 use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
     sync::atomic::{AtomicU64, Ordering},
     time::SystemTime,
 };
 
+use parking_lot::Mutex;
+
 use super::datastate::DataWrapper;
 
+// deadlines and TTLs are kept in milliseconds whatever unit they were sent in
 pub enum ExpireParameter {
     EXPIREAT(u64),
-    EXPIREIN(u32),
+    EXPIREIN(u64),
     KEEPTTL,
     None,
 }
 impl ExpireParameter {
-    pub fn from(data_ref: &[u8]) -> Self {
-        match data_ref {
-            [a, b, c, d, e, f, g, h] if data_ref.len() == 8 => {
-                ExpireParameter::EXPIREAT(u64::from_le_bytes([*a, *b, *c, *d, *e, *f, *g, *h]))
+    pub const TAG_NONE: u8 = 0;
+    pub const TAG_EXPIREAT_SECONDS: u8 = 1;
+    pub const TAG_EXPIREAT_MILLIS: u8 = 2;
+    pub const TAG_EXPIREIN_SECONDS: u8 = 3;
+    pub const TAG_EXPIREIN_MILLIS: u8 = 4;
+    pub const TAG_KEEPTTL: u8 = 5;
+    /**
+     * Tag byte followed by a little endian u64 for the time based tags.
+     * An empty argument means no expire so it can be skipped when later arguments are sent.
+     */
+    pub fn from(data_ref: &[u8]) -> Result<Self, String> {
+        let (tag, value) = match data_ref {
+            [] => return Ok(ExpireParameter::None),
+            [tag] => (*tag, None),
+            [tag, rest @ ..] => {
+                let value: [u8; 8] = rest
+                    .try_into()
+                    .map_err(|_| "Invalid expire value, expected u64".to_owned())?;
+                (*tag, Some(u64::from_le_bytes(value)))
             }
-            [a, b, c, d] if data_ref.len() == 4 => {
-                ExpireParameter::EXPIREIN(u32::from_le_bytes([*a, *b, *c, *d]))
+        };
+        match (tag, value) {
+            (Self::TAG_NONE, None) => Ok(ExpireParameter::None),
+            (Self::TAG_KEEPTTL, None) => Ok(ExpireParameter::KEEPTTL),
+            (Self::TAG_EXPIREAT_SECONDS, Some(v)) => {
+                Ok(ExpireParameter::EXPIREAT(v.saturating_mul(1000)))
             }
-            [_] if data_ref.len() == 1 => ExpireParameter::KEEPTTL,
-            _ => ExpireParameter::None,
+            (Self::TAG_EXPIREAT_MILLIS, Some(v)) => Ok(ExpireParameter::EXPIREAT(v)),
+            (Self::TAG_EXPIREIN_SECONDS, Some(v)) => {
+                Ok(ExpireParameter::EXPIREIN(v.saturating_mul(1000)))
+            }
+            (Self::TAG_EXPIREIN_MILLIS, Some(v)) => Ok(ExpireParameter::EXPIREIN(v)),
+            _ => Err(format!("Invalid expire parameter with tag {}", tag)),
         }
     }
+    pub fn is_set(&self) -> bool {
+        !matches!(self, ExpireParameter::None)
+    }
     pub fn calc_new_expire(&self, old_data: Option<&DataWrapper>) -> Option<u64> {
-        let out = match self {
-            ExpireParameter::EXPIREAT(eat) => Some(*eat),
-            ExpireParameter::EXPIREIN(ein) => {
-                let time = SystemTime::now()
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis() as u64;
-                return Some(time + *ein as u64);
-            }
-            ExpireParameter::KEEPTTL => {
-                if let Some(data) = old_data {
-                    return Some(data.get_expire().unwrap().load(Ordering::Relaxed));
-                } else {
-                    None
+        match self {
+            // a stored 0 means no TTL, the epoch is kept as a deadline already elapsed
+            ExpireParameter::EXPIREAT(eat) => Some((*eat).max(1)),
+            ExpireParameter::EXPIREIN(ein) => Some(current_millis().saturating_add(*ein)),
+            // keeping the TTL of a key that had none, or did not exist, leaves it without one
+            ExpireParameter::KEEPTTL => old_data
+                .and_then(|data| data.get_expire())
+                .map(|e| e.load(Ordering::Relaxed)),
+            ExpireParameter::None => None,
+        }
+    }
+}
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExpireCondition {
+    Always,
+    IfNoExpire,
+    IfHasExpire,
+    IfGreater,
+    IfLess,
+}
+impl ExpireCondition {
+    // single byte: 1 NX, 2 XX, 3 GT, 4 LT, missing or 0 applies unconditionally
+    pub fn from(data_ref: &[u8]) -> Result<Self, String> {
+        match data_ref {
+            [] | [0] => Ok(ExpireCondition::Always),
+            [1] => Ok(ExpireCondition::IfNoExpire),
+            [2] => Ok(ExpireCondition::IfHasExpire),
+            [3] => Ok(ExpireCondition::IfGreater),
+            [4] => Ok(ExpireCondition::IfLess),
+            _ => Err("Invalid expire condition".to_owned()),
+        }
+    }
+    // keys without TTL behave as an infinite TTL for GT and LT
+    pub fn allows(&self, current: Option<u64>, new: u64) -> bool {
+        match (self, current) {
+            (ExpireCondition::Always, _) => true,
+            (ExpireCondition::IfNoExpire, c) => c.is_none(),
+            (ExpireCondition::IfHasExpire, c) => c.is_some(),
+            (ExpireCondition::IfGreater, Some(c)) => new > c,
+            (ExpireCondition::IfGreater, None) => false,
+            (ExpireCondition::IfLess, Some(c)) => new < c,
+            (ExpireCondition::IfLess, None) => true,
+        }
+    }
+}
+// whether writes to an existing key refresh its TTL or only newly created keys get one
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExpireMode {
+    Always,
+    OnCreate,
+}
+impl ExpireMode {
+    pub fn from(data_ref: &[u8]) -> Result<Self, String> {
+        match data_ref {
+            [] | [0] => Ok(ExpireMode::Always),
+            [1] => Ok(ExpireMode::OnCreate),
+            _ => Err("Invalid expire mode".to_owned()),
+        }
+    }
+}
+/**
+ * Min-heap of (deadline, key) used by active expiry so only due keys are visited.
+ * Entries are never updated in place: a changed or removed TTL leaves a stale entry behind
+ * which is discarded when popped because it no longer matches the stored deadline.
+ */
+pub struct ExpiryIndex {
+    heap: Mutex<BinaryHeap<Reverse<(u64, String)>>>,
+}
+impl Default for ExpiryIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl ExpiryIndex {
+    pub fn new() -> Self {
+        Self {
+            heap: Mutex::new(BinaryHeap::new()),
+        }
+    }
+    pub fn track(&self, key: &str, deadline: u64) {
+        self.heap.lock().push(Reverse((deadline, key.to_owned())));
+    }
+    // pops at most max entries whose deadline is not after now
+    pub fn pop_due(&self, now: u64, max: usize) -> Vec<(u64, String)> {
+        let mut heap = self.heap.lock();
+        let mut due: Vec<(u64, String)> = Vec::new();
+        while due.len() < max {
+            match heap.peek() {
+                Some(Reverse((deadline, _))) if *deadline <= now => {
+                    due.push(heap.pop().unwrap().0);
                 }
+                _ => break,
             }
-            ExpireParameter::None => None,
-        };
-        return out;
+        }
+        due
+    }
+    pub fn len(&self) -> usize {
+        self.heap.lock().len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.heap.lock().is_empty()
+    }
+    pub fn rebuild(&self, entries: Vec<(u64, String)>) {
+        *self.heap.lock() = entries.into_iter().map(Reverse).collect();
     }
 }
 pub const NO_EXPIRE: AtomicU64 = AtomicU64::new(0);
+pub fn current_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
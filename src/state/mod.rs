@@ -1,4 +1,7 @@
+pub mod connectionstate;
 #[allow(dead_code)]
 pub mod datastate;
+pub mod datastore;
+pub mod eviction;
 pub mod expires;
 pub mod serverstate;
@@ -1,7 +1,24 @@
-use std::time::Instant;
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use string_builder::Builder;
 
+use crate::{
+    cluster::topology::Topology,
+    config::ApplicationConfig,
+    persistence::{aof::Aof, snapshot::Snapshotter},
+    pubsub::{keyspace::KeyspaceEvents, registry::PubSub},
+    replication::backlog::Replication,
+    scripting::scripts::Scripts,
+};
+
+// bumped on incompatible wire changes: 2 replies nil instead of an error for missing keys,
+// 3 sends expire arguments as a tag byte followed by the value
+pub const PROTOCOL_VERSION: u32 = 3;
+
 #[derive(Debug, Clone)]
 pub struct ServerState {
     pub current_connections: u32,
@@ -9,16 +26,40 @@ pub struct ServerState {
     pub processed_commands: u64,
     pub version: String,
     pub start_time: Instant,
+    pub config: Arc<ApplicationConfig>,
+    pub snapshots: Arc<Snapshotter>,
+    // installed after the startup replay so replayed commands are not logged again
+    pub aof: Option<Arc<Aof>>,
+    pub replication: Arc<Replication>,
+    // slot ownership when cluster mode is enabled
+    pub cluster: Option<Arc<Topology>>,
+    pub scripts: Arc<Scripts>,
+    pub pubsub: Arc<PubSub>,
+    pub keyspace_events: Arc<KeyspaceEvents>,
 }
 
 impl ServerState {
-    pub fn new(version: &str) -> Self {
+    pub fn new(version: &str, config: Arc<ApplicationConfig>) -> Self {
+        let pubsub = Arc::new(PubSub::new(config.pubsub_output_buffer_limit));
         Self {
             current_connections: 0,
             total_connections: 0,
             processed_commands: 0,
             version: version.to_owned(),
             start_time: Instant::now(),
+            snapshots: Arc::new(Snapshotter::new(PathBuf::from(&config.snapshot_path))),
+            replication: Arc::new(Replication::new(config.repl_backlog_size)),
+            scripts: Arc::new(Scripts::new(Duration::from_millis(
+                config.lua_time_limit_ms,
+            ))),
+            keyspace_events: Arc::new(KeyspaceEvents::new(
+                pubsub.clone(),
+                &config.notify_keyspace_events,
+            )),
+            pubsub,
+            config,
+            aof: None,
+            cluster: None,
         }
     }
     pub fn to_string(self: &Self) -> String {
@@ -35,6 +76,9 @@ impl ServerState {
         str_b.append("version: ");
         str_b.append(self.version.as_ref() as &str);
         str_b.append("\n");
+        str_b.append("protocol_version: ");
+        str_b.append(PROTOCOL_VERSION.to_string());
+        str_b.append("\n");
         str_b.append("uptime: ");
         str_b.append(
             Instant::now()
@@ -1,4 +1,52 @@
-use crate::data::HLL;
+use std::{path::PathBuf, sync::Arc};
+
+use config_file::FromConfigFile;
+use parking_lot::RwLock;
+
+use crate::{
+    cluster::{
+        crc16::{crc16, key_slot},
+        topology::{SlotAction, Topology, CROSSSLOT},
+    },
+    commands::{
+        commands::{Command, CommandType},
+        parser::Parser,
+    },
+    config::ApplicationConfig,
+    data::{glob_match, JsonPath, HLL},
+    persistence::{
+        aof::{self, Aof, FsyncPolicy},
+        codec::{decode_dump, encode_value, RESTORE_ABSTTL, RESTORE_REPLACE},
+        jsonlines,
+        snapshot::{load_snapshot, snapshot_bytes, write_snapshot},
+    },
+    pubsub::{keyspace::KeyspaceEvents, registry::PubSub},
+    replication::backlog::{Attach, Replication},
+    state::{
+        connectionstate::ConnectionState,
+        datastate::{
+            srlz_array, srlz_nil, DataState, DataType, DataWrapper, FloatType, HLLType, IntType,
+            JsonType, ListType, SetFlags, StringType,
+        },
+        datastore::{DataStore, BUSY},
+        eviction::{lru_clock, EvictionPolicy, MemoryLimit, LFU_INIT},
+        expires::{current_millis, ExpireCondition, ExpireParameter},
+        serverstate::ServerState,
+    },
+};
+const UNLIMITED: MemoryLimit = MemoryLimit {
+    max_memory: 0,
+    policy: EvictionPolicy::NoEviction,
+    samples: 5,
+};
+
+// store without memory limit and the server state of ./echors.toml
+fn test_store(dbs: usize) -> (Arc<DataStore>, Arc<RwLock<ServerState>>) {
+    let config = ApplicationConfig::from_config_file(PathBuf::from("./echors.toml")).unwrap();
+    let server_state = Arc::new(RwLock::new(ServerState::new("test", Arc::new(config))));
+    (Arc::new(DataStore::new(dbs, UNLIMITED)), server_state)
+}
+
 #[test]
 fn hll_test() {
     let mut hll = HLL::new(14);
@@ -9,3 +57,1415 @@ fn hll_test() {
     println!("count {}", hll.count());
     hll.reset();
 }
+#[test]
+fn json_path_test() {
+    let mut doc: serde_json::Value =
+        serde_json::from_str(r#"{"a":{"b":[1,2,{"c":"d"}]},"e f":true}"#).unwrap();
+    let path = JsonPath::parse("$.a.b[-1]['c']").unwrap();
+    assert_eq!(path.get(&doc), Some(&serde_json::json!("d")));
+    assert!(JsonPath::parse("$['e f']").unwrap().get(&doc).is_some());
+    JsonPath::parse("$.a.x")
+        .unwrap()
+        .set(&mut doc, serde_json::json!(5))
+        .unwrap();
+    assert_eq!(doc["a"]["x"], 5);
+    assert!(JsonPath::parse("$.a.b[0]").unwrap().delete(&mut doc));
+    assert_eq!(doc["a"]["b"].as_array().unwrap().len(), 2);
+    assert!(JsonPath::parse("$.a.b[5]")
+        .unwrap()
+        .set(&mut doc, serde_json::json!(1))
+        .is_err());
+    assert!(JsonPath::parse("a.b").is_err());
+}
+#[test]
+fn string_range_test() {
+    let mut s = StringType::new("Hello".to_owned());
+    assert_eq!(s.append(b" World").unwrap(), 11);
+    assert_eq!(
+        s.srlz_substr(-5, -1),
+        StringType::new("World".to_owned()).serialize()
+    );
+    assert_eq!(
+        s.srlz_substr(3, 1),
+        StringType::new(String::new()).serialize()
+    );
+    let mut padded = StringType::from_bytes(Vec::new());
+    assert_eq!(padded.set_range(3, b"ab").unwrap(), 5);
+    assert_eq!(padded.get(), &vec![0, 0, 0, b'a', b'b']);
+}
+#[test]
+fn conditional_set_test() {
+    let state = DataState::new();
+    let nx = SetFlags::from(&[SetFlags::NX | SetFlags::GET]).unwrap();
+    let xx = SetFlags::from(&[SetFlags::XX]).unwrap();
+    let string = |s: &str| DataType::String(StringType::new(s.to_owned()));
+    let outcome = state
+        .set_with_flags("k", string("a"), ExpireParameter::None, &xx)
+        .unwrap();
+    assert!(!outcome.applied);
+    let outcome = state
+        .set_with_flags("k", string("a"), ExpireParameter::None, &nx)
+        .unwrap();
+    assert!(outcome.applied && outcome.previous.is_none());
+    let outcome = state
+        .set_with_flags("k", string("b"), ExpireParameter::None, &nx)
+        .unwrap();
+    assert!(!outcome.applied);
+    assert_eq!(
+        outcome.previous,
+        Some(StringType::new("a".to_owned()).serialize())
+    );
+    assert!(
+        state
+            .set_with_flags("k", string("c"), ExpireParameter::None, &xx)
+            .unwrap()
+            .applied
+    );
+    assert!(SetFlags::from(&[SetFlags::NX | SetFlags::XX]).is_err());
+}
+#[test]
+fn typed_value_roundtrip_test() {
+    for value in [
+        DataType::Int(IntType::new(-7)),
+        DataType::Float(FloatType::new(1.5)),
+        DataType::String(StringType::new("abc".to_owned())),
+    ] {
+        let bytes = value.srlz_simple().unwrap();
+        let parsed = DataType::deserialize_simple(&bytes).unwrap();
+        assert_eq!(parsed.srlz_simple().unwrap(), bytes);
+    }
+    assert!(DataType::deserialize_simple(&[3, 5, 0, 0, 0, b'a']).is_err());
+}
+#[test]
+fn set_if_none_exist_test() {
+    let state = DataState::new();
+    let int = |i: i32| DataType::Int(IntType::new(i));
+    assert!(state.set_if_none_exist(vec![("a", int(1)), ("b", int(2))]));
+    // one live key is enough to leave every key untouched
+    assert!(!state.set_if_none_exist(vec![("c", int(3)), ("a", int(4))]));
+    assert!(state.get("c").is_none());
+    assert_eq!(
+        state.get("a").unwrap().get_data().srlz_simple(),
+        int(1).srlz_simple()
+    );
+    assert!(state.set_if_none_exist(vec![("c", int(3))]));
+}
+#[test]
+fn expire_conditions_test() {
+    let state = DataState::new();
+    let list = DataType::List(ListType::new(vec!["a".to_owned()]));
+    let _ = state.set("l", list, ExpireParameter::None);
+    let now = current_millis();
+    assert!(!state.expire("l", now + 10_000, ExpireCondition::IfHasExpire));
+    assert!(state.expire("l", now + 10_000, ExpireCondition::IfNoExpire));
+    assert!(!state.expire("l", now + 10_000, ExpireCondition::IfNoExpire));
+    assert!(!state.expire("l", now + 5_000, ExpireCondition::IfGreater));
+    assert!(state.expire("l", now + 5_000, ExpireCondition::IfLess));
+    assert!(state.persist("l"));
+    assert!(!state.persist("l"));
+    assert!(state.expire("l", now - 1, ExpireCondition::Always));
+    assert!(state.get("l").is_none());
+    // the shared implementation reports the command that was called
+    let cmd = Command {
+        command_type: CommandType::PExpireAt,
+        arguments: vec![b"l"],
+    };
+    let (store, server_state) = test_store(1);
+    assert_eq!(
+        cmd.execute(&store, &mut ConnectionState::default(), &server_state),
+        Err("Invalid number of arguments for PEXPIREAT command".to_owned())
+    );
+}
+#[test]
+fn expiring_writes_test() {
+    let (store, server_state) = test_store(1);
+    let mut conn = ConnectionState::default();
+    let in_seconds = |seconds: u64| {
+        let mut arg = vec![ExpireParameter::TAG_EXPIREIN_SECONDS];
+        arg.extend(seconds.to_le_bytes());
+        arg
+    };
+    let (ttl_100, ttl_200) = (in_seconds(100), in_seconds(200));
+    let on_create: &[u8] = &[1];
+    let mut run = |command_type: CommandType, arguments: Vec<&[u8]>| {
+        let cmd = Command {
+            command_type,
+            arguments,
+        };
+        cmd.execute(&store, &mut conn, &server_state).unwrap();
+    };
+    let before = current_millis();
+    run(CommandType::ListPushEx, vec![b"l", &ttl_100, b"a"]);
+    run(CommandType::HLLAddEx, vec![b"h", &ttl_100, b"x"]);
+    run(
+        CommandType::IncrementInt,
+        vec![b"c", b"", &ttl_100, on_create],
+    );
+    // existing keys: LPUSHEX refreshes the TTL, an OnCreate increment keeps it
+    run(CommandType::ListPushEx, vec![b"l", &ttl_200, b"b"]);
+    run(
+        CommandType::IncrementInt,
+        vec![b"c", b"", &ttl_200, on_create],
+    );
+    let after = current_millis();
+    let db = store.db(0).read();
+    let deadline = |key: &str| {
+        db.get(key)
+            .unwrap()
+            .get_expire()
+            .unwrap()
+            .load(std::sync::atomic::Ordering::Relaxed)
+    };
+    for (key, seconds) in [("l", 200), ("h", 100), ("c", 100)] {
+        let range = before + seconds * 1000..=after + seconds * 1000;
+        assert!(range.contains(&deadline(key)), "ttl of {} is wrong", key);
+    }
+    assert_eq!(
+        db.get("c").unwrap().get_data().srlz_simple(),
+        DataType::Int(IntType::new(2)).srlz_simple()
+    );
+}
+#[test]
+fn missing_key_reads_test() {
+    let (store, server_state) = test_store(1);
+    let mut conn = ConnectionState::default();
+    let zero = 0u32.to_le_bytes();
+    let reads: Vec<(CommandType, Vec<&[u8]>)> = vec![
+        (CommandType::ListLength, vec![b"missing"]),
+        (CommandType::ListPop, vec![b"missing"]),
+        (CommandType::ListRange, vec![b"missing", &zero, &zero]),
+        (CommandType::HLLCount, vec![b"missing"]),
+        (CommandType::JsonGet, vec![b"missing"]),
+        (CommandType::JsonGet, vec![b"missing", b"$.a"]),
+        (CommandType::JsonType, vec![b"missing"]),
+        (CommandType::JsonArrLen, vec![b"missing"]),
+    ];
+    for (command_type, arguments) in reads {
+        let cmd = Command {
+            command_type,
+            arguments,
+        };
+        let reply = cmd.execute(&store, &mut conn, &server_state);
+        assert_eq!(reply, Ok(Some(srlz_nil())), "{:?}", cmd.command_type);
+    }
+}
+#[test]
+fn active_expiry_test() {
+    let state = DataState::new();
+    let now = current_millis();
+    let int = |i: i32| DataType::Int(IntType::new(i));
+    let _ = state.set("a", int(1), ExpireParameter::EXPIREAT(now + 5));
+    let _ = state.set("b", int(2), ExpireParameter::EXPIREAT(now + 5));
+    let _ = state.set("c", int(3), ExpireParameter::EXPIREAT(now + 5));
+    // extended TTL leaves a stale index entry that must not remove the key
+    assert!(state.expire("b", now + 60_000, ExpireCondition::Always));
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    state.maintenance_work(1);
+    assert_eq!(state.data.len(), 2);
+    state.maintenance_work(10);
+    assert_eq!(state.data.len(), 1);
+    assert!(state.data.contains_key("b"));
+}
+#[test]
+fn expire_parameter_encoding_test() {
+    let mut in_seconds = vec![ExpireParameter::TAG_EXPIREIN_SECONDS];
+    in_seconds.extend(100u64.to_le_bytes());
+    let before = current_millis();
+    let deadline = ExpireParameter::from(&in_seconds)
+        .unwrap()
+        .calc_new_expire(None)
+        .unwrap();
+    assert!(deadline >= before + 100_000 && deadline <= current_millis() + 100_000);
+    // 64 bit relative TTLs no longer wrap at ~49 days
+    let mut long_ttl = vec![ExpireParameter::TAG_EXPIREIN_MILLIS];
+    long_ttl.extend((u32::MAX as u64 * 4).to_le_bytes());
+    assert!(
+        ExpireParameter::from(&long_ttl)
+            .unwrap()
+            .calc_new_expire(None)
+            .unwrap()
+            > before + u32::MAX as u64
+    );
+    assert!(ExpireParameter::from(&[])
+        .unwrap()
+        .calc_new_expire(None)
+        .is_none());
+    assert!(ExpireParameter::from(&[9]).is_err());
+    assert!(ExpireParameter::from(&[ExpireParameter::TAG_EXPIREAT_MILLIS, 1, 2]).is_err());
+    // KEEPTTL on a key without TTL used to panic
+    let state = DataState::new();
+    let _ = state.set("k", DataType::Int(IntType::new(1)), ExpireParameter::None);
+    let keep = ExpireParameter::from(&[ExpireParameter::TAG_KEEPTTL]).unwrap();
+    assert!(state.set("k", DataType::Int(IntType::new(2)), keep).is_ok());
+    assert!(state.get("k").unwrap().get_expire().is_none());
+    // an absolute deadline of 0 has elapsed, it is not stored as no TTL
+    let mut at_epoch = vec![ExpireParameter::TAG_EXPIREAT_SECONDS];
+    at_epoch.extend(0u64.to_le_bytes());
+    let at_epoch = ExpireParameter::from(&at_epoch).unwrap();
+    assert!(state
+        .set("k", DataType::Int(IntType::new(3)), at_epoch)
+        .is_ok());
+    assert!(state.get("k").is_none());
+}
+#[test]
+fn glob_and_scan_test() {
+    assert!(glob_match(b"user:*", b"user:42"));
+    assert!(glob_match(b"h?llo", b"hello"));
+    assert!(glob_match(b"h[a-e]llo", b"hello"));
+    assert!(!glob_match(b"h[^e]llo", b"hello"));
+    assert!(glob_match(b"a\\*b", b"a*b"));
+    assert!(!glob_match(b"a\\*b", b"axb"));
+    assert!(glob_match(b"*a*b*", b"xxaxxbxx"));
+    let state = DataState::new();
+    for i in 0..500 {
+        let _ = state.set(
+            &format!("key:{}", i),
+            DataType::Int(IntType::new(i)),
+            ExpireParameter::None,
+        );
+    }
+    // every key is returned exactly once whatever the count
+    let mut seen: Vec<String> = Vec::new();
+    let mut cursor: u64 = 0;
+    loop {
+        let (next, keys) = state.scan(cursor, 7, |_, _| true);
+        assert!(keys.len() <= 7);
+        seen.extend(keys);
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+    assert_eq!(seen.len(), 500);
+    seen.sort();
+    seen.dedup();
+    assert_eq!(seen.len(), 500);
+    let (_, matched) = state.scan(0, 10_000, |k, _| glob_match(b"key:1?", k.as_bytes()));
+    assert_eq!(matched.len(), 10);
+    assert!(state.random_key().is_some());
+    assert!(DataState::new().random_key().is_none());
+}
+#[test]
+fn rename_copy_test() {
+    let state = DataState::new();
+    let mut in_seconds = vec![ExpireParameter::TAG_EXPIREIN_SECONDS];
+    in_seconds.extend(100u64.to_le_bytes());
+    let _ = state.set(
+        "stats:current",
+        DataType::Int(IntType::new(7)),
+        ExpireParameter::from(&in_seconds).unwrap(),
+    );
+    let _ = state.set(
+        "taken",
+        DataType::Int(IntType::new(1)),
+        ExpireParameter::None,
+    );
+    assert_eq!(state.rename("stats:current", "taken", true), Ok(false));
+    assert_eq!(state.rename("stats:current", "stats:old", false), Ok(true));
+    assert!(state.get("stats:current").is_none());
+    // the TTL moves with the value
+    assert!(state.get("stats:old").unwrap().get_expire().is_some());
+    assert!(state.rename("missing", "other", false).is_err());
+    assert_eq!(state.copy("stats:old", "taken", false), Ok(false));
+    assert_eq!(state.copy("stats:old", "taken", true), Ok(true));
+    assert_eq!(state.copy("missing", "other", true), Ok(false));
+    assert!(state.copy("taken", "taken", true).is_err());
+    match state.get("taken").unwrap().get_data() {
+        DataType::Int(_) => {}
+        _ => panic!("copy changed the type"),
+    }
+    assert!(state.get("stats:old").is_some());
+}
+#[test]
+fn databases_test() {
+    let store = DataStore::new(4, UNLIMITED);
+    let _ = store
+        .db(0)
+        .read()
+        .set("k", DataType::Int(IntType::new(1)), ExpireParameter::None);
+    assert!(store.db(1).read().get("k").is_none());
+    assert_eq!(store.move_key("k", 0, 1), Ok(true));
+    assert!(store.db(0).read().get("k").is_none());
+    assert!(store.db(1).read().get("k").is_some());
+    // an existing destination key is left alone
+    let _ = store
+        .db(0)
+        .read()
+        .set("k", DataType::Int(IntType::new(2)), ExpireParameter::None);
+    assert_eq!(store.move_key("k", 0, 1), Ok(false));
+    assert_eq!(store.move_key("missing", 1, 0), Ok(false));
+    assert!(store.move_key("k", 1, 1).is_err());
+    store.swap(1, 3);
+    assert!(store.db(1).read().is_empty());
+    assert!(store.db(3).read().get("k").is_some());
+    store.db(3).write().flush();
+    assert!(store.db(0).read().get("k").is_some());
+    store.flush_all();
+    assert!(store.dbs().iter().all(|db| db.read().is_empty()));
+    assert!(store.parse_index(&4u32.to_le_bytes()).is_err());
+    assert_eq!(store.parse_index(&3u32.to_le_bytes()), Ok(3));
+}
+#[test]
+fn memory_eviction_test() {
+    let state = DataState::new();
+    let _ = state.set(
+        "s",
+        DataType::String(StringType::new("x".repeat(1000))),
+        ExpireParameter::None,
+    );
+    let with_string = state.used_memory();
+    assert!(with_string > 1000);
+    // in place updates are accounted when the guard is dropped
+    {
+        let mut value = state.get_mut("s").unwrap();
+        if let DataType::String(s) = value.value_mut().get_data_mut() {
+            s.append(&[b'y'; 4000]).unwrap();
+        }
+    }
+    assert!(state.used_memory() >= with_string + 3000);
+    state.remove("s");
+    assert_eq!(state.used_memory(), 0);
+
+    let limit = MemoryLimit {
+        max_memory: 20_000,
+        policy: EvictionPolicy::AllKeysLru,
+        samples: 5,
+    };
+    let store = DataStore::new(2, limit);
+    for i in 0..100 {
+        let _ = store.db(i % 2).read().set(
+            &format!("key:{}", i),
+            DataType::String(StringType::new("v".repeat(500))),
+            ExpireParameter::None,
+        );
+    }
+    assert!(store.used_memory() > 20_000);
+    assert!(store.reserve_memory(&mut Vec::new()).is_ok());
+    assert!(store.used_memory() <= 20_000);
+    assert!(store.evicted_keys() > 0);
+    // volatile policies can not evict keys without TTL
+    let limit = MemoryLimit {
+        policy: EvictionPolicy::VolatileTtl,
+        ..limit
+    };
+    let store = DataStore::new(1, limit);
+    for i in 0..100 {
+        let _ = store.db(0).read().set(
+            &format!("key:{}", i),
+            DataType::String(StringType::new("v".repeat(500))),
+            ExpireParameter::None,
+        );
+    }
+    assert!(store.reserve_memory(&mut Vec::new()).is_err());
+}
+#[test]
+fn memory_by_type_test() {
+    let state = DataState::new();
+    let _ = state.set("i", DataType::Int(IntType::new(1)), ExpireParameter::None);
+    let _ = state.set(
+        "l",
+        DataType::List(ListType::new((0..1000).map(|i| i.to_string()).collect())),
+        ExpireParameter::None,
+    );
+    let mut totals = std::collections::BTreeMap::new();
+    state.memory_by_type(&mut totals);
+    assert_eq!(totals["int"].0, 1);
+    assert_eq!(totals["list"].0, 1);
+    // sampled list estimate still accounts for every element
+    assert!(totals["list"].1 > 1000 * std::mem::size_of::<StringType>());
+    assert_eq!(totals["int"].1 + totals["list"].1, state.used_memory());
+}
+#[test]
+fn object_introspection_test() {
+    let state = DataState::new();
+    let before = current_millis();
+    let _ = state.set("h", DataType::HLL(HLLType::new()), ExpireParameter::None);
+    let value = state.peek("h").unwrap();
+    assert_eq!(value.get_data().encoding(), "dense");
+    assert!(value.created() >= before && value.created() <= current_millis());
+    assert!(value.get_data().serialized_len() > 0);
+    drop(value);
+    assert!(state.peek("missing").is_none());
+    let _ = state.rename("h", "h2", false);
+    assert!(state.peek("h2").unwrap().created() >= before);
+}
+#[test]
+fn memory_usage_access_test() {
+    let (store, server_state) = test_store(1);
+    let _ = store.db(0).read().set(
+        "k",
+        DataType::String(StringType::new("value".to_owned())),
+        ExpireParameter::None,
+    );
+    let cmd = Command {
+        command_type: CommandType::MemoryUsage,
+        arguments: vec![b"k"],
+    };
+    let reply = cmd.execute(&store, &mut ConnectionState::default(), &server_state);
+    assert!(reply.unwrap().is_some());
+    let frequency = || store.db(0).read().peek("k").unwrap().frequency(lru_clock());
+    // the first access from the initial counter always increments it
+    assert_eq!(frequency(), LFU_INIT);
+    drop(store.db(0).read().get("k"));
+    assert_eq!(frequency(), LFU_INIT + 1);
+}
+#[test]
+fn snapshot_roundtrip_test() {
+    let store = DataStore::new(2, UNLIMITED);
+    let mut hll = HLLType::new();
+    hll.get_mut().add("a");
+    hll.get_mut().add("b");
+    let mut in_seconds = vec![ExpireParameter::TAG_EXPIREIN_SECONDS];
+    in_seconds.extend(100u64.to_le_bytes());
+    {
+        let db0 = store.db(0).read();
+        let _ = db0.set("i", DataType::Int(IntType::new(-3)), ExpireParameter::None);
+        let _ = db0.set(
+            "s",
+            DataType::String(StringType::from_bytes(vec![0, 159, 146, 150])),
+            ExpireParameter::from(&in_seconds).unwrap(),
+        );
+        let _ = db0.set("h", DataType::HLL(hll), ExpireParameter::None);
+    }
+    {
+        let db1 = store.db(1).read();
+        let _ = db1.set(
+            "f",
+            DataType::Float(FloatType::new(1.5)),
+            ExpireParameter::None,
+        );
+        let _ = db1.set(
+            "l",
+            DataType::List(ListType::new(vec!["x".to_owned(), "y".to_owned()])),
+            ExpireParameter::None,
+        );
+        let doc = serde_json::json!({"a": [1, 2, {"b": null}]});
+        let _ = db1.set(
+            "j",
+            DataType::Json(JsonType::new(doc)),
+            ExpireParameter::None,
+        );
+    }
+    let mut bytes: Vec<u8> = Vec::new();
+    write_snapshot(&store, &mut bytes).unwrap();
+    bytes.extend(crc32fast::hash(&bytes).to_le_bytes());
+
+    let restored = DataStore::new(2, UNLIMITED);
+    assert_eq!(load_snapshot(&bytes, &restored), Ok(6));
+    let db0 = restored.db(0).read();
+    let db1 = restored.db(1).read();
+    assert!(db0.get("s").unwrap().get_expire().is_some());
+    assert!(db0.get("i").unwrap().get_expire().is_none());
+    for (db, key) in [
+        (&db0, "i"),
+        (&db0, "s"),
+        (&db0, "h"),
+        (&db1, "f"),
+        (&db1, "l"),
+        (&db1, "j"),
+    ] {
+        let original = if ["i", "s", "h"].contains(&key) {
+            store.db(0)
+        } else {
+            store.db(1)
+        };
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        encode_value(original.read().get(key).unwrap().get_data(), &mut a);
+        encode_value(db.get(key).unwrap().get_data(), &mut b);
+        assert_eq!(a, b, "value of {} changed", key);
+    }
+    // any damaged byte is detected by the checksum
+    let mut damaged = bytes.clone();
+    damaged[20] ^= 1;
+    assert!(load_snapshot(&damaged, &DataStore::new(2, UNLIMITED)).is_err());
+    assert!(load_snapshot(&bytes[..bytes.len() - 1], &DataStore::new(2, UNLIMITED)).is_err());
+    assert!(load_snapshot(&bytes, &DataStore::new(1, UNLIMITED)).is_err());
+}
+#[test]
+fn snapshot_swap_test() {
+    // swaps the databases once the first entries are written, between two shard copies
+    struct SwappingWriter<'a> {
+        store: &'a DataStore,
+        writes: usize,
+    }
+    impl std::io::Write for SwappingWriter<'_> {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes += 1;
+            if self.writes == 20 {
+                self.store.swap(0, 1);
+            }
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let store = DataStore::new(2, UNLIMITED);
+    for i in 0..1000 {
+        let _ = store.db(0).read().set(
+            &format!("key:{}", i),
+            DataType::Int(IntType::new(i)),
+            ExpireParameter::None,
+        );
+    }
+    let mut writer = SwappingWriter {
+        store: &store,
+        writes: 0,
+    };
+    let result = write_snapshot(&store, &mut writer);
+    assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::Interrupted);
+    // the databases stay consistent without a concurrent swap
+    let restored = DataStore::new(2, UNLIMITED);
+    assert_eq!(load_snapshot(&snapshot_bytes(&store), &restored), Ok(1000));
+    assert_eq!(restored.db(1).read().len(), 1000);
+}
+#[test]
+fn aof_replay_and_rewrite_test() {
+    let (store, server_state) = test_store(2);
+    let path = std::env::temp_dir().join(format!("echors-{}.aof", uuid::Uuid::new_v4()));
+    let aof = Arc::new(Aof::open(path.clone(), FsyncPolicy::Always).unwrap());
+    server_state.write().aof = Some(aof.clone());
+
+    let mut conn = ConnectionState::default();
+    let mut in_seconds = vec![ExpireParameter::TAG_EXPIREIN_SECONDS];
+    in_seconds.extend(100u64.to_le_bytes());
+    let db1 = 1u32.to_le_bytes();
+    let ttl = 50u64.to_le_bytes();
+    let mut regs = vec![4u8];
+    regs.extend([3u8; 16]);
+    let commands: Vec<(CommandType, Vec<&[u8]>)> = vec![
+        (CommandType::SetString, vec![b"s", b"v", &in_seconds]),
+        (CommandType::Append, vec![b"b", &[0, 159, 146, 150]]),
+        (CommandType::Select, vec![&db1]),
+        (CommandType::ListPush, vec![b"l", b"x", b"y"]),
+        (CommandType::Expire, vec![b"l", &ttl]),
+        (CommandType::ListPush, vec![b"e", b"x"]),
+        (CommandType::ListPop, vec![b"e"]),
+        (CommandType::Expire, vec![b"e", &ttl]),
+        (CommandType::HLLLoad, vec![b"h", &regs]),
+        (CommandType::JsonSet, vec![b"j", b"$", br#"{"a":[1]}"#]),
+    ];
+    for (command_type, arguments) in commands {
+        let cmd = Command {
+            command_type,
+            arguments,
+        };
+        cmd.execute(&store, &mut conn, &server_state).unwrap();
+    }
+    // the replayed copy must hold the same values and the same absolute deadlines
+    let assert_same = |restored: &DataStore| {
+        for (db, key) in [(0, "s"), (0, "b"), (1, "l"), (1, "e"), (1, "h"), (1, "j")] {
+            let (original, copy) = (store.db(db).read(), restored.db(db).read());
+            let (original, copy) = (original.get(key).unwrap(), copy.get(key).unwrap());
+            let (mut a, mut b) = (Vec::new(), Vec::new());
+            encode_value(original.get_data(), &mut a);
+            encode_value(copy.get_data(), &mut b);
+            assert_eq!(a, b, "value of {} changed", key);
+            let deadline = |w: &DataWrapper| {
+                w.get_expire()
+                    .map(|e| e.load(std::sync::atomic::Ordering::Relaxed))
+            };
+            assert_eq!(
+                deadline(&original),
+                deadline(&copy),
+                "ttl of {} changed",
+                key
+            );
+        }
+    };
+    let replay_state = Arc::new(RwLock::new(ServerState::new(
+        "test",
+        server_state.read().config.clone(),
+    )));
+    let restored = Arc::new(DataStore::new(2, UNLIMITED));
+    assert_eq!(aof::replay(&path, &restored, &replay_state), Ok(11));
+    assert_same(&restored);
+
+    // a record cut by a crash is dropped, the file is usable again afterwards
+    let size = std::fs::metadata(&path).unwrap().len();
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes.extend([20, 0, 0, 0, 2, 0]);
+    std::fs::write(&path, &bytes).unwrap();
+    let restored = Arc::new(DataStore::new(2, UNLIMITED));
+    assert_eq!(aof::replay(&path, &restored, &replay_state), Ok(11));
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), size);
+
+    // the rewrite keeps one command per key whatever the history was
+    for _ in 0..50 {
+        let cmd = Command {
+            command_type: CommandType::Append,
+            arguments: vec![b"c", b"!"],
+        };
+        cmd.execute(&store, &mut conn, &server_state).unwrap();
+    }
+    let size = std::fs::metadata(&path).unwrap().len();
+    let aof = Arc::new(Aof::open(path.clone(), FsyncPolicy::Always).unwrap());
+    server_state.write().aof = Some(aof.clone());
+    aof.rewrite_in_background(&store).unwrap();
+    while aof.rewriting() {
+        std::thread::sleep(std::time::Duration::from_millis(5));
+    }
+    assert!(aof.current_size() < size);
+    assert_eq!(aof.current_size(), std::fs::metadata(&path).unwrap().len());
+    let restored = Arc::new(DataStore::new(2, UNLIMITED));
+    aof::replay(&path, &restored, &replay_state).unwrap();
+    assert_same(&restored);
+
+    // writes made while the shards are copied are neither lost nor applied twice
+    aof.rewrite_in_background(&store).unwrap();
+    let mut written = 0;
+    while aof.rewriting() || written < 64 {
+        let key = format!("w{}", written % 32);
+        let cmd = Command {
+            command_type: CommandType::Append,
+            arguments: vec![key.as_bytes(), b"!"],
+        };
+        cmd.execute(&store, &mut conn, &server_state).unwrap();
+        written += 1;
+    }
+    let restored = Arc::new(DataStore::new(2, UNLIMITED));
+    aof::replay(&path, &restored, &replay_state).unwrap();
+    assert_same(&restored);
+    for key in (0..32).map(|i| format!("w{}", i)).chain(["c".to_owned()]) {
+        let (original, copy) = (store.db(1).read(), restored.db(1).read());
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        encode_value(original.get(&key).unwrap().get_data(), &mut a);
+        encode_value(copy.get(&key).unwrap().get_data(), &mut b);
+        assert_eq!(a, b, "value of {} changed", key);
+    }
+
+    // writes spanning shards and databases only copy what they touch and stay exact
+    let dataset = |store: &DataStore| {
+        let mut entries = Vec::new();
+        for (index, db) in store.dbs().iter().enumerate() {
+            let state = db.read();
+            for shard in 0..state.shard_count() {
+                for (key, deadline, data) in state.copy_shard(shard) {
+                    let mut value = Vec::new();
+                    encode_value(&data, &mut value);
+                    entries.push((index, key, deadline, value));
+                }
+            }
+        }
+        entries.sort();
+        entries
+    };
+    for i in 0..20_000 {
+        let _ = store.db(0).read().set(
+            &format!("bulk:{}", i),
+            DataType::Int(IntType::new(i)),
+            ExpireParameter::None,
+        );
+    }
+    aof.rewrite_in_background(&store).unwrap();
+    let (db0, db1) = (0u32.to_le_bytes(), 1u32.to_le_bytes());
+    let mut written = 0;
+    while aof.rewriting() || written < 200 {
+        let (a, b) = (format!("x{}", written % 32), format!("y{}", written % 17));
+        let (command_type, arguments): (CommandType, Vec<&[u8]>) = match written % 40 {
+            _ if written == 150 => (CommandType::FlushDb, vec![]),
+            19 => (CommandType::SwapDb, vec![&db0, &db1]),
+            n if n % 4 == 0 => (
+                CommandType::MSet,
+                vec![a.as_bytes(), b"1", b.as_bytes(), b"2"],
+            ),
+            n if n % 4 == 1 => (CommandType::Rename, vec![a.as_bytes(), b.as_bytes()]),
+            n if n % 4 == 2 => (CommandType::Move, vec![b.as_bytes(), &db0]),
+            _ => (CommandType::Copy, vec![b.as_bytes(), a.as_bytes(), &[1]]),
+        };
+        let cmd = Command {
+            command_type,
+            arguments,
+        };
+        let _ = cmd.execute(&store, &mut conn, &server_state);
+        written += 1;
+    }
+    let restored = Arc::new(DataStore::new(2, UNLIMITED));
+    aof::replay(&path, &restored, &replay_state).unwrap();
+    assert!(dataset(&restored) == dataset(&store), "datasets differ");
+    let _ = std::fs::remove_file(&path);
+}
+#[test]
+fn aof_deadline_test() {
+    let (store, server_state) = test_store(2);
+    let path = std::env::temp_dir().join(format!("echors-{}.aof", uuid::Uuid::new_v4()));
+    let aof = Arc::new(Aof::open(path.clone(), FsyncPolicy::Always).unwrap());
+    server_state.write().aof = Some(aof.clone());
+    let mut conn = ConnectionState::default();
+    let tagged = |tag: u8, value: u64| {
+        let mut arg = vec![tag];
+        arg.extend(value.to_le_bytes());
+        arg
+    };
+    let in_millis = tagged(ExpireParameter::TAG_EXPIREIN_MILLIS, 100_000);
+    let in_seconds = tagged(ExpireParameter::TAG_EXPIREIN_SECONDS, 50);
+    let ttl = 200u64.to_le_bytes();
+    let (zero, one) = (0u32.to_le_bytes(), 1u32.to_le_bytes());
+    let commands: Vec<(CommandType, Vec<&[u8]>)> = vec![
+        (CommandType::SetString, vec![b"s", b"v", &in_millis]),
+        (CommandType::SetInt, vec![b"e", &one]),
+        (CommandType::Expire, vec![b"e", &ttl]),
+        (CommandType::GetEx, vec![b"s", &in_seconds]),
+        (CommandType::SwapDb, vec![&zero, &one]),
+    ];
+    let mut deadlines = Vec::new();
+    for (command_type, arguments) in commands {
+        let key = std::str::from_utf8(arguments[0]).unwrap().to_owned();
+        let cmd = Command {
+            command_type,
+            arguments,
+        };
+        cmd.execute(&store, &mut conn, &server_state).unwrap();
+        let db = store.db(0).read();
+        let deadline = db.peek(&key).and_then(|w| {
+            w.get_expire()
+                .map(|e| e.load(std::sync::atomic::Ordering::Relaxed))
+        });
+        deadlines.push(deadline);
+    }
+    let bytes = std::fs::read(&path).unwrap();
+    let mut payloads = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let len = u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap()) as usize;
+        payloads.push(&bytes[pos + 4..pos + 4 + len]);
+        pos += 4 + len;
+    }
+    let records: Vec<Command> = payloads
+        .iter()
+        .map(|p| Parser::parse(p).unwrap())
+        .filter(|c| c.command_type != CommandType::Select)
+        .collect();
+    // relative expiries are logged as the deadline the key got, not one computed again
+    let logged = |arg: &[u8]| Some(u64::from_le_bytes(arg[arg.len() - 8..].try_into().unwrap()));
+    assert_eq!(logged(records[0].arguments[2]), deadlines[0]);
+    assert_eq!(records[2].command_type, CommandType::PExpireAt);
+    assert_eq!(logged(records[2].arguments[1]), deadlines[2]);
+    assert_eq!(logged(records[3].arguments[1]), deadlines[3]);
+    // writes without a relative expiry are logged as received
+    assert_eq!(records[4].arguments, vec![&zero[..], &one[..]]);
+    let _ = std::fs::remove_file(&path);
+}
+#[test]
+fn jsonlines_round_trip_test() {
+    let (source, server_state) = test_store(1);
+    let target = Arc::new(DataStore::new(1, UNLIMITED));
+    let mut conn = ConnectionState::default();
+    let mut run = |store: &Arc<DataStore>, command_type: CommandType, arguments: Vec<&[u8]>| {
+        let cmd = Command {
+            command_type,
+            arguments,
+        };
+        cmd.execute(store, &mut conn, &server_state).unwrap()
+    };
+    let items: Vec<Vec<u8>> = (0..150).map(|i| i.to_string().into_bytes()).collect();
+    let mut lpush: Vec<&[u8]> = vec![b"list"];
+    lpush.extend(items.iter().map(|i| i.as_slice()));
+    run(
+        &source,
+        CommandType::SetInt,
+        vec![b"int", &(-7i32).to_le_bytes()],
+    );
+    run(
+        &source,
+        CommandType::SetFloat,
+        vec![b"float", &1.5f32.to_le_bytes()],
+    );
+    run(&source, CommandType::SetString, vec![b"string", b"text"]);
+    run(
+        &source,
+        CommandType::Append,
+        vec![b"binary", &[0xff, 0, 0xfe]],
+    );
+    run(&source, CommandType::ListPush, lpush);
+    run(&source, CommandType::ListPush, vec![b"empty", b"x"]);
+    run(&source, CommandType::ListPop, vec![b"empty"]);
+    run(&source, CommandType::HLLAdd, vec![b"hll", b"a", b"b"]);
+    run(
+        &source,
+        CommandType::JsonSet,
+        vec![b"json", b"$", br#"{"a":[1,"x"]}"#],
+    );
+    run(
+        &source,
+        CommandType::PExpire,
+        vec![b"list", &100_000u64.to_le_bytes()],
+    );
+    let keys = [
+        "int", "float", "string", "binary", "list", "empty", "hll", "json",
+    ];
+    for key in keys {
+        let dump = run(&source, CommandType::Dump, vec![key.as_bytes()]).unwrap();
+        let mut line = jsonlines::export_line(&dump[5..]).unwrap();
+        line.insert("key".to_owned(), serde_json::json!(key));
+        let line = serde_json::to_string(&line).unwrap();
+        let record: serde_json::Value = serde_json::from_str(&line).unwrap();
+        for (command_type, arguments) in jsonlines::import_commands(&record).unwrap() {
+            run(
+                &target,
+                command_type,
+                arguments.iter().map(Vec::as_slice).collect(),
+            );
+        }
+    }
+    for key in keys {
+        let (source_db, target_db) = (source.db(0).read(), target.db(0).read());
+        let (expected, actual) = (source_db.get(key).unwrap(), target_db.get(key).unwrap());
+        let (mut expected_bytes, mut actual_bytes) = (Vec::new(), Vec::new());
+        encode_value(expected.get_data(), &mut expected_bytes);
+        encode_value(actual.get_data(), &mut actual_bytes);
+        assert_eq!(expected_bytes, actual_bytes, "value of {} differs", key);
+        let deadline = |entry: &DataWrapper| {
+            entry
+                .get_expire()
+                .map(|d| d.load(std::sync::atomic::Ordering::Relaxed))
+        };
+        match (deadline(&expected), deadline(&actual)) {
+            (Some(expected), Some(actual)) => assert!(expected.abs_diff(actual) < 1000),
+            (expected, actual) => assert_eq!(expected, actual, "ttl of {} differs", key),
+        }
+    }
+}
+#[test]
+fn dump_restore_test() {
+    let (store, server_state) = test_store(1);
+    let mut conn = ConnectionState::default();
+    let mut run = |command_type: CommandType, arguments: Vec<&[u8]>| {
+        let cmd = Command {
+            command_type,
+            arguments,
+        };
+        cmd.execute(&store, &mut conn, &server_state)
+    };
+    let mut in_seconds = vec![ExpireParameter::TAG_EXPIREIN_SECONDS];
+    in_seconds.extend(100u64.to_le_bytes());
+    run(CommandType::ListPushEx, vec![b"l", &in_seconds, b"a", b"b"]).unwrap();
+    let reply = run(CommandType::Dump, vec![b"l"]).unwrap().unwrap();
+    let blob = reply[5..].to_vec();
+    let (data, ttl) = decode_dump(&blob).unwrap();
+    assert_eq!(data.type_name(), "list");
+    assert!(ttl.is_some_and(|ttl| ttl > 99_000 && ttl <= 100_000));
+    assert_eq!(run(CommandType::Dump, vec![b"missing"]), Ok(Some(vec![7])));
+
+    // existing keys are only overwritten with REPLACE
+    assert!(run(CommandType::Restore, vec![b"l", &blob]).is_err());
+    assert_eq!(
+        run(
+            CommandType::Restore,
+            vec![b"l", &blob, b"", &[RESTORE_REPLACE]]
+        ),
+        Ok(None)
+    );
+    assert_eq!(run(CommandType::Restore, vec![b"c", &blob]), Ok(None));
+    assert_eq!(
+        run(CommandType::Restore, vec![b"p", &blob, &0u64.to_le_bytes()]),
+        Ok(None)
+    );
+    let past = (current_millis() - 1000).to_le_bytes();
+    let flags = [RESTORE_REPLACE | RESTORE_ABSTTL];
+    assert_eq!(
+        run(CommandType::Restore, vec![b"l", &blob, &past, &flags]),
+        Ok(None)
+    );
+    let mut damaged = blob.clone();
+    damaged[3] ^= 1;
+    assert!(run(CommandType::Restore, vec![b"d", &damaged]).is_err());
+    assert!(run(CommandType::Restore, vec![b"d", &blob[..blob.len() - 1]]).is_err());
+
+    let db = store.db(0).read();
+    assert!(db.get("l").is_none());
+    assert!(db.get("c").unwrap().get_expire().is_some());
+    assert!(db.get("p").unwrap().get_expire().is_none());
+    let (mut a, mut b) = (Vec::new(), Vec::new());
+    encode_value(&data, &mut a);
+    encode_value(db.get("p").unwrap().get_data(), &mut b);
+    assert_eq!(a, b);
+}
+#[test]
+fn replicated_apply_test() {
+    let (store, server_state) = test_store(1);
+    let (started, script_started) = std::sync::mpsc::channel();
+    // a script past its time limit holds the store until it ends
+    let script = {
+        let store = store.clone();
+        std::thread::spawn(move || {
+            let _exclusive = store.transaction_guard().unwrap();
+            store.set_script_busy(true);
+            started.send(()).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            store.set_script_busy(false);
+        })
+    };
+    script_started.recv().unwrap();
+    let cmd = Command {
+        command_type: CommandType::SetString,
+        arguments: vec![b"k", b"v"],
+    };
+    let mut conn = ConnectionState::default();
+    assert_eq!(
+        cmd.execute(&store, &mut conn, &server_state),
+        Err(BUSY.to_owned())
+    );
+    conn.replicated = true;
+    assert_eq!(cmd.apply(&store, &mut conn, &server_state), Ok(None));
+    assert!(store.db(0).read().peek("k").is_some());
+    script.join().unwrap();
+}
+#[test]
+fn replication_backlog_test() {
+    let store = DataStore::new(2, UNLIMITED);
+    let replication = Replication::new(64);
+    let set = Command {
+        command_type: CommandType::SetInt,
+        arguments: vec![b"k", &[1, 0, 0, 0]],
+    };
+    // nothing is kept before the first replica attaches
+    replication.feed().append(0, &set, None);
+    let (id, start) = match replication.attach(&store, "?", 0) {
+        Attach::Full { id, offset, .. } => (id, offset),
+        Attach::Continue { .. } => panic!("unknown history continued"),
+    };
+    assert_eq!(start, 0);
+    replication.feed().append(1, &set, None);
+    // a SELECT precedes the first record since the replica does not know the database
+    let records = replication.read(&id, start, 1024).unwrap();
+    assert_eq!(records.len(), 14 + 19);
+    assert!(replication
+        .read(&id, start + records.len() as u64, 1024)
+        .unwrap()
+        .is_empty());
+    assert!(matches!(
+        replication.attach(&store, &id, 14),
+        Attach::Continue { offset: 14, .. }
+    ));
+    assert!(matches!(
+        replication.attach(&store, "other", 10),
+        Attach::Full { .. }
+    ));
+    // old records leave the backlog once it is full, replicas behind need a full resync
+    for _ in 0..5 {
+        replication.feed().append(1, &set, None);
+    }
+    assert!(replication.read(&id, start, 1024).is_none());
+    assert!(matches!(
+        replication.attach(&store, &id, start),
+        Attach::Full { .. }
+    ));
+    assert!(replication.read("other", 0, 1024).is_none());
+}
+#[test]
+fn cluster_routing_test() {
+    assert_eq!(crc16(b"123456789"), 0x31C3);
+    assert_eq!(key_slot(b"foo"), 12182);
+    assert_eq!(key_slot(b"{user1000}.following"), key_slot(b"user1000"));
+    // an empty tag hashes the whole key
+    assert_eq!(key_slot(b"foo{}{bar}"), crc16(b"foo{}{bar}") % 16384);
+    assert_eq!(
+        CommandType::MSet.key_arguments(&[b"a", b"1", b"b", b"2"]),
+        vec![b"a" as &[u8], b"b"]
+    );
+    assert!(CommandType::Scan.key_arguments(&[&[0; 8]]).is_empty());
+
+    let nodes_path = std::env::temp_dir().join(format!("nodes-{}.echors", std::process::id()));
+    let ranges = vec![
+        ("127.0.0.1:3000".to_owned(), 0, 8191),
+        ("127.0.0.1:3001".to_owned(), 8192, 15000),
+    ];
+    let me = Topology::new("127.0.0.1:3000".to_owned(), nodes_path.clone(), &ranges).unwrap();
+    let exists = |_: &[u8]| true;
+    // "bar" is in slot 5061, "foo" in 12182 and "a" in 15495
+    assert!(me.route(&[b"bar"], false, exists).is_ok());
+    assert_eq!(
+        me.route(&[b"foo"], false, exists).unwrap_err(),
+        "MOVED 12182 127.0.0.1:3001"
+    );
+    assert_eq!(
+        me.route(&[b"foo", b"bar"], false, exists).unwrap_err(),
+        CROSSSLOT
+    );
+    assert!(me.route(&[b"{bar}1", b"{bar}2"], false, exists).is_ok());
+    assert!(me
+        .route(&[b"{a}"], false, exists)
+        .unwrap_err()
+        .starts_with("CLUSTERDOWN"));
+
+    // migrating 5061 away: missing keys are asked for on the target
+    me.set_slot(5061, SlotAction::Migrating("127.0.0.1:3001".to_owned()))
+        .unwrap();
+    assert!(me.route(&[b"bar"], false, exists).is_ok());
+    assert_eq!(
+        me.route(&[b"bar"], false, |_| false).unwrap_err(),
+        "ASK 5061 127.0.0.1:3001"
+    );
+    let other = Topology::new("127.0.0.1:3001".to_owned(), nodes_path.clone(), &ranges).unwrap();
+    other
+        .set_slot(5061, SlotAction::Importing("127.0.0.1:3000".to_owned()))
+        .unwrap();
+    assert!(other
+        .route(&[b"bar"], false, exists)
+        .unwrap_err()
+        .starts_with("MOVED"));
+    assert!(other.route(&[b"bar"], true, exists).is_ok());
+
+    // the new owner is saved and loaded back instead of the configured ranges
+    me.set_slot(5061, SlotAction::Node("127.0.0.1:3001".to_owned()))
+        .unwrap();
+    let reloaded = Topology::new("127.0.0.1:3000".to_owned(), nodes_path.clone(), &[]).unwrap();
+    assert_eq!(
+        reloaded.slot_ranges(),
+        vec![
+            ("127.0.0.1:3000".to_owned(), 0, 5060),
+            ("127.0.0.1:3001".to_owned(), 5061, 5061),
+            ("127.0.0.1:3000".to_owned(), 5062, 8191),
+            ("127.0.0.1:3001".to_owned(), 8192, 15000),
+        ]
+    );
+    assert!(reloaded
+        .route(&[b"bar"], false, exists)
+        .unwrap_err()
+        .starts_with("MOVED"));
+    std::fs::remove_file(&nodes_path).unwrap();
+}
+#[test]
+fn transaction_test() {
+    let (store, server_state) = test_store(1);
+    let run = |conn: &mut ConnectionState, command_type: CommandType, arguments: Vec<&[u8]>| {
+        Command {
+            command_type,
+            arguments,
+        }
+        .execute(&store, conn, &server_state)
+    };
+    // status byte of every reply in an EXEC array
+    let statuses = |reply: Vec<u8>| {
+        assert_eq!(reply[0], 8);
+        let mut rest = &reply[5..];
+        let mut statuses = Vec::new();
+        while !rest.is_empty() {
+            let len = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+            statuses.push(rest[4]);
+            rest = &rest[4 + len..];
+        }
+        statuses
+    };
+    let one = 1i32.to_le_bytes();
+    let (mut first, mut second) = (ConnectionState::default(), ConnectionState::default());
+    assert!(run(&mut first, CommandType::Exec, vec![]).is_err());
+    run(&mut first, CommandType::Multi, vec![]).unwrap();
+    assert!(run(&mut first, CommandType::Multi, vec![]).is_err());
+    first.queued.as_mut().unwrap().extend([
+        aof::encode(CommandType::SetInt, &[b"k", &one]),
+        aof::encode(CommandType::ListPush, &[b"l", b"a"]),
+        aof::encode(CommandType::ListPush, &[b"k", b"x"]),
+        aof::encode(CommandType::Get, &[b"k"]),
+    ]);
+    // errors of single commands do not stop the others
+    let reply = run(&mut first, CommandType::Exec, vec![]).unwrap().unwrap();
+    assert_eq!(statuses(reply), vec![1, 1, 2, 1]);
+    assert!(first.queued.is_none());
+
+    // a watched key written by another connection aborts the transaction
+    run(&mut first, CommandType::Watch, vec![b"k", b"missing"]).unwrap();
+    run(&mut second, CommandType::IncrementInt, vec![b"k", &one]).unwrap();
+    run(&mut first, CommandType::Multi, vec![]).unwrap();
+    first
+        .queued
+        .as_mut()
+        .unwrap()
+        .push(aof::encode(CommandType::Delete, &[b"l"]));
+    let reply = run(&mut first, CommandType::Exec, vec![]).unwrap().unwrap();
+    assert_eq!(reply, vec![7]);
+    assert!(store.db(0).read().peek("l").is_some());
+    assert!(first.watched.is_empty());
+
+    // keys created after WATCH count as changed, reads and UNWATCH do not
+    run(&mut first, CommandType::Watch, vec![b"k", b"missing"]).unwrap();
+    run(&mut second, CommandType::Get, vec![b"k"]).unwrap();
+    run(&mut first, CommandType::Multi, vec![]).unwrap();
+    let reply = run(&mut first, CommandType::Exec, vec![]).unwrap().unwrap();
+    assert_eq!(statuses(reply), Vec::<u8>::new());
+    run(&mut first, CommandType::Watch, vec![b"missing"]).unwrap();
+    run(&mut second, CommandType::SetInt, vec![b"missing", &one]).unwrap();
+    run(&mut first, CommandType::Multi, vec![]).unwrap();
+    assert_eq!(
+        run(&mut first, CommandType::Exec, vec![]).unwrap().unwrap(),
+        vec![7]
+    );
+    run(&mut first, CommandType::Watch, vec![b"missing"]).unwrap();
+    run(&mut first, CommandType::Unwatch, vec![]).unwrap();
+    run(&mut second, CommandType::Delete, vec![b"missing"]).unwrap();
+    run(&mut first, CommandType::Multi, vec![]).unwrap();
+    assert!(run(&mut first, CommandType::Exec, vec![]).unwrap().unwrap()[0] == 8);
+    first.queue_failed = true;
+    run(&mut first, CommandType::Multi, vec![]).unwrap();
+    run(&mut first, CommandType::Discard, vec![]).unwrap();
+    assert!(!first.queue_failed && first.queued.is_none());
+}
+#[test]
+fn scripting_test() {
+    let (store, server_state) = test_store(1);
+    let mut conn = ConnectionState::default();
+    let mut run = |command_type: CommandType, arguments: Vec<&[u8]>| {
+        Command {
+            command_type,
+            arguments,
+        }
+        .execute(&store, &mut conn, &server_state)
+    };
+    let one_key = 1u32.to_le_bytes();
+    let no_keys = 0u32.to_le_bytes();
+    let long = |n: u64| [vec![6], n.to_le_bytes().to_vec()].concat();
+    // read-modify-write on a key from KEYS with a limit from ARGV
+    let script: &[u8] = br#"
+        local count = echors.call("get", KEYS[1])
+        if count and count >= tonumber(ARGV[1]) then return {err = "limit reached"} end
+        echors.call("incrementint", KEYS[1])
+        return echors.call("get", KEYS[1])
+    "#;
+    let reply = run(CommandType::Eval, vec![script, &one_key, b"hits", b"2"]);
+    assert_eq!(reply.unwrap().unwrap(), long(1));
+    run(CommandType::Eval, vec![script, &one_key, b"hits", b"2"]).unwrap();
+    assert_eq!(
+        run(CommandType::Eval, vec![script, &one_key, b"hits", b"2"]),
+        Err("limit reached".to_owned())
+    );
+
+    // pcall hands command errors to the script, call raises them
+    let script: &[u8] = br#"
+        local reply = echors.pcall("listpush", "hits", "x")
+        return {reply.err ~= nil, 7, "s"}
+    "#;
+    let reply = run(CommandType::Eval, vec![script, &no_keys])
+        .unwrap()
+        .unwrap();
+    let expected = srlz_array(vec![
+        long(1),
+        long(7),
+        StringType::new("s".to_owned()).serialize(),
+    ]);
+    assert_eq!(reply, expected);
+    let script: &[u8] = br#"return echors.call("listpush", "hits", "x")"#;
+    assert!(run(CommandType::Eval, vec![script, &no_keys]).is_err());
+    let script: &[u8] = br#"return echors.call("multi")"#;
+    assert!(run(CommandType::Eval, vec![script, &no_keys]).is_err());
+
+    // EVALSHA runs scripts cached by SCRIPT LOAD or EVAL
+    let script: &[u8] = b"return #KEYS + #ARGV";
+    let reply = run(CommandType::ScriptLoad, vec![script]).unwrap().unwrap();
+    let sha = reply[5..].to_vec();
+    assert_eq!(
+        run(CommandType::EvalSha, vec![&sha, &one_key, b"a", b"b", b"c"]).unwrap(),
+        Some(long(3))
+    );
+    assert!(run(CommandType::EvalSha, vec![b"0000", &no_keys])
+        .unwrap_err()
+        .starts_with("NOSCRIPT"));
+    assert!(run(CommandType::ScriptKill, vec![])
+        .unwrap_err()
+        .starts_with("NOTBUSY"));
+
+    // a script that panics past its time limit still ends and clears BUSY
+    let scripts = server_state.read().scripts.clone();
+    let unwound = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _running = scripts.begin(&store);
+        store.set_script_busy(true);
+        panic!("script panicked");
+    }));
+    assert!(unwound.is_err());
+    assert!(run(CommandType::ScriptKill, vec![])
+        .unwrap_err()
+        .starts_with("NOTBUSY"));
+    let (started, other_started) = std::sync::mpsc::channel();
+    let other = {
+        let store = store.clone();
+        std::thread::spawn(move || {
+            let _exclusive = store.transaction_guard().unwrap();
+            started.send(()).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        })
+    };
+    other_started.recv().unwrap();
+    assert!(run(CommandType::SetString, vec![b"k", b"v"]).is_ok());
+    other.join().unwrap();
+}
+#[test]
+fn pubsub_test() {
+    let (store, server_state) = test_store(1);
+    let run = |conn: &mut ConnectionState, command_type: CommandType, arguments: Vec<&[u8]>| {
+        Command {
+            command_type,
+            arguments,
+        }
+        .execute(&store, conn, &server_state)
+    };
+    let long = |n: u64| Some([vec![6], n.to_le_bytes().to_vec()].concat());
+    let string = |s: &[u8]| StringType::from_bytes(s.to_vec()).serialize();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let (mut subscriber, mut publisher) = (ConnectionState::default(), ConnectionState::default());
+    let reply = run(
+        &mut subscriber,
+        CommandType::Subscribe,
+        vec![b"news", b"sport"],
+    );
+    assert_eq!(reply.unwrap(), long(2));
+    let reply = run(&mut subscriber, CommandType::PSubscribe, vec![b"n*"]);
+    assert_eq!(reply.unwrap(), long(3));
+    // push mode only accepts subscription commands
+    assert!(run(&mut subscriber, CommandType::Get, vec![b"k"]).is_err());
+
+    let reply = run(&mut publisher, CommandType::Publish, vec![b"news", b"hi"]);
+    assert_eq!(reply.unwrap(), long(2));
+    let subscription = subscriber.subscription.as_mut().unwrap();
+    let push = runtime.block_on(subscription.next_push()).unwrap();
+    let message = srlz_array(vec![string(b"message"), string(b"news"), string(b"hi")]);
+    assert_eq!(push.to_vec(), [vec![3], message].concat());
+    let push = runtime.block_on(subscription.next_push()).unwrap();
+    let message = srlz_array(vec![
+        string(b"pmessage"),
+        string(b"n*"),
+        string(b"news"),
+        string(b"hi"),
+    ]);
+    assert_eq!(push.to_vec(), [vec![3], message].concat());
+
+    let reply = run(&mut publisher, CommandType::PubSubChannels, vec![b"s*"]);
+    assert_eq!(
+        reply.unwrap().unwrap(),
+        [vec![4], string(b"sport")].concat()
+    );
+    let reply = run(
+        &mut publisher,
+        CommandType::PubSubNumSub,
+        vec![b"news", b"none"],
+    );
+    let counts = srlz_array(vec![long(1).unwrap(), long(0).unwrap()]);
+    assert_eq!(reply.unwrap().unwrap(), counts);
+
+    // leaving every channel and pattern ends push mode
+    let reply = run(&mut subscriber, CommandType::Unsubscribe, vec![]);
+    assert_eq!(reply.unwrap(), long(1));
+    let reply = run(&mut subscriber, CommandType::PUnsubscribe, vec![b"n*"]);
+    assert_eq!(reply.unwrap(), long(0));
+    assert!(subscriber.subscription.is_none());
+    assert!(run(&mut subscriber, CommandType::Get, vec![b"k"]).is_ok());
+    let reply = run(&mut publisher, CommandType::Publish, vec![b"news", b"hi"]);
+    assert_eq!(reply.unwrap(), long(0));
+
+    // a subscriber not reading its messages is cut off at the output buffer limit
+    let pubsub = PubSub::new(100);
+    let mut slow = pubsub.subscription();
+    pubsub.subscribe(&mut slow, b"flood", false);
+    for _ in 0..5 {
+        pubsub.publish(b"flood", &[0; 30]);
+    }
+    assert!(runtime.block_on(slow.next_push()).is_some());
+    assert!(runtime.block_on(slow.next_push()).is_none());
+    pubsub.remove(slow);
+    assert!(pubsub.active_channels(None).is_empty());
+}
+#[test]
+fn keyspace_events_test() {
+    let (store, server_state) = test_store(2);
+    let pubsub = server_state.read().pubsub.clone();
+    let events = Arc::new(KeyspaceEvents::new(pubsub.clone(), "KEg$lx"));
+    server_state.write().keyspace_events = events.clone();
+    let mut conn = ConnectionState::default();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    let mut subscription = pubsub.subscription();
+    pubsub.subscribe(&mut subscription, b"__keyspace@0__:*", true);
+    pubsub.subscribe(&mut subscription, b"__keyevent@0__:del", false);
+    pubsub.subscribe(&mut subscription, b"__keyspace@1__:*", true);
+    let mut run = |command_type: CommandType, arguments: Vec<&[u8]>| {
+        Command {
+            command_type,
+            arguments,
+        }
+        .execute(&store, &mut conn, &server_state)
+        .unwrap();
+    };
+    // channel and message of every push received so far
+    let mut received = || {
+        let mut pushes = Vec::new();
+        while let Ok(Some(push)) = runtime.block_on(async {
+            tokio::time::timeout(
+                std::time::Duration::from_millis(10),
+                subscription.next_push(),
+            )
+            .await
+        }) {
+            let mut items = Vec::new();
+            let mut rest = &push[6..];
+            while !rest.is_empty() {
+                let len = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+                items.push(rest[9..4 + len].to_vec());
+                rest = &rest[4 + len..];
+            }
+            let (channel, message) = (&items[items.len() - 2], &items[items.len() - 1]);
+            pushes.push((
+                String::from_utf8(channel.clone()).unwrap(),
+                String::from_utf8(message.clone()).unwrap(),
+            ));
+        }
+        pushes
+    };
+    let event = |channel: &str, message: &str| (channel.to_owned(), message.to_owned());
+
+    run(CommandType::SetString, vec![b"s", b"v"]);
+    run(CommandType::ListPush, vec![b"l", b"x"]);
+    // only keys that existed are deleted, hyperloglogs are not selected
+    run(CommandType::Delete, vec![b"s", b"missing"]);
+    run(CommandType::HLLAdd, vec![b"h", b"a"]);
+    // an expiry in the past deletes the key
+    run(CommandType::PExpireAt, vec![b"l", &1u64.to_le_bytes()]);
+    assert_eq!(
+        received(),
+        vec![
+            event("__keyspace@0__:s", "set"),
+            event("__keyspace@0__:l", "lpush"),
+            event("__keyspace@0__:s", "del"),
+            event("__keyevent@0__:del", "s"),
+            event("__keyspace@0__:l", "del"),
+            event("__keyevent@0__:del", "l"),
+        ]
+    );
+
+    // expired keys are reported by the maintenance, also those found expired on access
+    let in_millis = [
+        vec![ExpireParameter::TAG_EXPIREIN_MILLIS],
+        1u64.to_le_bytes().to_vec(),
+    ]
+    .concat();
+    run(CommandType::SetString, vec![b"a", b"v", &in_millis]);
+    run(CommandType::SetString, vec![b"b", b"v", &in_millis]);
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    run(CommandType::Get, vec![b"a"]);
+    crate::run_maintenance(&store, &events, 10);
+    let mut expired = received();
+    expired.sort();
+    assert_eq!(
+        expired,
+        vec![
+            event("__keyspace@0__:a", "expired"),
+            event("__keyspace@0__:a", "set"),
+            event("__keyspace@0__:b", "expired"),
+            event("__keyspace@0__:b", "set"),
+        ]
+    );
+
+    // a moved key is announced in both databases
+    run(CommandType::SetString, vec![b"m", b"v"]);
+    run(CommandType::Move, vec![b"m", &1u32.to_le_bytes()]);
+    assert_eq!(
+        received(),
+        vec![
+            event("__keyspace@0__:m", "set"),
+            event("__keyspace@0__:m", "move_from"),
+            event("__keyspace@1__:m", "move_to"),
+        ]
+    );
+}